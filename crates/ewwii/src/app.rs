@@ -8,10 +8,13 @@ use crate::{
         Cast, CastNone, DisplayExt, GtkWindowExt, ListModelExt, MonitorExt, NativeExt, ObjectExt,
         WidgetExt,
     },
-    opts::{WidgetAction, WidgetControlCommand},
+    opts::{CssCommand, WidgetAction, WidgetControlCommand},
     paths::EwwiiPaths,
     widgets::{
-        build_widget::build_gtk_widget, build_widget::WidgetInput,
+        animation::AnimationWidget,
+        build_widget::build_gtk_widget,
+        build_widget::WidgetInput,
+        tree_diff::{self, Patch},
         widget_definitions::WidgetRegistry,
     },
     window::{
@@ -24,7 +27,9 @@ use crate::{
     *,
 };
 use anyhow::anyhow;
+use ewwii_nbcl_impl::libraries::{Appearance, ColorScheme};
 use ewwii_plugin_api as epapi;
+use ewwii_shared_utils::ast::{hash_props, WidgetNode};
 use futures::future::FutureExt;
 use gdk::Monitor;
 use gtk4::Window;
@@ -40,6 +45,93 @@ use std::{
 };
 use tokio::sync::mpsc::UnboundedSender;
 
+/// Patch the widgets of an open window from `old_def` to `new_def`.
+///
+/// Returns false if the window has to be reopened instead, which is the case
+/// when its geometry or backend options changed.
+fn patch_window(
+    registry: &mut WidgetRegistry,
+    window: &EwwiiWindow,
+    old_def: &config::WindowDefinition,
+    new_def: &config::WindowDefinition,
+    dark_mode: bool,
+) -> bool {
+    if window.gtk_window.is_none() {
+        return false;
+    }
+    if hash_props(&old_def.props) != hash_props(&new_def.props) {
+        return false;
+    }
+
+    let mut patches = Vec::new();
+    tree_diff::diff_widget_trees(&old_def.root_widget, &new_def.root_widget, &mut patches);
+    log::debug!("Patching window '{}' with {} change(s)", window.name, patches.len());
+
+    for patch in patches {
+        let applied = match patch {
+            Patch::Props { old, new } => registry.patch_widget_props(old, new),
+            Patch::Replace { old, new } => {
+                registry.replace_widget(old, new).unwrap_or_else(|err| {
+                    log::error!("Failed to rebuild widget in '{}': {:?}", window.name, err);
+                    false
+                })
+            }
+        };
+        if !applied {
+            return false;
+        }
+    }
+
+    // a rebuilt root widget is missing the classes of the window
+    if let Some(root_widget) = window.root_widget() {
+        root_widget.add_css_class(&window.name);
+        set_color_scheme_class(&root_widget, dark_mode);
+    }
+    true
+}
+
+/// Wrap the root widget of a window with an open or close animation into an
+/// `AnimationWidget` playing them. The open animation is skipped if `play_open` is false.
+fn window_animation(
+    initiator: &WindowInitiator,
+    root_widget: &gtk4::Widget,
+    play_open: bool,
+) -> Option<AnimationWidget> {
+    if initiator.open_animation.is_none() && initiator.close_animation.is_none() {
+        return None;
+    }
+
+    let animation = AnimationWidget::new(root_widget);
+    if let Some(open) = initiator.open_animation.as_ref().filter(|_| play_open) {
+        animation.set_open(open.clone());
+    }
+    if let Some(close) = &initiator.close_animation {
+        animation.set_close(close.clone());
+    }
+    Some(animation)
+}
+
+/// Mark the root widget of a window with `dark` or `light`, following the color scheme.
+fn set_color_scheme_class(root_widget: &gtk4::Widget, dark_mode: bool) {
+    let (add, remove) = if dark_mode { ("dark", "light") } else { ("light", "dark") };
+    root_widget.remove_css_class(remove);
+    root_widget.add_css_class(add);
+}
+
+/// The color scheme GTK is set to, for desktops without the appearance portal.
+fn gtk_appearance() -> Appearance {
+    let Some(settings) = gtk4::Settings::default() else {
+        return Appearance::default();
+    };
+
+    let theme_name = settings.gtk_theme_name().unwrap_or_default();
+    let dark = settings.is_gtk_application_prefer_dark_theme()
+        || theme_name.ends_with("-dark")
+        || theme_name.ends_with(":dark");
+    let color_scheme = if dark { ColorScheme::Dark } else { ColorScheme::Light };
+    Appearance { color_scheme, accent_color: None }
+}
+
 fn register_active_plugin(lib: libloading::Library, id: String, version: String) -> Result<()> {
     let mut plugins =
         plugin::ACTIVE_PLUGINS.write().map_err(|_| anyhow!("Plugin registry is poisoned!"))?;
@@ -60,6 +152,16 @@ fn register_active_plugin(lib: libloading::Library, id: String, version: String)
 pub enum DaemonCommand {
     NoOp,
     ReloadConfigAndCss(DaemonResponseSender),
+    /// Reload only the active stylesheet.
+    ReloadCss(DaemonResponseSender),
+    /// The desktop portal reported new appearance settings, `None` if it has none.
+    SetAppearance(Option<Appearance>),
+    /// Apply the appearance again, after the GTK settings it falls back to changed.
+    RefreshAppearance,
+    Css {
+        command: CssCommand,
+        sender: DaemonResponseSender,
+    },
     OpenInspector,
     // OpenMany {
     //     windows: Vec<(String, String)>,
@@ -103,13 +205,28 @@ pub enum DaemonCommand {
         expr: String,
         sender: DaemonResponseSender,
     },
+    /// Start recording variable updates to `out`, or stop recording if it is `None`.
+    Record {
+        out: Option<PathBuf>,
+        sender: DaemonResponseSender,
+    },
+    Replay {
+        file: PathBuf,
+        speed: f64,
+        sender: DaemonResponseSender,
+    },
 }
 
 /// An opened window.
 pub struct EwwiiWindow {
     pub name: String,
-    pub gtk_window: Window,
+    /// The GTK window, or `None` if the daemon is running headless.
+    pub gtk_window: Option<Window>,
+    /// The widget tree of a window opened by a headless daemon, shown by `ewwii debug`.
+    pub widget_tree: Option<Rc<WidgetNode>>,
     pub waited_close: Option<Duration>,
+    /// Wrapper around the root widget playing the open and close animations, if any.
+    pub animation: Option<AnimationWidget>,
     pub delete_event_handler_id: Option<glib::SignalHandlerId>,
     pub destroy_event_handler_id: Option<glib::SignalHandlerId>,
 }
@@ -118,8 +235,10 @@ impl std::fmt::Debug for EwwiiWindow {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("EwwiiWindow")
             .field("name", &self.name)
-            .field("gtk_window", &"<GtkWindow>")
+            .field("gtk_window", &self.gtk_window.as_ref().map(|_| "<GtkWindow>"))
+            .field("widget_tree", &self.widget_tree)
             .field("waited_close", &self.waited_close)
+            .field("animation", &self.animation.as_ref().map(|_| "<AnimationWidget>"))
             .field("widget_reg_store", &"<WidgetRegistry>")
             .field("delete_event_handler_id", &self.delete_event_handler_id)
             .field("destroy_event_handler_id", &self.destroy_event_handler_id)
@@ -134,13 +253,50 @@ impl EwwiiWindow {
     pub fn close(self) {
         log::info!("Closing gtk window {}", self.name);
 
+        let Some(gtk_window) = self.gtk_window else {
+            return;
+        };
+
         for handler_id in
             [self.destroy_event_handler_id, self.delete_event_handler_id].into_iter().flatten()
         {
-            self.gtk_window.disconnect(handler_id);
+            gtk_window.disconnect(handler_id);
+        }
+
+        gtk_window.close();
+    }
+
+    /// Close the window once its close animation has played, or right away without one.
+    pub fn close_animated(mut self) {
+        let sequence = self.animation.as_ref().map(|a| a.close()).unwrap_or_default();
+        let animation = self.animation.take().filter(|a| a.is_mapped() && !sequence.is_empty());
+        let (Some(animation), Some(gtk_window)) = (animation, &self.gtk_window) else {
+            self.close();
+            return;
+        };
+
+        // The window is already on its way out, closing it again must not close a reopened one
+        for handler_id in
+            [self.destroy_event_handler_id.take(), self.delete_event_handler_id.take()]
+                .into_iter()
+                .flatten()
+        {
+            gtk_window.disconnect(handler_id);
         }
 
-        self.gtk_window.close();
+        log::info!("Playing close animation of window {}", self.name);
+        glib::MainContext::default().spawn_local(async move {
+            animation.play_to_end(&sequence).await;
+            self.close();
+        });
+    }
+
+    /// The root widget of the window, inside the wrapper playing its animations.
+    pub fn root_widget(&self) -> Option<gtk4::Widget> {
+        match &self.animation {
+            Some(animation) => animation.child(),
+            None => self.gtk_window.as_ref()?.child(),
+        }
     }
 }
 
@@ -155,14 +311,27 @@ pub struct App<B: DisplayBackend> {
     /// Window names that are supposed to be open, but failed.
     /// When reloading the config, these should be opened again.
     pub failed_windows: HashSet<String>,
-    /// The user's css provider.
-    pub css_provider: gtk4::CssProvider,
+    /// The user's css provider. `None` when running headless.
+    pub css_provider: Option<gtk4::CssProvider>,
+    /// Provider holding the `CssVar` custom properties. `None` when running headless.
+    pub css_var_provider: Option<gtk4::CssProvider>,
+    /// Provider holding `ewwii.dark.(s)css` while the color scheme is dark. `None` when headless.
+    pub dark_css_provider: Option<gtk4::CssProvider>,
+    /// Stylesheet picked with `ewwii css`, used instead of `ewwii.(s)css` when set.
+    pub stylesheet: Option<PathBuf>,
+    /// Appearance last reported by the desktop portal, GTK settings fill in its color scheme.
+    pub portal_appearance: Option<Appearance>,
+    /// Whether the dark color scheme is applied.
+    pub dark_mode: bool,
     /// This will be set by the plugins.
     pub custom_css_providers: Vec<Option<gtk4::CssProvider>>,
     pub gdk_display: Option<gtk4::gdk::Display>,
     pub plugin_buffer: plugin::PluginBuffer,
     pub nbcl_bootstraps: Vec<String>,
     pub reloading: bool,
+    /// Whether the daemon runs without a display. Windows are then only
+    /// tracked in `open_windows`, without building any GTK widgets.
+    pub headless: bool,
 
     /// Sender to send [`DaemonCommand`]s
     pub app_evt_send: UnboundedSender<DaemonCommand>,
@@ -185,6 +354,10 @@ impl<B: DisplayBackend> std::fmt::Debug for App<B> {
             .field("open_windows", &self.open_windows)
             .field("failed_windows", &self.failed_windows)
             .field("window_arguments", &self.instance_id_to_args)
+            .field("headless", &self.headless)
+            .field("stylesheet", &self.stylesheet)
+            .field("portal_appearance", &self.portal_appearance)
+            .field("dark_mode", &self.dark_mode)
             .field("paths", &self.paths)
             .finish()
     }
@@ -232,34 +405,50 @@ impl<B: DisplayBackend> App<B> {
         match event {
             DaemonCommand::NoOp => {}
             DaemonCommand::OpenInspector => {
-                gtk4::Window::set_interactive_debugging(true);
+                if self.headless {
+                    log::warn!("The GTK inspector is not available when running headless");
+                } else {
+                    gtk4::Window::set_interactive_debugging(true);
+                }
             }
             DaemonCommand::ReloadConfigAndCss(sender) => {
-                // Wait for all monitor models to be set. When a new monitor gets added, this
-                // might not immediately be the case. And if we were to wait inside the
-                // connect_monitor_added callback, model() never gets set. So instead we wait here.
-                wait_for_monitor_model().await;
-                let mut errors = Vec::new();
-
-                let config_result =
-                    config::read_from_ewwii_paths(&self.paths, self.nbcl_bootstraps.clone());
-
-                if let Err(e) = config_result.and_then(|new_config| self.load_config(new_config)) {
-                    errors.push(e)
-                }
-                match crate::config::scss::parse_scss_from_config(self.paths.get_config_dir()) {
-                    Ok((file_id, css)) => {
-                        if let Err(e) = self.load_css(file_id, &css) {
-                            errors.push(anyhow!(e));
+                let errors = self.reload_config_and_css().await;
+                sender.respond_with_error_list(errors)?;
+            }
+            DaemonCommand::ReloadCss(sender) => {
+                // A stylesheet change alone cannot bring back a rejected config, check it again
+                let errors = if VarWatcherAPI::state_of(crate::updates::CONFIG_ERROR_VAR).is_empty()
+                {
+                    match self.read_stylesheet().and_then(|(id, css)| self.load_css(id, &css)) {
+                        Ok(()) => Vec::new(),
+                        Err(e) => {
+                            VarWatcherAPI::update_with_broadcast(
+                                crate::updates::CONFIG_ERROR_VAR,
+                                format!("{:#}", e),
+                            );
+                            vec![e]
                         }
                     }
-                    Err(e) => {
-                        errors.push(e);
-                    }
-                }
-
+                } else {
+                    self.reload_config_and_css().await
+                };
                 sender.respond_with_error_list(errors)?;
             }
+            DaemonCommand::SetAppearance(appearance) => {
+                self.portal_appearance = appearance;
+                self.apply_appearance()?;
+            }
+            DaemonCommand::RefreshAppearance => self.apply_appearance()?,
+            DaemonCommand::Css { command, sender } => {
+                let (stylesheet, theme) = match command {
+                    CssCommand::Load { file } => (Ok(file.clone()), file.display().to_string()),
+                    CssCommand::Theme { name } => {
+                        (config::scss::find_theme(self.paths.get_config_dir(), &name), name)
+                    }
+                };
+                let result = stylesheet.and_then(|path| self.switch_stylesheet(path, theme));
+                sender.respond_with_result(result)?;
+            }
             DaemonCommand::KillServer => {
                 log::info!("Received kill command, stopping server!");
                 self.stop_application();
@@ -382,6 +571,31 @@ impl<B: DisplayBackend> App<B> {
                     Err(e) => sender.send_failure(e.to_string())?,
                 };
             }
+            DaemonCommand::Record { out: Some(out), sender } => {
+                match crate::updates::record::start_recording(&out) {
+                    Ok(_) => sender.send_success(format!("Recording to {}", out.display()))?,
+                    Err(e) => sender.send_failure(format!("{:?}", e))?,
+                };
+            }
+            DaemonCommand::Record { out: None, sender } => {
+                match crate::updates::record::stop_recording() {
+                    Some(path) => {
+                        sender.send_success(format!("Saved recording to {}", path.display()))?
+                    }
+                    None => sender.send_failure("No recording is running".to_string())?,
+                };
+            }
+            DaemonCommand::Replay { file, speed, sender } => {
+                match crate::updates::record::read_recording(&file) {
+                    Ok(updates) => {
+                        let count = updates.len();
+                        crate::updates::record::replay(updates, speed);
+                        sender
+                            .send_success(format!("Replaying {} update(s) at {}x", count, speed))?
+                    }
+                    Err(e) => sender.send_failure(format!("{:?}", e))?,
+                };
+            }
         }
         Ok(())
     }
@@ -406,6 +620,21 @@ impl<B: DisplayBackend> App<B> {
             format!("Tried to close window with id '{instance_id}', but no such window was open")
         })?;
 
+        // Instances of the same window share widget ids, so their tasks stay while one is open
+        if !self.open_windows.values().any(|w| w.name == ewwii_window.name) {
+            self.forget_window_widgets(&ewwii_window.name);
+        }
+
+        // Windows closed to be reopened by a reload are swapped out without animation
+        let animate = !self.reloading;
+        let close = move |window: EwwiiWindow| {
+            if animate {
+                window.close_animated();
+            } else {
+                window.close();
+            }
+        };
+
         if let Some(wc) = ewwii_window.waited_close {
             log::info!("Waiting {:?} before closing window.", wc);
 
@@ -416,7 +645,7 @@ impl<B: DisplayBackend> App<B> {
                 futures::select! {
                     _ = glib::timeout_future(wc).fuse() => {
                         log::info!("GLib timeout expired. Closing window.");
-                        ewwii_window.close();
+                        close(ewwii_window);
                     }
                     _ = &mut abort_rx => {
                         log::info!("Close window timer aborted via channel.");
@@ -424,7 +653,7 @@ impl<B: DisplayBackend> App<B> {
                 }
             });
         } else {
-            ewwii_window.close();
+            close(ewwii_window);
         }
 
         if auto_reopen {
@@ -449,6 +678,18 @@ impl<B: DisplayBackend> App<B> {
         Ok(())
     }
 
+    /// Drop the widgets of the window `name` from the registry and stop their property tasks.
+    fn forget_window_widgets(&self, name: &str) {
+        let Ok(window_def) = self.ewwii_config.get_window(name) else {
+            return;
+        };
+        if let Ok(mut wreg) = self.widget_reg_store.lock() {
+            if let Some(registry) = wreg.as_mut() {
+                registry.forget_subtree(&window_def.root_widget);
+            }
+        }
+    }
+
     fn open_window(&mut self, window_args: &WindowArguments) -> Result<()> {
         let instance_id = &window_args.instance_id;
         self.failed_windows.remove(instance_id);
@@ -477,18 +718,37 @@ impl<B: DisplayBackend> App<B> {
                 self.restart_signals()?;
             }
 
-            // load widgets
-            let root_widget = {
-                // builds the widget and populates widget registry
-                let mut maybe_registry = self.widget_reg_store.lock().unwrap();
-                let registry = maybe_registry.get_or_insert_with(WidgetRegistry::new);
-                build_gtk_widget(&WidgetInput::Window(window_def), registry)?
-            };
+            let mut ewwii_window = if self.headless {
+                // Nothing is realized, the window only keeps its widget tree around.
+                EwwiiWindow {
+                    name: initiator.name.clone(),
+                    gtk_window: None,
+                    widget_tree: Some(window_def.root_widget.clone()),
+                    waited_close: initiator.waited_close,
+                    animation: None,
+                    delete_event_handler_id: None,
+                    destroy_event_handler_id: None,
+                }
+            } else {
+                // load widgets
+                let root_widget = {
+                    // builds the widget and populates widget registry
+                    let mut maybe_registry = self.widget_reg_store.lock().unwrap();
+                    let registry = maybe_registry.get_or_insert_with(WidgetRegistry::new);
+                    build_gtk_widget(&WidgetInput::Window(window_def), registry)?
+                };
+
+                root_widget.add_css_class(window_name);
+                set_color_scheme_class(&root_widget, self.dark_mode);
 
-            root_widget.add_css_class(window_name);
+                let animation = window_animation(&initiator, &root_widget, !self.reloading);
+                let child = animation.clone().map_or(root_widget, |a| a.upcast());
 
-            let monitor = get_gdk_monitor(initiator.monitor.clone())?;
-            let mut ewwii_window = initialize_window::<B>(&initiator, monitor, root_widget)?;
+                let monitor = get_gdk_monitor(initiator.monitor.clone())?;
+                let mut ewwii_window = initialize_window::<B>(&initiator, monitor, child)?;
+                ewwii_window.animation = animation;
+                ewwii_window
+            };
 
             self.plugin_buffer.emit("ewwii-init-window", "true");
 
@@ -515,9 +775,9 @@ impl<B: DisplayBackend> App<B> {
 
             let closed_by_user = Rc::new(Cell::new(false));
 
-            // handling users close request
-            ewwii_window.delete_event_handler_id =
-                Some(ewwii_window.gtk_window.connect_close_request({
+            if let Some(gtk_window) = &ewwii_window.gtk_window {
+                // handling users close request
+                ewwii_window.delete_event_handler_id = Some(gtk_window.connect_close_request({
                     let handler = gtk_close_handler.clone();
                     let closed_by_user = closed_by_user.clone();
                     move |_| {
@@ -527,9 +787,8 @@ impl<B: DisplayBackend> App<B> {
                     }
                 }));
 
-            // handling destory request
-            ewwii_window.destroy_event_handler_id =
-                Some(ewwii_window.gtk_window.connect_destroy({
+                // handling destory request
+                ewwii_window.destroy_event_handler_id = Some(gtk_window.connect_destroy({
                     let handler = gtk_close_handler.clone();
                     let closed_by_user = closed_by_user.clone();
                     move |_| {
@@ -538,6 +797,7 @@ impl<B: DisplayBackend> App<B> {
                         }
                     }
                 }));
+            }
 
             let duration = window_args.duration;
             if let Some(duration) = duration {
@@ -587,6 +847,13 @@ impl<B: DisplayBackend> App<B> {
         log::info!("Reloading windows");
         log::trace!("loading config: {:#?}", config);
 
+        let has_registry = self.widget_reg_store.lock().is_ok_and(|wreg| wreg.is_some());
+        if has_registry {
+            let old_config = std::mem::replace(&mut self.ewwii_config, config);
+            self.restart_changed_signals(&old_config)?;
+            return self.patch_open_windows(&old_config);
+        }
+
         // clean widget store
         if let Ok(mut wreg) = self.widget_reg_store.lock() {
             *wreg = None;
@@ -624,13 +891,209 @@ impl<B: DisplayBackend> App<B> {
         result
     }
 
+    /// Reload the config and the stylesheet, returning the errors of both.
+    ///
+    /// Nothing running is touched until both are known to be good, so a broken
+    /// edit keeps the previous config, windows and signals running.
+    async fn reload_config_and_css(&mut self) -> Vec<anyhow::Error> {
+        // Wait for all monitor models to be set. When a new monitor gets added, this
+        // might not immediately be the case. And if we were to wait inside the
+        // connect_monitor_added callback, model() never gets set. So instead we wait here.
+        if !self.headless {
+            wait_for_monitor_model().await;
+        }
+        let mut errors = Vec::new();
+
+        let saved_parser = EWWII_CONFIG_PARSER.with(|p| p.borrow().clone());
+        let config_result =
+            config::read_from_ewwii_paths(&self.paths, self.nbcl_bootstraps.clone()).and_then(
+                |new_config| {
+                    self.validate_reload(&new_config)?;
+                    Ok(new_config)
+                },
+            );
+        let css_result = self.read_stylesheet();
+
+        match (config_result, css_result) {
+            (Ok(new_config), Ok((file_id, css))) => {
+                if let Err(e) = self.load_config(new_config) {
+                    errors.push(e)
+                }
+                if let Err(e) = self.load_css(file_id, &css) {
+                    errors.push(anyhow!(e));
+                }
+            }
+            (config_result, css_result) => {
+                log::warn!("Reload rejected, keeping the previous config running");
+                EWWII_CONFIG_PARSER.with(|p| *p.borrow_mut() = saved_parser);
+                errors.extend(config_result.err());
+                errors.extend(css_result.err());
+            }
+        }
+
+        let config_error = errors.iter().map(|e| format!("{:#}", e)).join("\n");
+        VarWatcherAPI::update_with_broadcast(crate::updates::CONFIG_ERROR_VAR, config_error);
+
+        errors
+    }
+
+    /// Compile the active stylesheet, which is `ewwii.(s)css` unless another
+    /// one was picked with `ewwii css`.
+    fn read_stylesheet(&self) -> Result<(usize, String)> {
+        match &self.stylesheet {
+            Some(path) => config::scss::parse_scss_from_file(path, self.paths.get_config_dir()),
+            None => config::scss::parse_scss_from_config(self.paths.get_config_dir()),
+        }
+    }
+
+    /// Load `stylesheet` in place of the active one, exposing `theme` in `ewwii:theme`.
+    fn switch_stylesheet(&mut self, stylesheet: PathBuf, theme: String) -> Result<()> {
+        let (file_id, css) =
+            config::scss::parse_scss_from_file(&stylesheet, self.paths.get_config_dir())?;
+        self.load_css(file_id, &css)?;
+
+        log::info!("Switched stylesheet to {}", stylesheet.display());
+        self.stylesheet = Some(stylesheet);
+        VarWatcherAPI::update_with_broadcast(crate::updates::THEME_VAR, theme);
+        Ok(())
+    }
+
+    /// Check that `config` can replace the running one: its signals are valid
+    /// and every open window still exists in it with a valid geometry.
+    fn validate_reload(&self, config: &config::EwwiiConfig) -> Result<()> {
+        for signal in crate::updates::retreive_signals(config.get_root_node()?.as_ref()) {
+            crate::check::check_signal(&signal)?;
+        }
+
+        for instance_id in self.open_windows.keys().chain(&self.failed_windows) {
+            let Some(window_args) = self.instance_id_to_args.get(instance_id) else {
+                continue;
+            };
+            let window_def = config.get_window(&window_args.window_name)?;
+            WindowInitiator::new(window_def, window_args).with_context(|| {
+                format!("Invalid definition for open window '{}'", window_args.window_name)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Update the open windows to `self.ewwii_config` in place, reopening the
+    /// ones whose window definition changed or that could not be patched.
+    fn patch_open_windows(&mut self, old_config: &config::EwwiiConfig) -> Result<()> {
+        self.reloading = true;
+        let result = (|| -> Result<()> {
+            self.apply_css_vars()?;
+
+            // Instances of the same window share widget ids, so none of them can be patched alone
+            let instance_counts = self.open_windows.values().counts_by(|w| w.name.clone());
+
+            let mut reopen_ids = Vec::new();
+            if let Ok(mut wreg) = self.widget_reg_store.lock() {
+                let registry = wreg.as_mut().context("Widget registry is empty")?;
+                for (instance_id, window) in &self.open_windows {
+                    let old_def = old_config.get_window(&window.name).ok();
+                    let new_def = self.ewwii_config.get_window(&window.name).ok();
+                    let patched = match (old_def, new_def) {
+                        (Some(old_def), Some(new_def)) if instance_counts[&window.name] == 1 => {
+                            patch_window(registry, window, old_def, new_def, self.dark_mode)
+                        }
+                        _ => false,
+                    };
+                    if !patched {
+                        if let Some(old_def) = old_def {
+                            registry.forget_subtree(&old_def.root_widget);
+                        }
+                        if let Some(new_def) = new_def {
+                            registry.forget_subtree(&new_def.root_widget);
+                        }
+                        reopen_ids.push(instance_id.clone());
+                    }
+                }
+            }
+
+            let reopen_ids: Vec<String> = reopen_ids
+                .into_iter()
+                .chain(self.failed_windows.iter().cloned())
+                .unique()
+                .collect();
+            for instance_id in &reopen_ids {
+                let window_arguments = self.instance_id_to_args.get(instance_id).with_context(|| {
+                    format!("Cannot reopen window, initial parameters were not saved correctly for {instance_id}")
+                })?;
+                self.open_window(&window_arguments.clone())?;
+            }
+
+            Ok(())
+        })();
+        self.reloading = false;
+
+        self.plugin_buffer.emit("ewwii-reloaded-windows", "true");
+
+        result
+    }
+
     /// Load a given CSS string into the gtk css provider
     pub fn load_css(&mut self, _file_id: usize, css: &str) -> Result<()> {
-        self.css_provider.load_from_string(css);
+        if let Some(css_provider) = &self.css_provider {
+            css_provider.load_from_string(css);
+        }
+
+        self.load_dark_css()
+    }
 
+    /// Load `ewwii.dark.(s)css` while the dark color scheme is applied, clear it otherwise.
+    fn load_dark_css(&self) -> Result<()> {
+        let Some(dark_css_provider) = &self.dark_css_provider else {
+            return Ok(());
+        };
+
+        let config_dir = self.paths.get_config_dir();
+        match config::scss::find_dark_stylesheet(config_dir).filter(|_| self.dark_mode) {
+            Some(path) => {
+                let (_, css) = config::scss::parse_scss_from_file(&path, config_dir)?;
+                dark_css_provider.load_from_string(&css);
+            }
+            None => dark_css_provider.load_from_string(""),
+        }
         Ok(())
     }
 
+    /// Follow the appearance of the desktop portal, or the color scheme of GTK if
+    /// there is no portal or it has no preference, which is the GNOME default.
+    ///
+    /// Exposes it in `ewwii:color_scheme` and `ewwii:accent_color`, and marks every
+    /// window as `dark` or `light`.
+    pub fn apply_appearance(&mut self) -> Result<()> {
+        let mut appearance = self.portal_appearance.clone().unwrap_or_default();
+        if appearance.color_scheme == ColorScheme::NoPreference && !self.headless {
+            appearance.color_scheme = gtk_appearance().color_scheme;
+        }
+        let dark_mode = appearance.color_scheme == ColorScheme::Dark;
+
+        VarWatcherAPI::update_with_broadcast(
+            crate::updates::COLOR_SCHEME_VAR,
+            if dark_mode { "dark" } else { "light" }.to_string(),
+        );
+        VarWatcherAPI::update_with_broadcast(
+            crate::updates::ACCENT_COLOR_VAR,
+            appearance.accent_color.unwrap_or_default(),
+        );
+
+        if dark_mode == self.dark_mode {
+            return Ok(());
+        }
+        log::info!("Switching to the {} color scheme", if dark_mode { "dark" } else { "light" });
+        self.dark_mode = dark_mode;
+
+        for window in self.open_windows.values() {
+            if let Some(root_widget) = window.root_widget() {
+                set_color_scheme_class(&root_widget, dark_mode);
+            }
+        }
+        self.load_dark_css()
+    }
+
     /// Perform widget control based on the action
     pub fn perform_widget_control(&mut self, command: WidgetControlCommand) -> Result<String> {
         match command {
@@ -725,14 +1188,10 @@ impl<B: DisplayBackend> App<B> {
                 if let Ok(mut maybe_registry) = self.widget_reg_store.lock() {
                     if let Some(widget_registry) = maybe_registry.as_mut() {
                         for (key, value) in &property_and_value {
-                            let success = widget_registry.update_property_by_name(
+                            widget_registry.update_property_by_name(
                                 &widget_name,
                                 (key.clone(), value.clone()),
-                            );
-
-                            if !success {
-                                anyhow::bail!("Widget with name '{}' not found", &widget_name);
-                            }
+                            )?;
                         }
                     } else {
                         log::error!("Widget registry is empty");
@@ -782,20 +1241,62 @@ impl<B: DisplayBackend> App<B> {
         crate::updates::kill_state_change_handler();
 
         // Start the global variables
-        let signals_vec =
-            crate::updates::retreive_signals(self.ewwii_config.get_root_node()?.as_ref());
+        let root_node = self.ewwii_config.get_root_node()?;
+        let signals_vec = crate::updates::retreive_signals(root_node.as_ref());
+        let bound_globals = crate::updates::retreive_bound_globals(root_node.as_ref());
 
         EWWII_CONFIG_PARSER.with(|p| {
             let parser_raw = p.borrow();
             let parser = parser_raw.as_ref().unwrap();
-            crate::updates::handle_state_changes(parser, signals_vec);
+            crate::updates::handle_state_changes(parser, signals_vec, bound_globals);
         });
 
+        // bound values subscribe to the variables registered above
+        self.apply_css_vars()?;
+
         self.plugin_buffer.emit("ewwii-started-signals", "true");
 
         Ok(())
     }
 
+    /// Restart only the signals that differ between `old_config` and the current
+    /// one. Variables keep their watchers, so open widgets stay subscribed.
+    fn restart_changed_signals(&self, old_config: &config::EwwiiConfig) -> Result<()> {
+        let old_ids: HashSet<u64> =
+            crate::updates::retreive_signals(old_config.get_root_node()?.as_ref())
+                .iter()
+                .map(crate::updates::SignalProps::id)
+                .collect();
+
+        let root_node = self.ewwii_config.get_root_node()?;
+        let signals = crate::updates::retreive_signals(root_node.as_ref());
+        let new_ids: HashSet<u64> = signals.iter().map(crate::updates::SignalProps::id).collect();
+        let bound_globals = crate::updates::retreive_bound_globals(root_node.as_ref());
+
+        crate::updates::kill_signal_handlers(&old_ids.difference(&new_ids).copied().collect());
+        let started: Vec<_> =
+            signals.into_iter().filter(|signal| !old_ids.contains(&signal.id())).collect();
+        log::debug!("Restarting {} changed signal(s)", started.len());
+
+        EWWII_CONFIG_PARSER.with(|p| {
+            let parser_raw = p.borrow();
+            let parser = parser_raw.as_ref().unwrap();
+            crate::updates::handle_state_changes(parser, started, bound_globals);
+        });
+
+        Ok(())
+    }
+
+    /// Write the `CssVar`s of the config into their provider.
+    pub fn apply_css_vars(&self) -> Result<()> {
+        if let Some(css_var_provider) = &self.css_var_provider {
+            let vars =
+                crate::css_vars::retreive_css_vars(self.ewwii_config.get_root_node()?.as_ref());
+            crate::css_vars::apply_css_vars(css_var_provider, vars);
+        }
+        Ok(())
+    }
+
     pub fn run_nbcl_expr(&self, expr: String) -> Result<()> {
         EWWII_CONFIG_PARSER.with(move |p| -> anyhow::Result<()> {
             let parser = p.borrow();
@@ -1002,8 +1503,10 @@ fn initialize_window<B: DisplayBackend>(
 
     Ok(EwwiiWindow {
         name: window_init.name.clone(),
-        gtk_window: window,
+        gtk_window: Some(window),
+        widget_tree: None,
         waited_close: window_init.waited_close,
+        animation: None,
         delete_event_handler_id: None,
         destroy_event_handler_id: None,
     })
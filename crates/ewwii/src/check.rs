@@ -0,0 +1,201 @@
+//! Offline configuration checking for `ewwii check`.
+//!
+//! Everything here runs without a display or a running daemon: the config is
+//! evaluated, the stylesheet compiled and plugins opened just far enough to
+//! catch the errors that would otherwise only show up in `ewwii logs`.
+
+use crate::{
+    config::{self, ConfigEngine, EWWII_CONFIG_PARSER},
+    error_handling_ctx, plugin,
+    updates::{self, mock::MockSource, SignalProps, SignalType},
+    EwwiiPaths,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use ewwii_plugin_api as epapi;
+use ewwii_plugin_api::IpcRequest;
+use ewwii_shared_utils::prop_utils::*;
+use ewwii_shared_utils::schema::DATA_SOURCES;
+use std::path::Path;
+
+/// Check the configuration in `paths`, printing every problem found.
+///
+/// Returns an error if at least one problem was found.
+pub fn check_config(paths: &EwwiiPaths) -> Result<()> {
+    let mut errors: Vec<anyhow::Error> = Vec::new();
+
+    // Relative imports and stylesheet paths resolve the same way they do in the daemon.
+    std::env::set_current_dir(paths.get_config_dir()).with_context(|| {
+        format!("Failed to change working directory to {}", paths.get_config_dir().display())
+    })?;
+
+    // Requests sent during evaluation have nowhere to go, keep the receiver
+    // alive so the parser does not notice.
+    let (ipc_tx, _ipc_rx) = tokio::sync::mpsc::unbounded_channel::<IpcRequest>();
+    EWWII_CONFIG_PARSER.with(|p| {
+        let config_parser = ewwii_nbcl_impl::parser::NbclConfigParser::new(ipc_tx);
+        *p.borrow_mut() = Some(ConfigEngine::Default(Box::new(config_parser)));
+    });
+
+    let plugin_paths = paths.get_plugin_paths();
+    for plugin_path in &plugin_paths {
+        if let Err(e) = check_plugin(plugin_path) {
+            errors.push(e);
+        }
+    }
+    if !plugin_paths.is_empty() {
+        log::warn!("Plugins are not started during `check`, so their bootstraps are not applied.");
+    }
+
+    match config::read_from_ewwii_paths(paths, Vec::new()) {
+        Ok(ewwii_config) => {
+            println!("Loaded {} window definition(s)", ewwii_config.get_windows().len());
+            if let Ok(root_node) = ewwii_config.get_root_node() {
+                for signal in updates::retreive_signals(&root_node) {
+                    if let Err(e) = check_signal(&signal) {
+                        errors.push(e);
+                    }
+                }
+            }
+        }
+        Err(e) => errors.push(e),
+    }
+
+    let config_dir = paths.get_config_dir();
+    if config_dir.join("ewwii.scss").exists() || config_dir.join("ewwii.css").exists() {
+        if let Err(e) = config::scss::parse_scss_from_config(config_dir) {
+            errors.push(e);
+        }
+    }
+
+    if errors.is_empty() {
+        println!("Configuration in {} is valid.", config_dir.display());
+        return Ok(());
+    }
+
+    let count = errors.len();
+    for err in errors {
+        error_handling_ctx::print_error(err);
+    }
+    bail!("Found {} error(s) in the configuration at {}", count, config_dir.display())
+}
+
+/// Validate the props of a `Poll`, `Listen` or `Script` node the same way the
+/// daemon reads them when starting the signal.
+pub(crate) fn check_signal(signal: &SignalProps) -> Result<()> {
+    let props = &signal.props;
+    let name = &signal.name;
+
+    if let Some(mock) = props.get("mock") {
+        MockSource::from_property(mock)
+            .map_err(|e| anyhow!("`{}` has an invalid mock: {}", name, e))?;
+    }
+
+    match signal.signal_type {
+        SignalType::Poll => {
+            let interval_prop = soft_retreive_prop(props, "interval", "1s");
+            get_duration_prop(&interval_prop, "interval")
+                .map_err(|e| anyhow!("Poll `{}` has an invalid interval: {}", name, e))?;
+
+            let cmd = match retreive_prop(props, "cmd") {
+                Ok(prop) => get_string_prop(prop, "cmd")
+                    .map(|c| unwrap_static("cmd", c))
+                    .map_err(|e| anyhow!("Poll `{}` has an invalid cmd: {}", name, e))?,
+                Err(_) => String::new(),
+            };
+            if cmd.trim().is_empty() {
+                bail!("Poll `{}` requires a `cmd` property", name);
+            }
+
+            if let Ok(prop) = retreive_prop(props, "skip_unchanged") {
+                get_bool_prop(prop, "skip_unchanged")
+                    .map_err(|e| anyhow!("Poll `{}` has an invalid skip_unchanged: {}", name, e))?;
+            }
+        }
+        SignalType::Listen => {
+            let source = match retreive_prop(props, "source") {
+                Ok(prop) => get_string_prop(prop, "source")
+                    .map(|s| unwrap_static("source", s))
+                    .map_err(|e| anyhow!("Listen `{}` has an invalid source: {}", name, e))?,
+                Err(_) => String::new(),
+            };
+
+            if !source.is_empty() {
+                if !DATA_SOURCES.contains(&source.as_str()) {
+                    bail!(
+                        "Listen `{}` uses unknown data source `{}` (expected one of: {})",
+                        name,
+                        source,
+                        DATA_SOURCES.join(", ")
+                    );
+                }
+                return Ok(());
+            }
+
+            let cmd = match retreive_prop(props, "cmd") {
+                Ok(prop) => get_string_prop(prop, "cmd")
+                    .map(|c| unwrap_static("cmd", c))
+                    .map_err(|e| anyhow!("Listen `{}` has an invalid cmd: {}", name, e))?,
+                Err(_) => String::new(),
+            };
+            if cmd.trim().is_empty() {
+                bail!("Listen `{}` requires either a `cmd` or a `source` property", name);
+            }
+        }
+        SignalType::Script => {
+            get_callback_prop(props, "run")
+                .map_err(|_| anyhow!("Script requires a `run` property holding a function"))?;
+
+            let every = retreive_prop(props, "every").ok();
+            let on = retreive_prop(props, "on").ok();
+
+            match (every, on) {
+                (Some(prop), None) => {
+                    get_duration_prop(prop, "every")
+                        .map_err(|e| anyhow!("Script has an invalid `every`: {}", e))?;
+                }
+                (None, Some(prop)) => {
+                    get_string_prop(prop, "on")
+                        .map_err(|e| anyhow!("Script has an invalid `on`: {}", e))?;
+                }
+                (Some(_), Some(_)) => {
+                    bail!("Script takes either `every` or `on`, not both");
+                }
+                (None, None) => {
+                    bail!("Script requires either an `every` or an `on` property");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Open a plugin library and check its exported symbols without creating the plugin.
+fn check_plugin(plugin_path: &Path) -> Result<()> {
+    // SAFETY: Opening the library runs its initializers, same as loading it in the daemon.
+    unsafe {
+        let lib = libloading::Library::new(plugin_path)
+            .map_err(|e| anyhow!("Failed to load plugin {:?}: {}", plugin_path, e))?;
+
+        let get_version: libloading::Symbol<unsafe extern "C" fn() -> *const u8> = lib
+            .get(b"ewwii_api_version")
+            .map_err(|_| anyhow!("Plugin {:?} has no API version symbol", plugin_path))?;
+
+        let c_str = std::ffi::CStr::from_ptr(get_version() as *const std::os::raw::c_char);
+        let plugin_api_ver = c_str.to_str().unwrap_or("0.0.0");
+
+        if !plugin::is_compatible(plugin_api_ver, epapi::API_VERSION) {
+            bail!(
+                "Plugin {:?} is incompatible: API mismatch (Plugin: {}, Host: {})",
+                plugin_path,
+                plugin_api_ver,
+                epapi::API_VERSION
+            );
+        }
+
+        lib.get::<unsafe extern "C" fn() -> epapi::PluginInfo>(b"ewwii_plugin_create")
+            .map_err(|e| anyhow!("Missing ewwii_plugin_create in {:?}: {}", plugin_path, e))?;
+    }
+
+    Ok(())
+}
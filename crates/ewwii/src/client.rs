@@ -23,6 +23,15 @@ pub fn handle_client_only_action(paths: &EwwiiPaths, action: ActionClientOnly) -
         ActionClientOnly::GetPluginVersion => {
             println!("Plugin Version: {}", ewwii_plugin_api::API_VERSION);
         }
+        ActionClientOnly::Check => {
+            crate::check::check_config(paths)?;
+        }
+        ActionClientOnly::Lsp => {
+            crate::lsp::run()?;
+        }
+        ActionClientOnly::Render { window_name, out, size, vars } => {
+            crate::render::render_window(paths, &window_name, &out, size, &vars)?;
+        }
     }
     Ok(())
 }
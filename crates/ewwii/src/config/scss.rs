@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Context};
 
@@ -16,22 +16,53 @@ pub fn parse_scss_from_config(path: &Path) -> anyhow::Result<(usize, String)> {
         ));
     }
 
-    let (s_css_path, css) = if css_file.exists() {
-        let css_file_content = std::fs::read_to_string(&css_file)
-            .with_context(|| format!("Given CSS file doesn't exist: {}", css_file.display()))?;
-        let css = replace_env_var_references(css_file_content);
-        (css_file, css)
+    if css_file.exists() {
+        parse_scss_from_file(&css_file, path)
     } else {
-        let scss_file_content = std::fs::read_to_string(&scss_file)
-            .with_context(|| format!("Given SCSS file doesn't exist! {}", path.display()))?;
-        let file_content = replace_env_var_references(scss_file_content);
-        let grass_config = grass::Options::default().load_path(path);
-        let css = grass::from_string(file_content, &grass_config)
-            .map_err(|err| anyhow!("SCSS parsing error: {}", err))?;
-        (scss_file, css)
+        parse_scss_from_file(&scss_file, path)
+    }
+}
+
+/// Like [`parse_scss_from_config`], but for any stylesheet. Files ending in `.css`
+/// are taken as is, everything else is compiled as SCSS. Imports resolve relative
+/// to the file and to the config directory.
+pub fn parse_scss_from_file(file: &Path, config_dir: &Path) -> anyhow::Result<(usize, String)> {
+    let content = std::fs::read_to_string(file)
+        .with_context(|| format!("Given stylesheet doesn't exist: {}", file.display()))?;
+    let content = replace_env_var_references(content);
+
+    let css = if file.extension().is_some_and(|ext| ext == "css") {
+        content
+    } else {
+        let mut grass_config = grass::Options::default().load_path(config_dir);
+        if let Some(parent) = file.parent() {
+            grass_config = grass_config.load_path(parent);
+        }
+        grass::from_string(content, &grass_config)
+            .map_err(|err| anyhow!("SCSS parsing error: {}", err))?
     };
 
     let mut file_db = error_handling_ctx::FILE_DATABASE.write().unwrap();
-    let file_id = file_db.insert_string(s_css_path.display().to_string(), css.clone())?;
+    let file_id = file_db.insert_string(file.display().to_string(), css.clone())?;
     Ok((file_id, css))
 }
+
+/// Find the stylesheet of the theme `name` in the `themes` directory of the config.
+pub fn find_theme(config_dir: &Path, name: &str) -> anyhow::Result<PathBuf> {
+    let themes_dir = config_dir.join("themes");
+    ["scss", "css"]
+        .iter()
+        .map(|ext| themes_dir.join(format!("{}.{}", name, ext)))
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            anyhow!("No theme named '{}', expected {}/{}.(s)css", name, themes_dir.display(), name)
+        })
+}
+
+/// Find `ewwii.dark.(s)css`, the overrides applied while the dark color scheme is active.
+pub fn find_dark_stylesheet(config_dir: &Path) -> Option<PathBuf> {
+    ["scss", "css"]
+        .iter()
+        .map(|ext| config_dir.join(format!("ewwii.dark.{}", ext)))
+        .find(|path| path.is_file())
+}
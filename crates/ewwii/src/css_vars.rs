@@ -0,0 +1,100 @@
+//! `CssVar` nodes, CSS custom properties declared on `:root` that follow
+//! the value they are bound to.
+//!
+//! All of them are written into one provider, layered above the user's
+//! stylesheet so they win over any defaults it declares for them.
+
+use crate::apply_property;
+use ewwii_shared_utils::ast::WidgetNode;
+use ewwii_shared_utils::prop::Property;
+use ewwii_shared_utils::prop_utils::{get_string_prop, PropValue};
+use gtk4::glib;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::rc::Rc;
+
+/// Owner of the property tasks of every `CssVar`. Widget ids are hashes of
+/// their props, so this one does not collide with them in practice.
+const CSS_VAR_TASK_OWNER: u64 = u64::MAX;
+
+/// The `CssVar` nodes of the config, as their name and value.
+pub fn retreive_css_vars(root_node: &WidgetNode) -> Vec<(String, Property)> {
+    let WidgetNode::Tree(children) = root_node else {
+        return Vec::new();
+    };
+
+    children
+        .iter()
+        .filter_map(|child| match child {
+            WidgetNode::CssVar { name, props } => {
+                Some((name.clone(), props.get("value").cloned().unwrap_or(Property::None)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Write `vars` into `provider`, replacing what a previous call wrote there.
+pub fn apply_css_vars(provider: &gtk4::CssProvider, vars: Vec<(String, Property)>) {
+    crate::property_macro::close_property_tasks_of(&HashSet::from([CSS_VAR_TASK_OWNER]));
+    provider.load_from_string("");
+
+    let values = Rc::new(RefCell::new(BTreeMap::new()));
+    crate::property_macro::with_task_owner(CSS_VAR_TASK_OWNER, || {
+        for (name, value) in vars {
+            let value = match value {
+                Property::Int(i) => Property::String(i.to_string()),
+                Property::Float(f) => Property::String(f.to_string()),
+                other => other,
+            };
+            let prop = match get_string_prop(&value, "value") {
+                Ok(prop) => prop,
+                Err(e) => {
+                    log::error!("CssVar {} has an invalid value: {}", name, e);
+                    continue;
+                }
+            };
+
+            let values = values.clone();
+            let provider = provider.clone();
+            apply_property!(prop, |v: String| {
+                values.borrow_mut().insert(name.clone(), v);
+                provider.load_from_string(&root_rule(&values.borrow()));
+            });
+        }
+    });
+}
+
+/// The `:root` rule declaring `values`. Empty values and values that would
+/// end the rule early are left out.
+fn root_rule(values: &BTreeMap<String, String>) -> String {
+    let mut css = String::from(":root {\n");
+    for (name, value) in values {
+        if value.trim().is_empty() {
+            continue;
+        }
+        if value.contains([';', '{', '}']) {
+            log::warn!("Ignoring value of CssVar {}, it contains `;`, `{{` or `}}`", name);
+            continue;
+        }
+        css.push_str(&format!("  {}: {};\n", name, value));
+    }
+    css.push('}');
+    css
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root_rule() {
+        let values = BTreeMap::from([
+            ("--accent".to_string(), "#ff8800".to_string()),
+            ("--empty".to_string(), String::new()),
+            ("--evil".to_string(), "red; } * { color: blue".to_string()),
+            ("--gap".to_string(), "4px".to_string()),
+        ]);
+        assert_eq!(root_rule(&values), ":root {\n  --accent: #ff8800;\n  --gap: 4px;\n}");
+    }
+}
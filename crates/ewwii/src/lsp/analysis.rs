@@ -0,0 +1,595 @@
+//! Text level analysis of nbcl sources for the language server.
+//!
+//! Completion and hover have to work on half written code, so instead of
+//! parsing, the source is scanned for blocks, strings and comments.
+
+use ewwii_shared_utils::schema::{self, PropSpec, PropType, WidgetSchema};
+use std::ops::Range;
+
+/// What the `{ ... }` block around a position belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scope {
+    TopLevel,
+    /// A widget or top-level node such as `Box` or `Window`.
+    Node(String),
+    /// The body of a component or function.
+    Code,
+    /// A map value such as `geometry = { ... }`.
+    Map,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompletionContext {
+    /// Start of a statement, where props and child widgets go.
+    Statement(Scope),
+    /// Value of `prop` in a `node` block, `quoted` if the string is already open.
+    PropValue {
+        node: String,
+        prop: String,
+        quoted: bool,
+    },
+    /// Name of a global inside `global("...")` or a template placeholder.
+    GlobalName,
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefinitionKind {
+    Component,
+    /// A variable defined by `Poll` or `Listen`.
+    Global,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub kind: DefinitionKind,
+    pub name: String,
+    /// Range of the name in the source it was found in.
+    pub range: Range<usize>,
+    /// Source of the definition shown on hover.
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    Node,
+    Prop,
+    Value,
+    Component,
+    Global,
+    Keyword,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionEntry {
+    pub label: String,
+    pub kind: EntryKind,
+    pub detail: Option<String>,
+    pub insert_text: Option<String>,
+}
+
+impl CompletionEntry {
+    fn new(label: impl Into<String>, kind: EntryKind) -> Self {
+        Self { label: label.into(), kind, detail: None, insert_text: None }
+    }
+
+    fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    fn with_insert_text(mut self, insert_text: impl Into<String>) -> Self {
+        self.insert_text = Some(insert_text.into());
+        self
+    }
+}
+
+struct ScanState {
+    scopes: Vec<Scope>,
+    /// Offset of the opening quote if the scan ended inside a string.
+    string_start: Option<usize>,
+}
+
+/// Scan `text` up to `end`, keeping track of open blocks and strings.
+fn scan(text: &str, end: usize) -> ScanState {
+    let mut scopes = vec![Scope::TopLevel];
+    let mut string_start = None;
+    let mut in_comment = false;
+    let mut escaped = false;
+    let mut header_start = 0;
+
+    for (i, c) in text[..end].char_indices() {
+        if in_comment {
+            if c == '\n' {
+                in_comment = false;
+                header_start = i + 1;
+            }
+            continue;
+        }
+
+        if string_start.is_some() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => string_start = None,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => string_start = Some(i),
+            '#' => in_comment = true,
+            '{' => {
+                let parent = scopes.last().cloned().unwrap_or(Scope::TopLevel);
+                scopes.push(classify_block(&text[header_start..i], parent));
+                header_start = i + 1;
+            }
+            '}' => {
+                if scopes.len() > 1 {
+                    scopes.pop();
+                }
+                header_start = i + 1;
+            }
+            '\n' => header_start = i + 1,
+            _ => {}
+        }
+    }
+
+    ScanState { scopes, string_start }
+}
+
+/// Decide what a block is from the code in front of its `{`.
+fn classify_block(header: &str, parent: Scope) -> Scope {
+    let header = header.trim();
+    let first_word = header.split(|c: char| !is_ident_char(c)).next().unwrap_or_default();
+
+    if header.ends_with(['=', ':']) {
+        Scope::Map
+    } else if crate::regex!(r"\b(component|fn)\b").is_match(header) {
+        Scope::Code
+    } else if matches!(first_word, "for" | "if" | "else" | "while") {
+        parent
+    } else if let Some(caps) =
+        crate::regex!(r#"([A-Z][A-Za-z0-9_]*)\s*("(?:[^"\\]|\\.)*")?$"#).captures(header)
+    {
+        Scope::Node(caps[1].to_string())
+    } else {
+        Scope::Code
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Schema of a widget or top-level node.
+fn schema_for(name: &str) -> Option<&'static WidgetSchema> {
+    schema::widget_schema(name).or_else(|| schema::top_level_schema(name))
+}
+
+/// Whether a node of type `name` takes child widgets.
+fn takes_children(name: &str) -> bool {
+    !matches!(name, "Poll" | "Listen" | "Script" | "CssVar")
+}
+
+pub fn completion_context(text: &str, offset: usize) -> CompletionContext {
+    let state = scan(text, offset);
+    let scope = state.scopes.last().cloned().unwrap_or(Scope::TopLevel);
+
+    if let Some(start) = state.string_start {
+        let before = text[..start].trim_end();
+        let inside = &text[start + 1..offset];
+
+        if before.ends_with("global(") {
+            return CompletionContext::GlobalName;
+        }
+        if before.ends_with("template(") {
+            // Option orders `None` first, so this also covers a missing `}`
+            if inside.rfind('{') > inside.rfind('}') {
+                return CompletionContext::GlobalName;
+            }
+            return CompletionContext::None;
+        }
+
+        if let (Scope::Node(node), Some(caps)) =
+            (&scope, crate::regex!(r"([A-Za-z_]\w*)\s*=$").captures(before))
+        {
+            return CompletionContext::PropValue {
+                node: node.clone(),
+                prop: caps[1].to_string(),
+                quoted: true,
+            };
+        }
+
+        return CompletionContext::None;
+    }
+
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = &text[line_start..offset];
+
+    if let Some(caps) = crate::regex!(r"^\s*([A-Za-z_]\w*)\s*=\s*\w*$").captures(line) {
+        return match scope {
+            Scope::Node(node) => {
+                CompletionContext::PropValue { node, prop: caps[1].to_string(), quoted: false }
+            }
+            _ => CompletionContext::None,
+        };
+    }
+
+    if crate::regex!(r"(?:^|[{}])\s*\w*$").is_match(line) {
+        return CompletionContext::Statement(scope);
+    }
+
+    CompletionContext::None
+}
+
+pub fn complete(context: &CompletionContext, definitions: &[Definition]) -> Vec<CompletionEntry> {
+    let widgets = || {
+        let nodes = schema::widget_schemas()
+            .iter()
+            .map(|w| CompletionEntry::new(w.name, EntryKind::Node).with_detail("widget"));
+        let components = definitions
+            .iter()
+            .filter(|d| d.kind == DefinitionKind::Component)
+            .map(|d| CompletionEntry::new(&d.name, EntryKind::Component).with_detail("component"));
+        nodes.chain(components)
+    };
+
+    let mut entries: Vec<CompletionEntry> = match context {
+        CompletionContext::Statement(Scope::TopLevel) => schema::top_level_schemas()
+            .iter()
+            .map(|n| CompletionEntry::new(n.name, EntryKind::Node).with_detail("top-level node"))
+            .chain(std::iter::once(CompletionEntry::new("component", EntryKind::Keyword)))
+            .collect(),
+        CompletionContext::Statement(Scope::Node(name)) => {
+            let props = schema_for(name).into_iter().flat_map(|s| s.all_props()).map(|spec| {
+                CompletionEntry::new(spec.name, EntryKind::Prop)
+                    .with_detail(type_label(spec))
+                    .with_insert_text(format!("{} = ", spec.name))
+            });
+
+            if takes_children(name) {
+                props.chain(widgets()).collect()
+            } else {
+                props.collect()
+            }
+        }
+        CompletionContext::Statement(Scope::Code) => widgets().collect(),
+        CompletionContext::PropValue { node, prop, quoted } => {
+            let Some(spec) = schema_for(node).and_then(|s| s.get(prop)) else {
+                return Vec::new();
+            };
+
+            let value = |v: &str| {
+                let entry = CompletionEntry::new(v, EntryKind::Value);
+                if *quoted {
+                    entry
+                } else {
+                    entry.with_insert_text(format!("\"{}\"", v))
+                }
+            };
+
+            match spec.ty {
+                PropType::Enum(values) => values.iter().map(|v| value(v)).collect(),
+                PropType::Bool if !quoted => ["true", "false"]
+                    .iter()
+                    .map(|v| CompletionEntry::new(*v, EntryKind::Value))
+                    .collect(),
+                _ => Vec::new(),
+            }
+        }
+        CompletionContext::GlobalName => definitions
+            .iter()
+            .filter(|d| d.kind == DefinitionKind::Global)
+            .map(|d| {
+                let header = d.snippet.lines().next().unwrap_or_default();
+                CompletionEntry::new(&d.name, EntryKind::Global).with_detail(header)
+            })
+            .collect(),
+        CompletionContext::Statement(Scope::Map) | CompletionContext::None => Vec::new(),
+    };
+
+    // The same component or global may be found in several files
+    let mut seen = std::collections::HashSet::new();
+    entries.retain(|e| seen.insert((e.label.clone(), e.kind as u8)));
+    entries
+}
+
+/// Range and text of the identifier at `offset`.
+pub fn word_at(text: &str, offset: usize) -> Option<(Range<usize>, &str)> {
+    let start = text[..offset].rfind(|c| !is_ident_char(c)).map_or(0, |i| {
+        // `rfind` gives the start of the char, skip past it
+        i + text[i..].chars().next().map_or(1, char::len_utf8)
+    });
+    let end = text[offset..].find(|c| !is_ident_char(c)).map_or(text.len(), |i| offset + i);
+
+    (start < end).then(|| (start..end, &text[start..end]))
+}
+
+/// Whether `offset` is inside a string literal.
+pub fn in_string(text: &str, offset: usize) -> bool {
+    scan(text, offset).string_start.is_some()
+}
+
+/// The definition the identifier at `offset` refers to.
+pub fn find_definition<'a>(
+    text: &str,
+    offset: usize,
+    definitions: &'a [Definition],
+) -> Option<(Range<usize>, &'a Definition)> {
+    let (range, word) = word_at(text, offset)?;
+    // Globals are referred to by name in strings, components by bare name
+    let kind =
+        if in_string(text, offset) { DefinitionKind::Global } else { DefinitionKind::Component };
+    let def = definitions.iter().find(|d| d.kind == kind && d.name == word)?;
+    Some((range, def))
+}
+
+/// Markdown describing the identifier at `offset`.
+pub fn hover(
+    text: &str,
+    offset: usize,
+    definitions: &[Definition],
+) -> Option<(Range<usize>, String)> {
+    let (range, word) = word_at(text, offset)?;
+
+    if !in_string(text, offset) {
+        let after = text[range.end..].trim_start();
+        let scope = scan(text, range.start).scopes.pop().unwrap_or(Scope::TopLevel);
+
+        if let (Scope::Node(node), true) = (&scope, after.starts_with('=')) {
+            if let Some(spec) = schema_for(node).and_then(|s| s.get(word)) {
+                return Some((range, format!("{}\n\nProperty of `{}`", prop_line(spec), node)));
+            }
+        }
+
+        if let (Some(schema), true) = (schema_for(word), after.starts_with(['{', '"'])) {
+            return Some((range, node_docs(schema)));
+        }
+    }
+
+    let (range, def) = find_definition(text, offset, definitions)?;
+    Some((range, format!("```nbcl\n{}\n```", def.snippet)))
+}
+
+/// Components and globals defined in `text`.
+pub fn definitions(text: &str) -> Vec<Definition> {
+    let mut defs = Vec::new();
+
+    for caps in crate::regex!(r"(?m)^[ \t]*component\s+([A-Za-z_]\w*)[^{\n]*").captures_iter(text) {
+        let name = caps.get(1).unwrap();
+        defs.push(Definition {
+            kind: DefinitionKind::Component,
+            name: name.as_str().to_string(),
+            range: name.range(),
+            snippet: caps[0].trim().to_string(),
+        });
+    }
+
+    for caps in crate::regex!(r#"(?m)^[ \t]*(?:Poll|Listen)\s+"([^"]*)""#).captures_iter(text) {
+        let whole = caps.get(0).unwrap();
+        let name = caps.get(1).unwrap();
+        let start = whole.start() + (whole.as_str().len() - whole.as_str().trim_start().len());
+        defs.push(Definition {
+            kind: DefinitionKind::Global,
+            name: name.as_str().to_string(),
+            range: name.range(),
+            snippet: block_at(text, start),
+        });
+    }
+
+    defs
+}
+
+/// The `{ ... }` block starting at `start`, cut off after a few lines.
+fn block_at(text: &str, start: usize) -> String {
+    const MAX_LINES: usize = 12;
+
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut prev = '\0';
+    let mut end = text.len();
+    for (i, c) in text[start..].char_indices() {
+        match c {
+            '"' if prev != '\\' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    end = start + i + 1;
+                    break;
+                }
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+
+    let lines: Vec<&str> = text[start..end].lines().collect();
+    if lines.len() > MAX_LINES {
+        format!("{}\n    ...", lines[..MAX_LINES].join("\n"))
+    } else {
+        lines.join("\n")
+    }
+}
+
+fn type_label(spec: &PropSpec) -> String {
+    match spec.ty {
+        PropType::Enum(values) => {
+            let values: Vec<_> = values.iter().map(|v| format!("\"{}\"", v)).collect();
+            format!("one of {}", values.join(", "))
+        }
+        ty => schema::describe_type(ty).to_string(),
+    }
+}
+
+fn prop_line(spec: &PropSpec) -> String {
+    let required = if spec.required { " (required)" } else { "" };
+    format!("`{}`: {}{}", spec.name, type_label(spec), required)
+}
+
+fn node_docs(schema: &WidgetSchema) -> String {
+    let mut doc = format!("**{}**\n", schema.name);
+    for spec in schema.props {
+        doc.push_str(&format!("\n- {}", prop_line(spec)));
+    }
+
+    if schema.common {
+        let common: Vec<_> =
+            schema.all_props().skip(schema.props.len()).map(|p| format!("`{}`", p.name)).collect();
+        doc.push_str(&format!("\n\nAlso takes the common props {}.", common.join(", ")));
+    }
+
+    doc
+}
+
+/// Byte offset of an LSP position, which counts UTF-16 code units.
+pub fn offset_at(text: &str, line: u32, character: u32) -> usize {
+    let line_start = if line == 0 {
+        0
+    } else {
+        match text.match_indices('\n').nth(line as usize - 1) {
+            Some((i, _)) => i + 1,
+            None => return text.len(),
+        }
+    };
+
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16() as u32;
+    }
+    text.len()
+}
+
+/// LSP position of a byte offset, as `(line, character)`.
+pub fn position_at(text: &str, offset: usize) -> (u32, u32) {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() as u32;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character = before[line_start..].chars().map(|c| c.len_utf16() as u32).sum();
+    (line, character)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = r#"
+component Bar () {
+    Box {
+        orientation = "h"
+        halign = "cen"
+
+        Label { text = global("time") }
+    }
+}
+
+Poll "time" {
+    interval = "10s"
+    cmd = "date"
+}
+
+Window "bar" {
+    geometry = { anchor = "top center" }
+    Bar {}
+}
+"#;
+
+    fn offset_after(pattern: &str) -> usize {
+        CODE.find(pattern).unwrap() + pattern.len()
+    }
+
+    #[test]
+    fn test_completion_context() {
+        assert_eq!(
+            completion_context(CODE, offset_after("\"cen")),
+            CompletionContext::PropValue {
+                node: "Box".into(),
+                prop: "halign".into(),
+                quoted: true
+            }
+        );
+        assert_eq!(
+            completion_context(CODE, offset_after("\"h\"\n")),
+            CompletionContext::Statement(Scope::Node("Box".into()))
+        );
+        assert_eq!(
+            completion_context(CODE, offset_after("global(\"ti")),
+            CompletionContext::GlobalName
+        );
+        assert_eq!(
+            completion_context(CODE, offset_after("anchor = \"top")),
+            CompletionContext::None
+        );
+        assert_eq!(
+            completion_context(CODE, offset_after("component Bar () {\n")),
+            CompletionContext::Statement(Scope::Code)
+        );
+        assert_eq!(
+            completion_context(CODE, CODE.len()),
+            CompletionContext::Statement(Scope::TopLevel)
+        );
+    }
+
+    #[test]
+    fn test_complete() {
+        let defs = definitions(CODE);
+        let context = CompletionContext::PropValue {
+            node: "Box".into(),
+            prop: "halign".into(),
+            quoted: false,
+        };
+        let entries = complete(&context, &defs);
+        assert!(entries
+            .iter()
+            .any(|e| e.label == "center" && e.insert_text.as_deref() == Some("\"center\"")));
+
+        let entries = complete(&CompletionContext::Statement(Scope::Code), &defs);
+        assert!(entries.iter().any(|e| e.label == "Label" && e.kind == EntryKind::Node));
+        assert!(entries.iter().any(|e| e.label == "Bar" && e.kind == EntryKind::Component));
+
+        let entries = complete(&CompletionContext::GlobalName, &defs);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].label, "time");
+    }
+
+    #[test]
+    fn test_definitions() {
+        let defs = definitions(CODE);
+        assert_eq!(defs.len(), 2);
+        assert_eq!(&CODE[defs[0].range.clone()], "Bar");
+        assert_eq!(defs[0].snippet, "component Bar ()");
+        assert_eq!(&CODE[defs[1].range.clone()], "time");
+        assert!(defs[1].snippet.ends_with("cmd = \"date\"\n}"));
+
+        let (_, def) = find_definition(CODE, offset_after("    Ba"), &defs).unwrap();
+        assert_eq!(def.name, "Bar");
+        let (_, def) = find_definition(CODE, offset_after("global(\"ti"), &defs).unwrap();
+        assert_eq!(def.kind, DefinitionKind::Global);
+    }
+
+    #[test]
+    fn test_hover() {
+        let defs = definitions(CODE);
+        let (range, doc) = hover(CODE, offset_after("    hal"), &defs).unwrap();
+        assert_eq!(&CODE[range], "halign");
+        assert!(doc.contains("Property of `Box`"));
+
+        let (_, doc) = hover(CODE, offset_after("    Bo"), &defs).unwrap();
+        assert!(doc.starts_with("**Box**"));
+        assert!(doc.contains("`orientation`"));
+    }
+
+    #[test]
+    fn test_positions() {
+        let text = "a\nñé😀x\n";
+        let offset = text.find('x').unwrap();
+        assert_eq!(position_at(text, offset), (1, 4));
+        assert_eq!(offset_at(text, 1, 4), offset);
+        assert_eq!(offset_at(text, 5, 0), text.len());
+    }
+}
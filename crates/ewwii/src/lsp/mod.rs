@@ -0,0 +1,448 @@
+//! `ewwii lsp`, a language server for nbcl configs speaking LSP over stdio.
+//!
+//! Syntax errors are reported while typing. Evaluation errors, including
+//! property errors from the widget schema, are reported when a document is
+//! opened or saved, as evaluating on every keystroke would be wasteful.
+
+mod analysis;
+
+use analysis::{CompletionEntry, EntryKind};
+use anyhow::{bail, Context, Result};
+use ewwii_nbcl_impl::errors::SourceDiagnostic;
+use ewwii_nbcl_impl::parser::NbclConfigParser;
+use ewwii_plugin_api::IpcRequest;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// How deep to look for other nbcl files next to an open document.
+const MAX_SEARCH_DEPTH: usize = 4;
+
+struct Server {
+    /// Open documents by uri.
+    documents: HashMap<String, String>,
+    /// Uri and text of the nbcl files on disk, by the directory searched.
+    /// Open documents are served from `documents` instead, so this only
+    /// goes stale when a file is saved or closed.
+    disk_sources: RefCell<HashMap<PathBuf, Vec<(String, String)>>>,
+    parser: NbclConfigParser,
+    // Requests sent during evaluation are dropped, keep the receiver alive
+    // so the parser does not notice.
+    _ipc_rx: UnboundedReceiver<IpcRequest>,
+    shutdown_requested: bool,
+}
+
+/// Serve requests on stdin until the client exits.
+pub fn run() -> Result<()> {
+    let mut reader = std::io::stdin().lock();
+    let mut writer = std::io::stdout().lock();
+    let mut server = Server::new();
+
+    log::info!("Starting ewwii language server");
+
+    while let Some(message) = read_message(&mut reader)? {
+        let method = message.get("method").and_then(Value::as_str);
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        match (method, message.get("id")) {
+            (Some("exit"), _) => {
+                if !server.shutdown_requested {
+                    bail!("Language server exited without a shutdown request");
+                }
+                return Ok(());
+            }
+            (Some(method), Some(id)) => {
+                let response = match server.handle_request(method, &params) {
+                    Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    None => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": format!("Unknown method {}", method) },
+                    }),
+                };
+                write_message(&mut writer, &response)?;
+            }
+            (Some(method), None) => {
+                if let Some(notification) = server.handle_notification(method, &params) {
+                    write_message(&mut writer, &notification)?;
+                }
+            }
+            // Responses to requests, the server never sends any
+            (None, _) => {}
+        }
+    }
+
+    Ok(())
+}
+
+impl Server {
+    fn new() -> Self {
+        let (ipc_tx, ipc_rx) = tokio::sync::mpsc::unbounded_channel::<IpcRequest>();
+        Self {
+            documents: HashMap::new(),
+            disk_sources: RefCell::default(),
+            parser: NbclConfigParser::new(ipc_tx),
+            _ipc_rx: ipc_rx,
+            shutdown_requested: false,
+        }
+    }
+
+    /// Handle a request, returning `None` for unknown methods.
+    fn handle_request(&mut self, method: &str, params: &Value) -> Option<Value> {
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": {
+                        "openClose": true,
+                        "change": 1,
+                        "save": { "includeText": false },
+                    },
+                    "completionProvider": { "triggerCharacters": ["\"", "{", "("] },
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                },
+                "serverInfo": { "name": "ewwii", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                Value::Null
+            }
+            "textDocument/completion" => self.completion(params).unwrap_or(Value::Null),
+            "textDocument/hover" => self.hover(params).unwrap_or(Value::Null),
+            "textDocument/definition" => self.definition(params).unwrap_or(Value::Null),
+            _ => return None,
+        };
+
+        Some(result)
+    }
+
+    /// Handle a notification, returning a notification to send back if any.
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Option<Value> {
+        let uri = params["textDocument"]["uri"].as_str()?.to_string();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str()?;
+                self.documents.insert(uri.clone(), text.to_string());
+                Some(self.publish_diagnostics(&uri, true))
+            }
+            "textDocument/didChange" => {
+                // Full sync, the last change holds the whole document
+                let text = params["contentChanges"].as_array()?.last()?["text"].as_str()?;
+                self.documents.insert(uri.clone(), text.to_string());
+                Some(self.publish_diagnostics(&uri, false))
+            }
+            "textDocument/didSave" => {
+                self.disk_sources.get_mut().clear();
+                Some(self.publish_diagnostics(&uri, true))
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.disk_sources.get_mut().clear();
+                Some(diagnostics_notification(&uri, Vec::new()))
+            }
+            _ => None,
+        }
+    }
+
+    fn publish_diagnostics(&self, uri: &str, evaluate: bool) -> Value {
+        let Some(text) = self.documents.get(uri) else {
+            return diagnostics_notification(uri, Vec::new());
+        };
+
+        // Imports are resolved relative to the working directory, same as in the daemon
+        if let Some(dir) = uri_to_path(uri).as_deref().and_then(Path::parent) {
+            if let Err(e) = std::env::set_current_dir(dir) {
+                log::warn!("Failed to change working directory to {}: {}", dir.display(), e);
+            }
+        }
+
+        let diagnostics = self
+            .parser
+            .diagnose(text, evaluate)
+            .iter()
+            .map(|diag| to_lsp_diagnostic(text, diag))
+            .collect();
+
+        diagnostics_notification(uri, diagnostics)
+    }
+
+    fn completion(&self, params: &Value) -> Option<Value> {
+        let (uri, text, offset) = self.document_position(params)?;
+        let definitions: Vec<_> = self
+            .sources(uri)
+            .into_iter()
+            .flat_map(|(_, text)| analysis::definitions(&text))
+            .collect();
+
+        let context = analysis::completion_context(text, offset);
+        let items: Vec<_> =
+            analysis::complete(&context, &definitions).iter().map(to_lsp_completion).collect();
+
+        Some(json!(items))
+    }
+
+    fn hover(&self, params: &Value) -> Option<Value> {
+        let (uri, text, offset) = self.document_position(params)?;
+        let definitions: Vec<_> = self
+            .sources(uri)
+            .into_iter()
+            .flat_map(|(_, text)| analysis::definitions(&text))
+            .collect();
+
+        let (range, contents) = analysis::hover(text, offset, &definitions)?;
+        Some(json!({
+            "contents": { "kind": "markdown", "value": contents },
+            "range": lsp_range(text, range),
+        }))
+    }
+
+    fn definition(&self, params: &Value) -> Option<Value> {
+        let (uri, text, offset) = self.document_position(params)?;
+
+        for (source_uri, source) in self.sources(uri) {
+            let definitions = analysis::definitions(&source);
+            let Some((_, def)) = analysis::find_definition(text, offset, &definitions) else {
+                continue;
+            };
+
+            return Some(
+                json!({ "uri": source_uri, "range": lsp_range(&source, def.range.clone()) }),
+            );
+        }
+
+        None
+    }
+
+    /// Uri, text and byte offset of the position a request is about.
+    fn document_position<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a str, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let text = self.documents.get(uri)?;
+        let line = params["position"]["line"].as_u64()? as u32;
+        let character = params["position"]["character"].as_u64()? as u32;
+        Some((uri, text, analysis::offset_at(text, line, character)))
+    }
+
+    /// The document at `uri` followed by every other nbcl source that may
+    /// define something it uses: other open documents and nbcl files on disk
+    /// next to it.
+    fn sources(&self, uri: &str) -> Vec<(String, String)> {
+        let mut sources: Vec<(String, String)> = Vec::new();
+        if let Some(text) = self.documents.get(uri) {
+            sources.push((uri.to_string(), text.clone()));
+        }
+        for (other_uri, text) in &self.documents {
+            if other_uri != uri {
+                sources.push((other_uri.clone(), text.clone()));
+            }
+        }
+
+        if let Some(dir) = uri_to_path(uri).as_deref().and_then(Path::parent) {
+            let mut disk_sources = self.disk_sources.borrow_mut();
+            let files =
+                disk_sources.entry(dir.to_path_buf()).or_insert_with(|| read_nbcl_files(dir));
+
+            for (file_uri, text) in files.iter() {
+                if !self.documents.contains_key(file_uri) {
+                    sources.push((file_uri.clone(), text.clone()));
+                }
+            }
+        }
+
+        sources
+    }
+}
+
+/// Uri and text of every nbcl file in `dir` and below.
+fn read_nbcl_files(dir: &Path) -> Vec<(String, String)> {
+    let mut files = Vec::new();
+    find_nbcl_files(dir, MAX_SEARCH_DEPTH, &mut files);
+
+    files
+        .into_iter()
+        .filter_map(|file| Some((path_to_uri(&file), std::fs::read_to_string(&file).ok()?)))
+        .collect()
+}
+
+fn find_nbcl_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() && depth > 0 {
+            find_nbcl_files(&path, depth - 1, files);
+        } else if path.extension().is_some_and(|ext| ext == "nbcl") {
+            files.push(path);
+        }
+    }
+}
+
+fn diagnostics_notification(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn to_lsp_diagnostic(text: &str, diag: &SourceDiagnostic) -> Value {
+    let mut message = format!("[{}] {}", diag.label, diag.message);
+    if let Some(hint) = &diag.hint {
+        message.push_str(&format!("\nhint: {}", hint));
+    }
+
+    json!({
+        "range": lsp_range(text, diag.range.clone().unwrap_or(0..0)),
+        "severity": 1,
+        "source": "ewwii",
+        "message": message,
+    })
+}
+
+fn to_lsp_completion(entry: &CompletionEntry) -> Value {
+    // CompletionItemKind values from the LSP specification
+    let kind = match entry.kind {
+        EntryKind::Node => 7,
+        EntryKind::Prop => 10,
+        EntryKind::Value => 20,
+        EntryKind::Component => 3,
+        EntryKind::Global => 6,
+        EntryKind::Keyword => 14,
+    };
+
+    let mut item = json!({ "label": entry.label, "kind": kind });
+    if let Some(detail) = &entry.detail {
+        item["detail"] = json!(detail);
+    }
+    if let Some(insert_text) = &entry.insert_text {
+        item["insertText"] = json!(insert_text);
+    }
+    item
+}
+
+fn lsp_range(text: &str, range: std::ops::Range<usize>) -> Value {
+    let (start_line, start_char) = analysis::position_at(text, range.start);
+    let (end_line, end_char) = analysis::position_at(text, range.end);
+    json!({
+        "start": { "line": start_line, "character": start_char },
+        "end": { "line": end_line, "character": end_char },
+    })
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let decoded = (b == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match decoded {
+            Some(byte) => {
+                bytes.push(byte);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for b in path.to_string_lossy().bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    uri
+}
+
+/// Read one message, returning `None` once the input is closed.
+fn read_message(reader: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(len) = header.strip_prefix("Content-Length:") {
+            content_length = Some(len.trim().parse::<usize>().context("Invalid Content-Length")?);
+        }
+    }
+
+    let len = content_length.context("Message is missing the Content-Length header")?;
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body).context("Invalid JSON-RPC message")?))
+}
+
+fn write_message(writer: &mut impl Write, message: &Value) -> Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_roundtrip() {
+        let message = json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} });
+        let mut buf = Vec::new();
+        write_message(&mut buf, &message).unwrap();
+
+        let mut reader = std::io::Cursor::new(buf);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(message));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_uri_conversion() {
+        let path = Path::new("/home/me/.config/ewwii/my bar.nbcl");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///home/me/.config/ewwii/my%20bar.nbcl");
+        assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+    }
+
+    #[test]
+    fn test_sources_cache() {
+        let dir = std::env::temp_dir().join(format!("ewwii-lsp-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("widgets.nbcl"), "old").unwrap();
+
+        let mut server = Server::new();
+        let uri = path_to_uri(&dir.join("ewwii.nbcl"));
+        server.documents.insert(uri.clone(), "main".to_string());
+        let texts = |server: &Server| -> Vec<String> {
+            server.sources(&uri).into_iter().map(|(_, text)| text).collect()
+        };
+        assert_eq!(texts(&server), ["main", "old"]);
+
+        std::fs::write(dir.join("widgets.nbcl"), "new").unwrap();
+        assert_eq!(texts(&server), ["main", "old"]);
+        let saved = json!({ "textDocument": { "uri": path_to_uri(&dir.join("widgets.nbcl")) } });
+        server.handle_notification("textDocument/didSave", &saved);
+        assert_eq!(texts(&server), ["main", "new"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
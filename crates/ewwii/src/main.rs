@@ -6,7 +6,7 @@ extern crate gtk4;
 extern crate gtk4_layer_shell as gtk4_layer_shell;
 
 // imporing dependencies
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::CommandFactory as _;
 use daemon_response::{DaemonResponse, DaemonResponseReceiver};
 use display_backend::DisplayBackend;
@@ -19,8 +19,10 @@ use crate::server::ForkResult;
 // importing all local modules
 mod app; // major thing here
 mod application_lifecycle;
+mod check;
 mod client;
 mod config;
+mod css_vars;
 mod daemon_response;
 mod diag_error;
 mod display_backend;
@@ -29,10 +31,12 @@ mod error_handling_ctx;
 mod file_database;
 mod gen_diagnostic_macro;
 mod ipc_server;
+mod lsp;
 mod opts;
 mod paths;
 mod plugin;
 mod property_macro;
+mod render;
 mod server;
 mod updates;
 mod util;
@@ -141,6 +145,17 @@ fn run<B: DisplayBackend>(opts: opts::Opt, ewwii_binary_name: String) -> Result<
         opts::Action::WithServer(action) => opts.restart && action.can_start_daemon(),
         opts::Action::ClientOnly(_) => false,
     };
+    if let Some(fixtures) = &opts.mock_fixtures {
+        // The fixtures are only ever loaded into a daemon this command starts
+        if !should_restart && check_server_running(paths.get_ipc_socket_file()) {
+            bail!(
+                "Ewwii server already running, so `--mock` would be ignored. \
+                 Pass `--restart` to restart it with the fixtures."
+            );
+        }
+        updates::mock::load_fixtures(fixtures)?;
+    }
+
     if should_restart {
         let response = handle_server_command(&paths, &ActionWithServer::KillServer, 1);
         if let Ok(Some(response)) = response {
@@ -171,8 +186,12 @@ fn run<B: DisplayBackend>(opts: opts::Opt, ewwii_binary_name: String) -> Result<
                     ewwii_binary_name
                 );
             }
-            let fork_result =
-                server::initialize_server::<B>(paths.clone(), None, !opts.no_daemonize)?;
+            let fork_result = server::initialize_server::<B>(
+                paths.clone(),
+                None,
+                !opts.no_daemonize,
+                opts.headless,
+            )?;
             opts.no_daemonize || fork_result == ForkResult::Parent
         }
 
@@ -211,8 +230,12 @@ fn run<B: DisplayBackend>(opts: opts::Opt, ewwii_binary_name: String) -> Result<
 
                     let (command, response_recv) = action.into_daemon_command();
                     // start the daemon and give it the command
-                    let fork_result =
-                        server::initialize_server::<B>(paths.clone(), Some(command), true)?;
+                    let fork_result = server::initialize_server::<B>(
+                        paths.clone(),
+                        Some(command),
+                        true,
+                        opts.headless,
+                    )?;
                     let is_parent = fork_result == ForkResult::Parent;
                     if let (Some(recv), true) = (response_recv, is_parent) {
                         listen_for_daemon_response(recv);
@@ -22,6 +22,8 @@ pub struct Opt {
     pub config_path: Option<std::path::PathBuf>,
     pub action: Action,
     pub no_daemonize: bool,
+    pub headless: bool,
+    pub mock_fixtures: Option<std::path::PathBuf>,
 }
 
 #[derive(Parser, Debug, Serialize, Deserialize, PartialEq)]
@@ -52,6 +54,15 @@ pub(super) struct RawOpt {
     #[arg(long = "restart", global = true)]
     restart: bool,
 
+    /// Run the daemon without a display. Windows are tracked but never shown.
+    #[arg(long = "headless", global = true)]
+    headless: bool,
+
+    /// Replace the values of Poll and Listen variables with the fixtures in this JSON file.
+    /// Their commands are never started. Only applies to a daemon this command starts.
+    #[arg(long = "mock", global = true)]
+    mock: Option<std::path::PathBuf>,
+
     #[command(subcommand)]
     action: Action,
 }
@@ -85,6 +96,34 @@ pub enum ActionClientOnly {
     /// Print the plugin version
     #[command(name = "plugin-version")]
     GetPluginVersion,
+
+    /// Check the configuration for errors without starting the daemon
+    #[command(name = "check")]
+    Check,
+
+    /// Run a language server for the configuration over stdio
+    #[command(name = "lsp")]
+    Lsp,
+
+    /// Render a window to a PNG image. Needs a display, and the window shows on it while
+    /// rendering on X11 without a compositor, so run it with e.g. `xvfb-run`
+    #[command(name = "render")]
+    Render {
+        /// Name of the window to render
+        window_name: String,
+
+        /// Path of the PNG file to write
+        #[arg(short, long)]
+        out: std::path::PathBuf,
+
+        /// Size of the image in pixels (i.e.: 400x40)
+        #[arg(short, long, value_parser = parse_size)]
+        size: (i32, i32),
+
+        /// Set a variable before rendering, i.e.: `--var "time=12:00"`. Can be repeated.
+        #[arg(long = "var", value_parser = parse_var_assignment)]
+        vars: Vec<(String, String)>,
+    },
 }
 
 #[derive(Subcommand, Debug, Serialize, Deserialize, PartialEq)]
@@ -207,6 +246,37 @@ pub enum ActionWithServer {
         mappings: HashMap<String, String>,
     },
 
+    /// Record every variable update to a file, to be replayed with `ewwii replay`
+    #[command(name = "record")]
+    Record {
+        /// Path of the recording to write, in JSON lines
+        #[arg(long, value_parser = parse_absolute_path, required_unless_present = "stop")]
+        out: Option<std::path::PathBuf>,
+
+        /// Stop the running recording
+        #[arg(long, conflicts_with = "out")]
+        stop: bool,
+    },
+
+    /// Replay a recording made with `ewwii record`. Signals are paused until the next reload.
+    #[command(name = "replay")]
+    Replay {
+        /// Path of the recording
+        #[arg(value_parser = parse_absolute_path)]
+        file: std::path::PathBuf,
+
+        /// Playback speed, i.e.: 2x
+        #[arg(long, default_value = "1x", value_parser = parse_speed)]
+        speed: f64,
+    },
+
+    /// Switch the stylesheet of the running daemon
+    #[command(name = "css")]
+    Css {
+        #[command(subcommand)]
+        command: CssCommand,
+    },
+
     /// Run an nbcl expression
     #[command(name = "nbcl-run")]
     NbclRun {
@@ -218,6 +288,23 @@ pub enum ActionWithServer {
     },
 }
 
+/// Subcommands for switching stylesheets
+#[derive(Subcommand, Debug, Serialize, Deserialize, PartialEq)]
+pub enum CssCommand {
+    /// Load a stylesheet from a file in place of ewwii.(s)css
+    Load {
+        /// Path of the .scss or .css file
+        #[arg(value_parser = parse_absolute_path)]
+        file: std::path::PathBuf,
+    },
+
+    /// Load the stylesheet `themes/<name>.(s)css` from the config directory
+    Theme {
+        /// Name of the theme
+        name: String,
+    },
+}
+
 /// Subcommands for widget control
 #[derive(Subcommand, Debug, Serialize, Deserialize, PartialEq)]
 pub enum WidgetControlCommand {
@@ -314,8 +401,17 @@ impl Opt {
 
 impl From<RawOpt> for Opt {
     fn from(other: RawOpt) -> Self {
-        let RawOpt { log_debug, force_wayland, config, show_logs, no_daemonize, restart, action } =
-            other;
+        let RawOpt {
+            log_debug,
+            force_wayland,
+            config,
+            show_logs,
+            no_daemonize,
+            restart,
+            headless,
+            mock,
+            action,
+        } = other;
         Opt {
             log_debug,
             force_wayland,
@@ -324,6 +420,8 @@ impl From<RawOpt> for Opt {
             config_path: config,
             action,
             no_daemonize,
+            headless,
+            mock_fixtures: mock,
         }
     }
 }
@@ -353,6 +451,19 @@ impl ActionWithServer {
             ActionWithServer::NbclRun { expr } => {
                 return with_response_channel(|sender| app::DaemonCommand::NbclRun { expr, sender })
             }
+            ActionWithServer::Record { out, stop: _ } => {
+                return with_response_channel(|sender| app::DaemonCommand::Record { out, sender })
+            }
+            ActionWithServer::Replay { file, speed } => {
+                return with_response_channel(|sender| app::DaemonCommand::Replay {
+                    file,
+                    speed,
+                    sender,
+                })
+            }
+            ActionWithServer::Css { command } => {
+                return with_response_channel(|sender| app::DaemonCommand::Css { command, sender })
+            }
             ActionWithServer::OpenInspector => app::DaemonCommand::OpenInspector,
 
             ActionWithServer::KillServer => app::DaemonCommand::KillServer,
@@ -428,6 +539,36 @@ where
     (f(sender), Some(recv))
 }
 
+fn parse_size(s: &str) -> Result<(i32, i32), String> {
+    let parse = |n: &str| n.trim().parse::<i32>().ok().filter(|n| *n > 0);
+    match s.split_once(['x', 'X']) {
+        Some((w, h)) => parse(w).zip(parse(h)),
+        None => None,
+    }
+    .ok_or_else(|| format!("Invalid size '{}', expected WIDTHxHEIGHT in pixels", s))
+}
+
+fn parse_var_assignment(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(name, value)| (name.trim().to_string(), value.to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .ok_or_else(|| format!("Invalid variable '{}', expected name=value", s))
+}
+
+/// The daemon runs in the config directory, so paths are resolved by the client.
+fn parse_absolute_path(s: &str) -> Result<std::path::PathBuf, String> {
+    std::path::absolute(s).map_err(|e| format!("Invalid path '{}': {}", s, e))
+}
+
+fn parse_speed(s: &str) -> Result<f64, String> {
+    s.trim()
+        .trim_end_matches(['x', 'X'])
+        .parse::<f64>()
+        .ok()
+        .filter(|speed| speed.is_finite() && *speed > 0.0)
+        .ok_or_else(|| format!("Invalid speed '{}', expected a positive factor like 2x", s))
+}
+
 fn parse_duration(s: &str) -> Result<std::time::Duration, crate::dynval::ConversionError> {
     DynVal::from_string(s.to_owned()).as_duration()
 }
@@ -489,3 +630,27 @@ fn parse_inject_var_map(s: &str) -> Result<HashMap<String, String>, String> {
     }
     Ok(map)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("400x40"), Ok((400, 40)));
+        assert_eq!(parse_size(" 1920 X 1080 "), Ok((1920, 1080)));
+        assert!(parse_size("400").is_err());
+        assert!(parse_size("0x40").is_err());
+        assert!(parse_size("-400x40").is_err());
+        assert!(parse_size("400x40x2").is_err());
+    }
+
+    #[test]
+    fn test_parse_var_assignment() {
+        assert_eq!(parse_var_assignment("time=12:00"), Ok(("time".into(), "12:00".into())));
+        assert_eq!(parse_var_assignment(" cmd =a=b"), Ok(("cmd".into(), "a=b".into())));
+        assert_eq!(parse_var_assignment("empty="), Ok(("empty".into(), String::new())));
+        assert!(parse_var_assignment("time").is_err());
+        assert!(parse_var_assignment("=12:00").is_err());
+    }
+}
@@ -12,6 +12,7 @@ use ewwii_plugin_api::{
 use gtk4::glib::translate::FromGlibPtrFull;
 use ewwii_shared_utils::ast::WidgetNode;
 use ewwii_shared_utils::prop::Callback;
+use ewwii_shared_utils::template::{register_template_function, TemplateFn};
 use nbcl::library::Library as NbclLibrary;
 use nbcl::library::LibraryItem as NbclLibraryItem;
 use nbcl::Type as ActualNbclType;
@@ -19,6 +20,7 @@ use nbcl::Value as NbclValue;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::sync::OnceLock;
 use std::sync::RwLock;
 use tokio::sync::mpsc;
@@ -173,6 +175,55 @@ fn trigger_plugin_func_call(
         .unwrap_or(PluginValue::Null)
 }
 
+/// Template values are plain strings, numbers and booleans are passed to
+/// the plugin typed so it doesn't have to parse them itself.
+fn template_arg_to_plugin_value(arg: &str) -> PluginValue {
+    if let Ok(i) = arg.parse::<i64>() {
+        PluginValue::Int(i)
+    } else if let Ok(f) = arg.parse::<f64>() {
+        PluginValue::Float(f)
+    } else if let Ok(b) = arg.parse::<bool>() {
+        PluginValue::Bool(b)
+    } else {
+        PluginValue::String(arg.to_string())
+    }
+}
+
+fn plugin_value_to_json(val: PluginValue) -> serde_json::Value {
+    match val {
+        PluginValue::String(s) => serde_json::Value::String(s),
+        PluginValue::Int(i) => i.into(),
+        PluginValue::Float(f) => f.into(),
+        PluginValue::Bool(b) => b.into(),
+        PluginValue::Array(arr) => arr.into_iter().map(plugin_value_to_json).collect(),
+        PluginValue::Null => serde_json::Value::Null,
+    }
+}
+
+fn plugin_value_to_template(val: PluginValue) -> String {
+    match val {
+        PluginValue::String(s) => s,
+        PluginValue::Null => String::new(),
+        other => plugin_value_to_json(other).to_string(),
+    }
+}
+
+fn trigger_plugin_template_call(
+    plugin_id: &str,
+    callback_id: u64,
+    args: &[String],
+) -> Result<String, String> {
+    let args: Vec<PluginValue> = args.iter().map(|a| template_arg_to_plugin_value(a)).collect();
+    let arg_bytes = bincode::serialize(&args).unwrap_or_default();
+    let res = call_plugin_handler(plugin_id, callback_id, arg_bytes)
+        .ok_or_else(|| format!("Plugin '{}' returned null", plugin_id))?;
+    match bincode::deserialize::<CallbackResponse>(&res).map_err(|e| e.to_string())? {
+        CallbackResponse::PluginValue(v) => Ok(plugin_value_to_template(v)),
+        CallbackResponse::Error(e) => Err(e.to_string()),
+        _ => Err("Unexpected response type".to_string()),
+    }
+}
+
 fn trigger_plugin_config_parse(
     plugin_id: &str,
     callback_id: u64,
@@ -275,6 +326,15 @@ impl<B: DisplayBackend> App<B> {
 
                 self.register_lib_internal(id, name, items);
             }
+            PluginRequest::RegisterTemplateFn { id, name, arity, callback_id } => {
+                let handler: TemplateFn = Arc::new(move |args: &[String]| {
+                    trigger_plugin_template_call(&id, callback_id, args)
+                });
+
+                if let Err(e) = register_template_function(&name, arity, handler) {
+                    log::error!("Failed to register template function: {}", e);
+                }
+            }
             PluginRequest::RegisterConfigEngine { id, extension, main_file, callback_id } => {
                 if extension.trim().is_empty() || main_file.trim().is_empty() {
                     log::error!("File extension or main file cannot be empty");
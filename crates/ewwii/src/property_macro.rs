@@ -1,31 +1,80 @@
 use crate::updates::api::VarWatcherAPI;
-use crate::updates::SHUTDOWN_REGISTRY;
 use ewwii_shared_utils::prop::Callback;
 use ewwii_shared_utils::template::TemplateExpr;
 use gtk4::glib;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
 use std::rc::Rc;
 use tokio::sync::watch;
 
 thread_local! {
-    static ACTIVE_TASKS: RefCell<Vec<glib::JoinHandle<()>>> = const { RefCell::new(Vec::new()) };
+    /// Running property tasks, with the registry id of the widget they belong to.
+    static ACTIVE_TASKS: RefCell<Vec<(u64, glib::JoinHandle<()>)>> = const { RefCell::new(Vec::new()) };
+    static TASK_OWNER: Cell<u64> = const { Cell::new(0) };
+    /// Shutdown senders of the tasks behind template bound properties, with their owner.
+    static TEMPLATE_SHUTDOWNS: RefCell<Vec<(u64, watch::Sender<bool>)>> = const { RefCell::new(Vec::new()) };
 }
 
 pub fn close_all_property_tasks() {
     ACTIVE_TASKS.with(|tasks| {
         let mut tasks_mut = tasks.borrow_mut();
-        for handle in tasks_mut.drain(..) {
+        for (_, handle) in tasks_mut.drain(..) {
             handle.abort();
         }
     });
+    TEMPLATE_SHUTDOWNS.with(|shutdowns| {
+        for (_, shutdown_tx) in shutdowns.borrow_mut().drain(..) {
+            let _ = shutdown_tx.send(true);
+        }
+    });
+}
+
+/// Stop the property tasks of the widgets with the given registry ids.
+pub fn close_property_tasks_of(owners: &HashSet<u64>) {
+    ACTIVE_TASKS.with(|tasks| {
+        tasks.borrow_mut().retain(|(owner, handle)| {
+            if owners.contains(owner) {
+                handle.abort();
+                return false;
+            }
+            true
+        });
+    });
+    TEMPLATE_SHUTDOWNS.with(|shutdowns| {
+        shutdowns.borrow_mut().retain(|(owner, shutdown_tx)| {
+            if owners.contains(owner) {
+                let _ = shutdown_tx.send(true);
+                return false;
+            }
+            true
+        });
+    });
+}
+
+/// Run `f` with every task registered in it owned by the widget `owner`.
+pub fn with_task_owner<T>(owner: u64, f: impl FnOnce() -> T) -> T {
+    let previous = TASK_OWNER.replace(owner);
+    let result = f();
+    TASK_OWNER.set(previous);
+    result
 }
 
 pub fn register_task(handle: glib::JoinHandle<()>) {
     ACTIVE_TASKS.with(|tasks| {
-        tasks.borrow_mut().push(handle);
+        tasks.borrow_mut().push((TASK_OWNER.get(), handle));
     });
 }
 
+/// Shutdown signal of the tasks behind a template bound property, sent along
+/// with the other tasks of the widget the property belongs to.
+pub fn template_shutdown() -> watch::Receiver<bool> {
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    TEMPLATE_SHUTDOWNS.with(|shutdowns| {
+        shutdowns.borrow_mut().push((TASK_OWNER.get(), shutdown_tx));
+    });
+    shutdown_rx
+}
+
 pub fn handle_template(template: TemplateExpr) -> watch::Receiver<String> {
     let watched_vars = template.collect_vars();
     let watched_vars: Vec<String> =
@@ -35,8 +84,7 @@ pub fn handle_template(template: TemplateExpr) -> watch::Receiver<String> {
     let (notify_tx, mut notify_rx) = tokio::sync::mpsc::unbounded_channel::<()>();
     let _ = notify_tx.send(()); // init
 
-    let (shutdown_tx, shutdown_rx) = watch::channel(false);
-    SHUTDOWN_REGISTRY.lock().unwrap().push(shutdown_tx);
+    let shutdown_rx = template_shutdown();
 
     // subscribe to all vars in template
     for var_name in watched_vars.clone() {
@@ -0,0 +1,133 @@
+//! Rendering a window to an image for `ewwii render`.
+//!
+//! The window is built from the config like the daemon would, but no signals
+//! are started. Variables only hold the values given on the command line.
+//! GTK still needs a display connection to lay out widgets. The window is
+//! really mapped on that display, only with an opacity of 0, so it can still
+//! take focus or show up in a taskbar for a moment. On X11 without a
+//! compositor the opacity is ignored and the window flashes on screen while
+//! it renders. Run it on a display of its own such as `xvfb-run`, which is
+//! enough since drawing uses the cairo renderer and needs no GPU or compositor.
+
+use crate::{
+    config::{self, ConfigEngine, EWWII_CONFIG_PARSER},
+    updates::{self, api::VarWatcherAPI, SignalType},
+    widgets::{
+        build_widget::build_gtk_widget, build_widget::WidgetInput,
+        widget_definitions::WidgetRegistry,
+    },
+    EwwiiPaths,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use ewwii_plugin_api::IpcRequest;
+use gtk4::prelude::*;
+use gtk4::{gdk, glib, graphene, gsk};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How long to let layout and property tasks settle before drawing.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Render the window `window_name` at `size` and write it to `out` as a PNG.
+pub fn render_window(
+    paths: &EwwiiPaths,
+    window_name: &str,
+    out: &Path,
+    size: (i32, i32),
+    vars: &[(String, String)],
+) -> Result<()> {
+    let (width, height) = size;
+
+    std::env::set_current_dir(paths.get_config_dir()).with_context(|| {
+        format!("Failed to change working directory to {}", paths.get_config_dir().display())
+    })?;
+
+    // Keep rendering on the CPU. This has to be set before GTK is initialized,
+    // and applies to the whole process, which only ever renders this window.
+    std::env::set_var("GSK_RENDERER", "cairo");
+
+    let (ipc_tx, _ipc_rx) = tokio::sync::mpsc::unbounded_channel::<IpcRequest>();
+    EWWII_CONFIG_PARSER.with(|p| {
+        let config_parser = ewwii_nbcl_impl::parser::NbclConfigParser::new(ipc_tx);
+        *p.borrow_mut() = Some(ConfigEngine::Default(Box::new(config_parser)));
+    });
+
+    let ewwii_config = config::read_from_ewwii_paths(paths, Vec::new())?;
+    let window_def = ewwii_config.get_window(window_name)?.clone();
+
+    // Bound properties subscribe to variables with tokio tasks
+    let runtime = tokio::runtime::Runtime::new()?;
+    let _guard = runtime.enter();
+
+    gtk4::init().context("Failed to initialize GTK, rendering still needs a display")?;
+    let display = gdk::Display::default().context("No display available to render with")?;
+
+    let config_dir = paths.get_config_dir();
+    if config_dir.join("ewwii.scss").exists() || config_dir.join("ewwii.css").exists() {
+        let (_, css) = config::scss::parse_scss_from_config(config_dir)?;
+        let css_provider = gtk4::CssProvider::new();
+        css_provider.load_from_string(&css);
+        gtk4::style_context_add_provider_for_display(&display, &css_provider, 900);
+    }
+
+    // Registering keeps the first value, so `--var` wins over `initial`
+    for (name, value) in vars {
+        VarWatcherAPI::register(name, value.clone());
+    }
+    for signal in updates::retreive_signals(&ewwii_config.get_root_node()?) {
+        if matches!(signal.signal_type, SignalType::Poll | SignalType::Listen) {
+            VarWatcherAPI::register(&signal.name, signal.initial());
+        }
+    }
+
+    let mut registry = WidgetRegistry::new();
+    let root_widget = build_gtk_widget(&WidgetInput::Window(window_def), &mut registry)?;
+    root_widget.add_css_class(window_name);
+
+    let window = gtk4::Window::builder()
+        .decorated(false)
+        .resizable(false)
+        .default_width(width)
+        .default_height(height)
+        .child(&root_widget)
+        .build();
+
+    // The window has to be mapped on the display for its widgets to draw,
+    // GTK 4 has no offscreen windows. Only the root widget is drawn, which
+    // leaves this opacity out. X11 needs a compositor to honor it.
+    window.set_opacity(0.0);
+    window.present();
+
+    let main_context = glib::MainContext::default();
+    let settle_until = Instant::now() + SETTLE_TIME;
+    while Instant::now() < settle_until {
+        if !main_context.iteration(false) {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    if !root_widget.is_mapped() {
+        window.destroy();
+        bail!("Window `{}` could not be mapped for rendering", window_name);
+    }
+
+    let snapshot = gtk4::Snapshot::new();
+    gtk4::WidgetPaintable::new(Some(&root_widget)).snapshot(&snapshot, width as f64, height as f64);
+    let node = snapshot.to_node().with_context(|| format!("Window `{}` drew nothing", window_name));
+
+    let result = node.and_then(|node| {
+        let renderer = gsk::CairoRenderer::new();
+        renderer.realize(None).map_err(|e| anyhow!("Failed to start cairo renderer: {}", e))?;
+        let bounds = graphene::Rect::new(0.0, 0.0, width as f32, height as f32);
+        let texture = renderer.render_texture(&node, Some(&bounds));
+        renderer.unrealize();
+
+        texture.save_to_png(out).with_context(|| format!("Failed to write {}", out.display()))
+    });
+
+    window.destroy();
+    result?;
+
+    println!("Rendered `{}` to {}", window_name, out.display());
+    Ok(())
+}
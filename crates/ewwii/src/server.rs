@@ -7,8 +7,9 @@ use crate::{
     error_handling_ctx, ipc_server, plugin, EwwiiPaths,
 };
 use anyhow::{Context, Result};
+use ewwii_nbcl_impl::libraries::watch_appearance;
 use ewwii_plugin_api::IpcRequest;
-use gtk4::prelude::{DisplayExt, ListModelExt};
+use gtk4::prelude::{DisplayExt, ListModelExt, ObjectExt};
 use std::{
     collections::{HashMap, HashSet},
     ffi::OsString,
@@ -16,7 +17,6 @@ use std::{
     marker::PhantomData,
     os::unix::io::AsRawFd,
     path::Path,
-    sync::{atomic::Ordering, Arc},
 };
 use tokio::sync::mpsc::*;
 
@@ -24,6 +24,7 @@ pub fn initialize_server<B: DisplayBackend>(
     paths: EwwiiPaths,
     action: Option<DaemonCommand>,
     should_daemonize: bool,
+    headless: bool,
 ) -> Result<ForkResult> {
     let (ui_send, mut ui_recv) = tokio::sync::mpsc::unbounded_channel();
 
@@ -72,11 +73,17 @@ pub fn initialize_server<B: DisplayBackend>(
         },
     );
 
-    if B::IS_WAYLAND {
-        std::env::set_var("GDK_BACKEND", "wayland")
-    }
+    // Headless daemons never touch GDK, so they run without a display.
+    // Only glib is used to drive the main loop.
+    if headless {
+        log::info!("Running headless, windows will not be shown");
+    } else {
+        if B::IS_WAYLAND {
+            std::env::set_var("GDK_BACKEND", "wayland")
+        }
 
-    gtk4::init()?;
+        gtk4::init()?;
+    }
 
     let main_loop = gtk4::glib::MainLoop::new(None, false);
 
@@ -85,12 +92,18 @@ pub fn initialize_server<B: DisplayBackend>(
         open_windows: HashMap::new(),
         failed_windows: HashSet::new(),
         instance_id_to_args: HashMap::new(),
-        css_provider: gtk4::CssProvider::new(),
+        css_provider: (!headless).then(gtk4::CssProvider::new),
+        css_var_provider: (!headless).then(gtk4::CssProvider::new),
+        dark_css_provider: (!headless).then(gtk4::CssProvider::new),
+        stylesheet: None,
+        portal_appearance: None,
+        dark_mode: false,
         custom_css_providers: Vec::new(),
-        gdk_display: gtk4::gdk::Display::default(),
+        gdk_display: if headless { None } else { gtk4::gdk::Display::default() },
         plugin_buffer: plugin::PluginBuffer::new(),
         nbcl_bootstraps: Vec::new(),
         reloading: false,
+        headless,
         app_evt_send: ui_send.clone(),
         window_close_timer_abort_senders: HashMap::new(),
         widget_reg_store: std::rc::Rc::new(std::sync::Mutex::new(None)),
@@ -126,8 +139,15 @@ pub fn initialize_server<B: DisplayBackend>(
         }
     };
 
-    if let Some(display) = &app.gdk_display {
-        gtk4::style_context_add_provider_for_display(display, &app.css_provider, 900);
+    if let (Some(display), Some(css_provider)) = (&app.gdk_display, &app.css_provider) {
+        gtk4::style_context_add_provider_for_display(display, css_provider, 900);
+        if let Some(dark_css_provider) = &app.dark_css_provider {
+            gtk4::style_context_add_provider_for_display(display, dark_css_provider, 901);
+        }
+        if let Some(css_var_provider) = &app.css_var_provider {
+            // above the user's stylesheets, so the values win over their defaults
+            gtk4::style_context_add_provider_for_display(display, css_var_provider, 902);
+        }
         app.plugin_buffer.emit("ewwii-applied-styles", "true");
     }
 
@@ -137,7 +157,15 @@ pub fn initialize_server<B: DisplayBackend>(
         }
     }
 
-    connect_monitor_added(ui_send.clone());
+    // start out with the GTK settings, the portal may take a moment to answer
+    if let Err(e) = app.apply_appearance() {
+        error_handling_ctx::print_error(e);
+    }
+
+    if !headless {
+        connect_monitor_added(ui_send.clone());
+        connect_gtk_settings_changed(ui_send.clone());
+    }
 
     let config_ext = {
         EWWII_CONFIG_PARSER
@@ -200,6 +228,25 @@ fn connect_monitor_added(ui_send: UnboundedSender<DaemonCommand>) {
     }
 }
 
+fn connect_gtk_settings_changed(ui_send: UnboundedSender<DaemonCommand>) {
+    let Some(settings) = gtk4::Settings::default() else {
+        return;
+    };
+
+    for property in ["gtk-application-prefer-dark-theme", "gtk-theme-name"] {
+        settings.connect_notify_local(
+            Some(property),
+            gtk4::glib::clone!(
+                #[strong]
+                ui_send,
+                move |_, _| {
+                    let _ = ui_send.send(DaemonCommand::RefreshAppearance);
+                }
+            ),
+        );
+    }
+}
+
 fn reload_config_and_css(ui_send: &UnboundedSender<DaemonCommand>) -> Result<()> {
     let (daemon_resp_sender, mut daemon_resp_response) = daemon_response::create_pair();
     ui_send.send(DaemonCommand::ReloadConfigAndCss(daemon_resp_sender))?;
@@ -215,6 +262,21 @@ fn reload_config_and_css(ui_send: &UnboundedSender<DaemonCommand>) -> Result<()>
     Ok(())
 }
 
+fn reload_css(ui_send: &UnboundedSender<DaemonCommand>) -> Result<()> {
+    let (daemon_resp_sender, mut daemon_resp_response) = daemon_response::create_pair();
+    ui_send.send(DaemonCommand::ReloadCss(daemon_resp_sender))?;
+    tokio::spawn(async move {
+        match daemon_resp_response.recv().await {
+            Some(daemon_response::DaemonResponse::Success(_)) => {
+                log::info!("Reloaded stylesheet successfully")
+            }
+            Some(daemon_response::DaemonResponse::Failure(e)) => eprintln!("{}", e),
+            None => log::error!("No response to stylesheet-reload request"),
+        }
+    });
+    Ok(())
+}
+
 fn init_async_part(
     paths: EwwiiPaths,
     ui_send: UnboundedSender<app::DaemonCommand>,
@@ -246,6 +308,12 @@ fn init_async_part(
                     })
                 };
 
+                // Not joined below, desktops without a session bus go on without it
+                {
+                    let ui_send = ui_send.clone();
+                    tokio::spawn(async move { forward_appearance(ui_send).await });
+                }
+
                 let forward_exit_to_app_handle = {
                     let ui_send = ui_send.clone();
                     tokio::spawn(async move {
@@ -273,6 +341,24 @@ fn init_async_part(
     handle
 }
 
+/// Forward the appearance settings of the desktop portal to the ewwii app.
+async fn forward_appearance(ui_send: UnboundedSender<app::DaemonCommand>) {
+    let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let watcher = tokio::spawn(watch_appearance(tx, shutdown_rx));
+
+    while let Some(appearance) = rx.recv().await {
+        if ui_send.send(app::DaemonCommand::SetAppearance(appearance)).is_err() {
+            break;
+        }
+    }
+
+    let _ = shutdown_tx.send(true);
+    if let Ok(Err(e)) = watcher.await {
+        log::warn!("Not following the desktop appearance: {}", e);
+    }
+}
+
 /// Watch configuration files for changes, sending reload events to the ewwii app when the files change.
 async fn run_filewatch<P: AsRef<Path>>(
     config_dir: P,
@@ -285,12 +371,14 @@ async fn run_filewatch<P: AsRef<Path>>(
     let mut watcher: RecommendedWatcher =
         notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
             Ok(notify::Event { kind: notify::EventKind::Modify(_), paths, .. }) => {
-                let relevant_files_changed = paths.iter().any(|path| {
+                let config_changed =
+                    paths.iter().any(|path| path.extension().unwrap_or_default() == config_ext);
+                let css_changed = paths.iter().any(|path| {
                     let ext = path.extension().unwrap_or_default();
-                    ext == config_ext || ext == "scss" || ext == "css"
+                    ext == "scss" || ext == "css"
                 });
-                if relevant_files_changed {
-                    if let Err(err) = tx.send(()) {
+                if config_changed || css_changed {
+                    if let Err(err) = tx.send(config_changed) {
                         log::warn!("Error forwarding file update event: {:?}", err);
                     }
                 }
@@ -300,28 +388,33 @@ async fn run_filewatch<P: AsRef<Path>>(
         })?;
     watcher.watch(config_dir.as_ref(), RecursiveMode::Recursive)?;
 
-    // make sure to not trigger reloads too much by only accepting one reload every 500ms.
-    let debounce_done = Arc::new(std::sync::atomic::AtomicBool::new(true));
-
     crate::loop_select_exiting! {
-        Some(()) = rx.recv() => {
-            let debounce_done = debounce_done.clone();
-            if debounce_done.swap(false, Ordering::SeqCst) {
-                tokio::spawn(async move {
-                    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-                    debounce_done.store(true, Ordering::SeqCst);
-                });
+        Some(mut config_changed) = rx.recv() => {
+            // make sure to not trigger reloads too much by collecting the events of the
+            // next 500ms into one reload. It reloads the config if any of them asks for it,
+            // so a stylesheet change saved along with the config cannot hide the latter.
+            //
+            // without this wait, reading the config file sometimes gives an empty file.
+            // This is probably a result of editors not locking the file correctly,
+            // and eww being too fast, thus reading the file while it's empty.
+            // There should be some cleaner solution for this, but this will do for now.
+            // - Elkowar
+            //
+            // Byson94 here, I am not sure if this issue is still relevant,
+            // but I am keeping this for now.
+            let debounce = tokio::time::sleep(std::time::Duration::from_millis(500));
+            tokio::pin!(debounce);
+            loop {
+                tokio::select! {
+                    _ = &mut debounce => break,
+                    Some(changed) = rx.recv() => config_changed |= changed,
+                }
+            }
 
-                // without this sleep, reading the config file sometimes gives an empty file.
-                // This is probably a result of editors not locking the file correctly,
-                // and eww being too fast, thus reading the file while it's empty.
-                // There should be some cleaner solution for this, but this will do for now.
-                // - Elkowar
-                //
-                // Byson94 here, I am not sure if this issue is still relevant,
-                // but I am keeping this for now.
-                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            if config_changed {
                 reload_config_and_css(&evt_send)?;
+            } else {
+                reload_css(&evt_send)?;
             }
         },
         else => break
@@ -19,7 +19,14 @@ pub struct VarWatcherAPI;
 
 impl VarWatcherAPI {
     /// Register new variable
+    ///
+    /// A variable that is registered already keeps its watcher and value,
+    /// so whatever subscribed to it keeps receiving updates.
     pub fn register(var_name: &str, initial_value: String) {
+        if VAR_WATCHERS.read().unwrap().contains_key(var_name) {
+            return;
+        }
+
         let (tx, _) = watch::channel(initial_value.clone());
         VAR_WATCHERS.write().unwrap().insert(var_name.to_owned(), tx);
         GLOBAL_VAR_STORE.write().unwrap().insert(var_name.to_owned(), initial_value);
@@ -42,6 +49,7 @@ impl VarWatcherAPI {
 
     /// Update the store and broadcast
     pub fn update_with_broadcast(var_name: &str, val: String) {
+        super::record::record(var_name, &val);
         GLOBAL_VAR_STORE.write().unwrap().insert(var_name.to_owned(), val.clone());
         Self::broadcast_value(var_name, &val);
     }
@@ -80,8 +88,11 @@ impl VarWatcherAPI {
     }
 
     /// Clear all variable state and watchers
+    ///
+    /// The values of the `ewwii:*` globals are kept, the daemon only sets them
+    /// when what they describe changes.
     pub fn clear_all() {
-        GLOBAL_VAR_STORE.write().unwrap().clear();
+        GLOBAL_VAR_STORE.write().unwrap().retain(|name, _| name.starts_with("ewwii:"));
         VAR_WATCHERS.write().unwrap().clear();
         PENDING_SUBSCRIBERS.write().unwrap().clear();
     }
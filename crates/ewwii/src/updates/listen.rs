@@ -1,4 +1,5 @@
-use super::{api::VarWatcherAPI, SHUTDOWN_REGISTRY};
+use super::{api::VarWatcherAPI, register_shutdown};
+use ewwii_nbcl_impl::libraries::stream_source;
 use ewwii_shared_utils::prop::PropertyMap;
 use ewwii_shared_utils::prop_utils::*;
 use nix::libc;
@@ -109,18 +110,30 @@ pub async fn stream_cmd_lines(
 
 pub fn handle_listen(var_name: String, props: &PropertyMap, shell: String) {
     const CMD_KEY: &str = "cmd";
+    const SOURCE_KEY: &str = "source";
+
+    // A builtin data source takes the place of the command
+    let source_prop = soft_retreive_prop(props, SOURCE_KEY, "");
+    let source = match get_string_prop(&source_prop, SOURCE_KEY) {
+        Ok(s) => unwrap_static(SOURCE_KEY, s),
+        Err(e) => {
+            log::warn!("Listen {} source property is invalid: {}", var_name, e);
+            return;
+        }
+    };
 
     let cmd_prop = soft_retreive_prop(props, CMD_KEY, "");
     let cmd = match get_string_prop(&cmd_prop, CMD_KEY) {
         Ok(c) => unwrap_static(CMD_KEY, c),
-        Err(e) => {
+        Err(e) if source.is_empty() => {
             log::warn!("Listen {} cmd property either missing or invalid: {}", var_name, e);
             return;
         }
+        Err(_) => String::new(),
     };
 
     let (shutdown_tx, shutdown_rx) = watch::channel(false);
-    SHUTDOWN_REGISTRY.lock().unwrap().push(shutdown_tx.clone());
+    register_shutdown(shutdown_tx.clone());
 
     // Task to catch SIGINT and SIGTERM
     tokio::spawn({
@@ -144,8 +157,17 @@ pub fn handle_listen(var_name: String, props: &PropertyMap, shell: String) {
     tokio::spawn(async move {
         let (tx, mut rx) = mpsc::channel::<String>(32);
 
-        // Spawn the generic streamer
-        tokio::spawn(stream_cmd_lines(shell, cmd, tx, shutdown_rx));
+        if source.is_empty() {
+            // Spawn the generic streamer
+            tokio::spawn(stream_cmd_lines(shell, cmd, tx, shutdown_rx));
+        } else {
+            let var_name = var_name.clone();
+            tokio::spawn(async move {
+                if let Err(e) = stream_source(&source, tx, shutdown_rx).await {
+                    log::error!("[{}] data source '{}' failed: {}", var_name, source, e);
+                }
+            });
+        }
 
         // Handle dedup + broadcast in this task
         let mut last_value: Option<String> = None;
@@ -0,0 +1,313 @@
+//! Scripted values standing in for `Poll` and `Listen` signals.
+//!
+//! A signal is mocked by an entry in the fixture file given with `--mock`,
+//! or by its own `mock` property. The fixture file wins if both are set.
+//! A mocked signal never spawns its command, its variable is only fed the
+//! scripted values, so `ewwii update` keeps working on it as usual.
+//!
+//! A mock is written as one of:
+//! - a plain value, which is set once: `"connected"`
+//! - a list, cycled through every second: `["1", "2", "3"]`
+//! - a map with `value`, `sequence` or `walk` and an optional `interval`:
+//!   `{ "walk": [0, 100], "step": 5, "interval": "2s" }`
+
+use super::{api::VarWatcherAPI, register_shutdown};
+use anyhow::{anyhow, bail, Context, Result};
+use ewwii_shared_utils::prop::{Property, PropertyMap};
+use ewwii_shared_utils::prop_utils::get_duration_prop;
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::sleep;
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Mocks loaded from the `--mock` fixture file, keyed by variable name.
+static FIXTURES: OnceCell<HashMap<String, MockSource>> = OnceCell::new();
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockSource {
+    /// A value that is set once.
+    Constant(String),
+    /// Values cycled through, one per interval.
+    Sequence { values: Vec<String>, interval: Duration },
+    /// A number moving up or down by `step` every interval, kept within `min..=max`.
+    RandomWalk { min: f64, max: f64, step: f64, initial: f64, interval: Duration },
+}
+
+impl MockSource {
+    pub fn from_property(prop: &Property) -> Result<Self> {
+        match prop {
+            Property::Array(items) => Ok(MockSource::Sequence {
+                values: sequence_values(items)?,
+                interval: DEFAULT_INTERVAL,
+            }),
+            Property::Map(map) => Self::from_map(map),
+            _ => scalar_value(prop).map(MockSource::Constant),
+        }
+    }
+
+    fn from_map(map: &PropertyMap) -> Result<Self> {
+        let interval = match map.get("interval") {
+            Some(prop) => get_duration_prop(prop, "interval")?,
+            None => DEFAULT_INTERVAL,
+        };
+
+        if let Some(value) = map.get("value") {
+            return scalar_value(value).map(MockSource::Constant);
+        }
+
+        if let Some(sequence) = map.get("sequence") {
+            let Property::Array(items) = sequence else {
+                bail!("`sequence` must be a list of values");
+            };
+            return Ok(MockSource::Sequence { values: sequence_values(items)?, interval });
+        }
+
+        if let Some(walk) = map.get("walk") {
+            let bounds = match walk {
+                Property::Array(items) if items.len() == 2 => {
+                    (number_value(&items[0], "walk")?, number_value(&items[1], "walk")?)
+                }
+                _ => bail!("`walk` must be a list of two numbers, the lower and upper bound"),
+            };
+            let (min, max) = if bounds.0 <= bounds.1 { bounds } else { (bounds.1, bounds.0) };
+
+            let step = match map.get("step") {
+                Some(prop) => number_value(prop, "step")?,
+                None => 1.0,
+            };
+            if step <= 0.0 {
+                bail!("`step` must be greater than zero");
+            }
+
+            let initial = match map.get("initial") {
+                Some(prop) => number_value(prop, "initial")?.clamp(min, max),
+                None => (min + max) / 2.0,
+            };
+
+            return Ok(MockSource::RandomWalk { min, max, step, initial, interval });
+        }
+
+        bail!("Expected a map with one of `value`, `sequence` or `walk`")
+    }
+}
+
+fn scalar_value(prop: &Property) -> Result<String> {
+    match prop {
+        Property::String(s) => Ok(s.clone()),
+        Property::Int(i) => Ok(i.to_string()),
+        Property::Float(f) => Ok(f.to_string()),
+        Property::Bool(b) => Ok(b.to_string()),
+        _ => bail!("Expected a string, number or bool as mock value"),
+    }
+}
+
+fn sequence_values(items: &[Property]) -> Result<Vec<String>> {
+    if items.is_empty() {
+        bail!("A mock sequence needs at least one value");
+    }
+    items.iter().map(scalar_value).collect()
+}
+
+fn number_value(prop: &Property, key: &str) -> Result<f64> {
+    match prop {
+        Property::Int(i) => Ok(*i as f64),
+        Property::Float(f) => Ok(*f),
+        Property::String(s) => {
+            s.trim().parse().map_err(|_| anyhow!("Expected a number for `{}`, got \"{}\"", key, s))
+        }
+        _ => bail!("Expected a number for `{}`", key),
+    }
+}
+
+fn json_to_property(value: serde_json::Value) -> Property {
+    match value {
+        serde_json::Value::Null => Property::None,
+        serde_json::Value::Bool(b) => Property::Bool(b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Property::Int(i),
+            None => Property::Float(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => Property::String(s),
+        serde_json::Value::Array(items) => {
+            Property::Array(items.into_iter().map(json_to_property).collect())
+        }
+        serde_json::Value::Object(entries) => {
+            let mut map = PropertyMap::new();
+            for (key, value) in entries {
+                map.insert(key, json_to_property(value));
+            }
+            Property::Map(map)
+        }
+    }
+}
+
+/// Read the fixture file, a JSON object mapping variable names to mocks.
+pub fn load_fixtures(path: &Path) -> Result<()> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read mock fixtures from {}", path.display()))?;
+    let json: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&content)
+        .with_context(|| format!("{} is not a JSON object", path.display()))?;
+
+    let mut fixtures = HashMap::new();
+    for (name, value) in json {
+        let source = MockSource::from_property(&json_to_property(value))
+            .with_context(|| format!("Invalid mock fixture for `{}`", name))?;
+        fixtures.insert(name, source);
+    }
+
+    log::info!("Loaded {} mock fixture(s) from {}", fixtures.len(), path.display());
+    FIXTURES.set(fixtures).map_err(|_| anyhow!("Mock fixtures were already loaded"))
+}
+
+/// The mock replacing the signal `var_name`, if there is one.
+pub fn mock_for(var_name: &str, props: &PropertyMap) -> Result<Option<MockSource>> {
+    if let Some(source) = FIXTURES.get().and_then(|f| f.get(var_name)) {
+        return Ok(Some(source.clone()));
+    }
+
+    props.get("mock").map(MockSource::from_property).transpose()
+}
+
+pub fn handle_mock(var_name: String, source: MockSource) {
+    log::info!("[{}] is mocked, its command will not be started", var_name);
+
+    let (values, interval) = match source {
+        MockSource::Constant(value) => {
+            VarWatcherAPI::update_with_broadcast(&var_name, value);
+            return;
+        }
+        MockSource::Sequence { values, interval } => {
+            (MockValues::Sequence { values, index: 0 }, interval)
+        }
+        MockSource::RandomWalk { min, max, step, initial, interval } => {
+            let seed = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default();
+            let walk =
+                MockValues::Walk { min, max, step, current: initial, rng: XorShift::new(seed) };
+            (walk, interval)
+        }
+    };
+
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+    register_shutdown(shutdown_tx);
+
+    tokio::spawn(async move {
+        let mut values = values;
+        loop {
+            let value = values.next_value();
+            log::debug!("[{}] mocked value: {}", var_name, value);
+            VarWatcherAPI::update_with_broadcast(&var_name, value);
+
+            tokio::select! {
+                _ = sleep(interval) => {}
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Running state of a mock that changes over time.
+enum MockValues {
+    Sequence { values: Vec<String>, index: usize },
+    Walk { min: f64, max: f64, step: f64, current: f64, rng: XorShift },
+}
+
+impl MockValues {
+    fn next_value(&mut self) -> String {
+        match self {
+            MockValues::Sequence { values, index } => {
+                let value = values[*index % values.len()].clone();
+                *index = (*index + 1) % values.len();
+                value
+            }
+            MockValues::Walk { min, max, step, current, rng } => {
+                let value = *current;
+                let delta = if rng.next_u64() % 2 == 0 { *step } else { -*step };
+                // Round away the float noise that would build up over many steps
+                *current = ((*current + delta).clamp(*min, *max) * 1e9).round() / 1e9;
+                value.to_string()
+            }
+        }
+    }
+}
+
+/// Small xorshift generator, plenty to wiggle a fixture value around.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero
+        XorShift(seed | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<MockSource> {
+        MockSource::from_property(&json_to_property(serde_json::from_str(json).unwrap()))
+    }
+
+    #[test]
+    fn test_parse_mock_sources() {
+        assert_eq!(parse(r#""connected""#).unwrap(), MockSource::Constant("connected".into()));
+        assert_eq!(parse("42").unwrap(), MockSource::Constant("42".into()));
+        assert_eq!(
+            parse(r#"["1", 2]"#).unwrap(),
+            MockSource::Sequence {
+                values: vec!["1".into(), "2".into()],
+                interval: DEFAULT_INTERVAL
+            }
+        );
+        assert_eq!(
+            parse(r#"{ "walk": [100, 0], "step": 5, "interval": "2s" }"#).unwrap(),
+            MockSource::RandomWalk {
+                min: 0.0,
+                max: 100.0,
+                step: 5.0,
+                initial: 50.0,
+                interval: Duration::from_secs(2),
+            }
+        );
+
+        assert!(parse("[]").is_err());
+        assert!(parse(r#"{ "walk": [0] }"#).is_err());
+        assert!(parse(r#"{ "walk": [0, 10], "step": 0 }"#).is_err());
+        assert!(parse(r#"{ "interval": "1s" }"#).is_err());
+    }
+
+    #[test]
+    fn test_mock_values() {
+        let mut sequence = MockValues::Sequence { values: vec!["a".into(), "b".into()], index: 0 };
+        let cycled: Vec<_> = (0..3).map(|_| sequence.next_value()).collect();
+        assert_eq!(cycled, ["a", "b", "a"]);
+
+        let mut walk =
+            MockValues::Walk { min: 0.0, max: 3.0, step: 1.5, current: 0.0, rng: XorShift::new(7) };
+        for _ in 0..100 {
+            let value: f64 = walk.next_value().parse().unwrap();
+            assert!((0.0..=3.0).contains(&value));
+            assert_eq!(value % 1.5, 0.0);
+        }
+    }
+}
@@ -1,6 +1,8 @@
 pub mod api;
 mod listen;
+pub mod mock;
 mod poll;
+pub mod record;
 mod script;
 
 use crate::config::ConfigEngine;
@@ -8,16 +10,39 @@ use api::VarWatcherAPI;
 use ewwii_shared_utils::ast::WidgetNode;
 use ewwii_shared_utils::prop::PropertyMap;
 use listen::handle_listen;
+use mock::{handle_mock, mock_for};
 use once_cell::sync::Lazy;
 use poll::handle_poll;
 use script::handle_script;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::process::Command;
 use std::sync::Mutex;
 use tokio::sync::watch;
 
-pub static SHUTDOWN_REGISTRY: Lazy<Mutex<Vec<watch::Sender<bool>>>> =
+/// Global holding the error of the last rejected reload, empty while the config is fine.
+pub const CONFIG_ERROR_VAR: &str = "ewwii:config_error";
+/// Global holding the theme or stylesheet picked with `ewwii css`, empty for the default one.
+pub const THEME_VAR: &str = "ewwii:theme";
+/// Global holding the color scheme of the desktop, `dark` or `light`.
+pub const COLOR_SCHEME_VAR: &str = "ewwii:color_scheme";
+/// Global holding the accent color of the desktop as `#rrggbb`, empty if it has none.
+pub const ACCENT_COLOR_VAR: &str = "ewwii:accent_color";
+
+/// Shutdown senders of the running signal handlers, with the id of their signal.
+static SHUTDOWN_REGISTRY: Lazy<Mutex<Vec<(u64, watch::Sender<bool>)>>> =
     Lazy::new(|| Mutex::new(Vec::new()));
 
+thread_local! {
+    static STARTING_SIGNAL: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Register the shutdown sender of a handler, owned by the signal being started.
+pub fn register_shutdown(shutdown_tx: watch::Sender<bool>) {
+    SHUTDOWN_REGISTRY.lock().unwrap().push((STARTING_SIGNAL.get(), shutdown_tx));
+}
+
 pub fn get_prefered_shell() -> String {
     // Check Dash and prefer if dash is installed.
     let dash_installed: bool =
@@ -42,6 +67,26 @@ pub struct SignalProps {
     pub signal_type: SignalType,
 }
 
+impl SignalProps {
+    /// Identity of the signal, ignoring where in the config it is defined.
+    pub fn id(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let kind = match self.signal_type {
+            SignalType::Poll => 0,
+            SignalType::Listen => 1,
+            SignalType::Script => 2,
+        };
+        (kind, &self.name).hash(&mut hasher);
+        self.props.0.iter().filter(|(key, _)| *key != "dyn_id").for_each(|p| p.hash(&mut hasher));
+        hasher.finish()
+    }
+
+    /// The `initial` value of a poll or listen, empty when it is not set.
+    pub fn initial(&self) -> String {
+        self.props.get("initial").and_then(|p| p.as_str()).unwrap_or_default().to_string()
+    }
+}
+
 pub fn retreive_signals(root_node: &WidgetNode) -> Vec<SignalProps> {
     let mut signals: Vec<SignalProps> = Vec::new();
 
@@ -85,30 +130,100 @@ pub fn retreive_signals(root_node: &WidgetNode) -> Vec<SignalProps> {
     signals
 }
 
-pub fn handle_state_changes(parser: &ConfigEngine, signals: Vec<SignalProps>) {
+/// Names of the globals that widgets are two-way bound to with `bind = global("name")`.
+pub fn retreive_bound_globals(root_node: &WidgetNode) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut stack = vec![root_node];
+
+    while let Some(node) = stack.pop() {
+        match node {
+            WidgetNode::Tree(children) => stack.extend(children),
+            WidgetNode::DefWindow { node, .. } => stack.push(node),
+            _ => {
+                let bound = node.props().and_then(|props| props.get("bind"));
+                if let Some(var) = bound.and_then(|prop| prop.as_global_var()) {
+                    if !names.contains(&var.name) {
+                        names.push(var.name.clone());
+                    }
+                }
+                stack.extend(crate::widgets::tree_diff::children(node));
+            }
+        }
+    }
+
+    names
+}
+
+pub fn handle_state_changes(
+    parser: &ConfigEngine,
+    signals: Vec<SignalProps>,
+    bound_globals: Vec<String>,
+) {
     let shell = get_prefered_shell();
 
+    // Keep the values set before any window was open
+    for var_name in [CONFIG_ERROR_VAR, THEME_VAR, COLOR_SCHEME_VAR, ACCENT_COLOR_VAR] {
+        VarWatcherAPI::register(var_name, VarWatcherAPI::state_of(var_name));
+    }
+
+    // Bound globals need a watcher even when no signal sets them
+    for var_name in bound_globals {
+        VarWatcherAPI::register(&var_name, VarWatcherAPI::state_of(&var_name));
+    }
+
     for signal in signals {
-        match signal.signal_type {
-            SignalType::Poll => {
-                VarWatcherAPI::register(&signal.name, String::new());
-                handle_poll(signal.name, &signal.props, shell.clone());
-            }
-            SignalType::Listen => {
-                VarWatcherAPI::register(&signal.name, String::new());
-                handle_listen(signal.name, &signal.props, shell.clone());
+        STARTING_SIGNAL.set(signal.id());
+        start_signal(parser, signal, &shell);
+    }
+    STARTING_SIGNAL.set(0);
+}
+
+fn start_signal(parser: &ConfigEngine, signal: SignalProps, shell: &str) {
+    if matches!(signal.signal_type, SignalType::Poll | SignalType::Listen) {
+        VarWatcherAPI::register(&signal.name, signal.initial());
+
+        match mock_for(&signal.name, &signal.props) {
+            Ok(Some(source)) => {
+                handle_mock(signal.name, source);
+                return;
             }
-            SignalType::Script => {
-                handle_script(parser, &signal.props, shell.clone());
+            Ok(None) => {}
+            Err(e) => {
+                // Never fall back to the real command when a mock was asked for
+                log::error!("Invalid mock for {}, not starting it: {}", signal.name, e);
+                return;
             }
         }
     }
+
+    match signal.signal_type {
+        SignalType::Poll => {
+            handle_poll(signal.name, &signal.props, shell.to_string());
+        }
+        SignalType::Listen => {
+            handle_listen(signal.name, &signal.props, shell.to_string());
+        }
+        SignalType::Script => {
+            handle_script(parser, &signal.props, shell.to_string());
+        }
+    }
 }
 
 pub fn kill_state_change_handler() {
-    let registry = SHUTDOWN_REGISTRY.lock().unwrap();
-    for sender in registry.iter() {
+    let mut registry = SHUTDOWN_REGISTRY.lock().unwrap();
+    for (_, sender) in registry.drain(..) {
         let _ = sender.send(true);
     }
     log::debug!("All state change handlers requested to stop");
 }
+
+/// Stop the handlers of the signals with the given ids.
+pub fn kill_signal_handlers(signal_ids: &HashSet<u64>) {
+    SHUTDOWN_REGISTRY.lock().unwrap().retain(|(signal_id, sender)| {
+        if signal_ids.contains(signal_id) {
+            let _ = sender.send(true);
+            return false;
+        }
+        true
+    });
+}
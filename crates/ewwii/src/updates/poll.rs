@@ -1,4 +1,4 @@
-use super::{api::VarWatcherAPI, SHUTDOWN_REGISTRY};
+use super::{api::VarWatcherAPI, register_shutdown};
 use ewwii_shared_utils::prop::PropertyMap;
 use ewwii_shared_utils::prop_utils::*;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
@@ -36,7 +36,7 @@ pub fn handle_poll(var_name: String, props: &PropertyMap, shell: String) {
     };
 
     let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
-    SHUTDOWN_REGISTRY.lock().unwrap().push(shutdown_tx.clone());
+    register_shutdown(shutdown_tx.clone());
 
     tokio::spawn(async move {
         let mut child = match Command::new(&shell)
@@ -0,0 +1,159 @@
+//! Recording variable updates to a file and replaying them later.
+//!
+//! A recording is a JSON lines file with one [`RecordedUpdate`] per line.
+//! It starts with the state of every variable at time zero, followed by
+//! every update made through [`VarWatcherAPI::update_with_broadcast`].
+
+use super::api::VarWatcherAPI;
+use anyhow::{anyhow, bail, Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static RECORDER: Lazy<Mutex<Option<Recorder>>> = Lazy::new(|| Mutex::new(None));
+static REPLAY_TASK: Lazy<Mutex<Option<tokio::task::JoinHandle<()>>>> =
+    Lazy::new(|| Mutex::new(None));
+
+/// A single line of a recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedUpdate {
+    /// Milliseconds since the recording started.
+    pub time_ms: u64,
+    pub name: String,
+    pub value: String,
+}
+
+struct Recorder {
+    file: File,
+    path: PathBuf,
+    started: Instant,
+}
+
+impl Recorder {
+    fn write(&mut self, update: &RecordedUpdate) -> Result<()> {
+        let line = serde_json::to_string(update)?;
+        writeln!(self.file, "{}", line)
+            .with_context(|| format!("Failed to write to {}", self.path.display()))
+    }
+}
+
+/// Start recording every variable update to `path`, replacing a running recording.
+pub fn start_recording(path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create recording at {}", path.display()))?;
+    let mut recorder = Recorder { file, path: path.to_path_buf(), started: Instant::now() };
+
+    let mut state: Vec<_> = VarWatcherAPI::state().into_iter().collect();
+    state.sort();
+    for (name, value) in state {
+        recorder.write(&RecordedUpdate { time_ms: 0, name, value })?;
+    }
+
+    log::info!("Recording variable updates to {}", path.display());
+    *RECORDER.lock().unwrap() = Some(recorder);
+    Ok(())
+}
+
+/// Stop the running recording, returning the path it was written to.
+pub fn stop_recording() -> Option<PathBuf> {
+    let recorder = RECORDER.lock().unwrap().take()?;
+    log::info!("Stopped recording to {}", recorder.path.display());
+    Some(recorder.path)
+}
+
+/// Append an update to the running recording, if there is one.
+pub(super) fn record(name: &str, value: &str) {
+    let mut recorder = RECORDER.lock().unwrap();
+    let Some(active) = recorder.as_mut() else {
+        return;
+    };
+
+    let update = RecordedUpdate {
+        time_ms: active.started.elapsed().as_millis() as u64,
+        name: name.to_string(),
+        value: value.to_string(),
+    };
+    if let Err(e) = active.write(&update) {
+        log::error!("Stopping recording: {:?}", e);
+        *recorder = None;
+    }
+}
+
+/// Read the updates of a recording, in the order they were made.
+pub fn read_recording(path: &Path) -> Result<Vec<RecordedUpdate>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read recording {}", path.display()))?;
+
+    let mut updates = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let update: RecordedUpdate = serde_json::from_str(line)
+            .map_err(|e| anyhow!("{}:{}: invalid update: {}", path.display(), idx + 1, e))?;
+        if updates.last().is_some_and(|last: &RecordedUpdate| last.time_ms > update.time_ms) {
+            bail!("{}:{}: updates are not in chronological order", path.display(), idx + 1);
+        }
+        updates.push(update);
+    }
+    Ok(updates)
+}
+
+/// Feed `updates` back with their original timing divided by `speed`.
+///
+/// Signals are stopped first so they do not overwrite the replayed values.
+/// They stay stopped once the replay is done, until the next reload.
+pub fn replay(updates: Vec<RecordedUpdate>, speed: f64) {
+    super::kill_state_change_handler();
+
+    let task = tokio::spawn(async move {
+        let started = Instant::now();
+        for update in updates {
+            let at = Duration::from_secs_f64(update.time_ms as f64 / 1000.0 / speed);
+            if let Some(wait) = at.checked_sub(started.elapsed()) {
+                tokio::time::sleep(wait).await;
+            }
+            log::debug!("[{}] replayed value: {}", update.name, update.value);
+            VarWatcherAPI::update_with_broadcast(&update.name, update.value);
+        }
+        log::info!("Replay finished, run `ewwii reload` to start the signals again");
+    });
+
+    if let Some(previous) = REPLAY_TASK.lock().unwrap().replace(task) {
+        previous.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_recording() {
+        let path = std::env::temp_dir().join(format!("ewwii-recording-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "{\"time_ms\":0,\"name\":\"a\",\"value\":\"1\"}\n\n{\"time_ms\":20,\"name\":\"b\",\"value\":\"x\"}\n",
+        )
+        .unwrap();
+        let updates = read_recording(&path).unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(
+            updates[1],
+            RecordedUpdate { time_ms: 20, name: "b".to_string(), value: "x".to_string() }
+        );
+
+        std::fs::write(
+            &path,
+            "{\"time_ms\":20,\"name\":\"a\",\"value\":\"1\"}\n{\"time_ms\":0,\"name\":\"a\",\"value\":\"2\"}\n",
+        )
+        .unwrap();
+        assert!(read_recording(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,5 +1,5 @@
 use super::listen::stream_cmd_lines;
-use super::SHUTDOWN_REGISTRY;
+use super::register_shutdown;
 use crate::config::ConfigEngine;
 use ewwii_shared_utils::prop::PropertyMap;
 use ewwii_shared_utils::prop_utils::*;
@@ -31,7 +31,7 @@ pub fn handle_script(parser: &ConfigEngine, props: &PropertyMap, shell: String)
     run.set_handle(Some("<script>".to_string()));
 
     let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
-    SHUTDOWN_REGISTRY.lock().unwrap().push(shutdown_tx.clone());
+    register_shutdown(shutdown_tx.clone());
 
     let parser: ConfigEngine = parser.clone();
 
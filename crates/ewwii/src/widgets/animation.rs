@@ -1,10 +1,17 @@
+use crate::widgets::animation_sequence::{Easing, Pose, Sequence};
+use ewwii_shared_utils::prop::Property;
+use ewwii_shared_utils::prop_utils::get_transition_prop;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
 use gtk4::{glib, graphene, gsk};
-use std::cell::{Cell, RefCell};
-use std::collections::VecDeque;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::time::{Duration, Instant};
 
+/// How much longer than its duration `play_to_end` waits for a sequence.
+const PLAY_TO_END_SLACK: Duration = Duration::from_millis(100);
+
 glib::wrapper! {
     pub struct AnimationWidget(ObjectSubclass<imp::AnimationWidget>)
         @extends gtk4::Widget,
@@ -35,146 +42,135 @@ impl AnimationWidget {
         }
     }
 
+    pub fn child(&self) -> Option<gtk4::Widget> {
+        self.imp().child.borrow().clone()
+    }
+
     pub fn trigger(&self, sequence: &str) {
         if !sequence.is_empty() {
             self.imp().transition_to_sequence(sequence);
         }
     }
-}
 
-#[derive(Debug, Default, Clone, Copy, PartialEq)]
-pub enum Easing {
-    #[default]
-    Linear,
-    EaseIn,
-    EaseOut,
-    EaseInOut,
-}
+    /// Name the sequences `trigger`, `select_sequence` and the event props can refer to.
+    pub fn set_sequences(&self, sequences: HashMap<String, String>) {
+        let imp = self.imp();
+        *imp.sequences.borrow_mut() = sequences;
 
-impl Easing {
-    pub fn apply(&self, t: f32) -> f32 {
-        match self {
-            Easing::Linear => t,
-            Easing::EaseIn => t * t,
-            Easing::EaseOut => t * (2.0 - t),
-            Easing::EaseInOut => {
-                if t < 0.5 {
-                    2.0 * t * t
-                } else {
-                    -1.0 + (4.0 - 2.0 * t) * t
-                }
-            }
+        // The selection may have come in before the sequence it names
+        let selected = imp.selected.borrow().clone();
+        if !selected.is_empty() {
+            imp.transition_to_sequence(&selected);
         }
     }
-}
 
-#[derive(Debug, Clone)]
-pub enum AnimProperty {
-    Scale(f32),
-    Rotate(f32),
-    Opacity(f32),
-    TranslateX(f32),
-    TranslateY(f32),
+    /// Play the sequence named `name` or written out in it, replacing the one playing.
+    /// An empty `name` stops it and returns the child to rest.
+    pub fn select_sequence(&self, name: &str) {
+        let imp = self.imp();
+        let previous = imp.selected.replace(name.to_string());
+        if !name.is_empty() {
+            imp.transition_to_sequence(name);
+        } else if !previous.is_empty() {
+            imp.play(Sequence::rest());
+        }
+    }
+
+    /// Play `sequence` and wait for it to end, or for another one to replace it.
+    /// Waits no longer than the sequence should take, as unmapped widgets get no frames.
+    pub async fn play_to_end(&self, sequence: &str) {
+        let imp = self.imp();
+        let sequence = imp.parse_sequence(sequence);
+        let Some(duration) = sequence.duration() else {
+            log::warn!("Not waiting for an animation sequence that repeats forever");
+            return;
+        };
+        if sequence.stages.is_empty() {
+            return;
+        }
+
+        let (finished_tx, finished_rx) = futures::channel::oneshot::channel();
+        imp.play(sequence);
+        imp.finished.replace(Some(finished_tx));
+
+        let timeout = glib::timeout_future(duration + PLAY_TO_END_SLACK);
+        futures::future::select(finished_rx, timeout).await;
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct TargetGroup {
-    pub properties: Vec<AnimProperty>,
+/// The `transition` of a numeric property, e.g. `300ms ease-out`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
     pub duration: Duration,
     pub easing: Easing,
-    pub raw_sequence_fragment: String,
 }
 
-fn parse_inner_value(raw: &str, default: f32) -> f32 {
-    if let (Some(start), Some(end)) = (raw.find('('), raw.find(')')) {
-        raw[start + 1..end].parse::<f32>().unwrap_or(default)
-    } else {
-        default
+impl Transition {
+    pub fn from_prop(prop: &Property, key: &str) -> anyhow::Result<Self> {
+        let (duration, easing) = get_transition_prop(prop, key)?;
+        Ok(Transition { duration, easing })
     }
 }
 
-// simple animation parser engine (similar to css)
-fn parse_stage_string(input: &str, width: f32, height: f32) -> TargetGroup {
-    let mut properties = Vec::new();
-    let mut duration = Duration::from_millis(300);
-    let mut easing = Easing::Linear;
-
-    for token in input.split('+') {
-        let parts: Vec<&str> = token.split_whitespace().collect();
-        if parts.is_empty() {
-            continue;
-        }
-
-        let name_raw = parts[0];
-        if name_raw.starts_with("scale") {
-            let scale = parse_inner_value(name_raw, 1.0);
-            properties.push(AnimProperty::Scale(scale));
-        } else if name_raw.starts_with("rotate") {
-            let deg = parse_inner_value(name_raw, 0.0);
-            properties.push(AnimProperty::Rotate(deg));
-        } else if name_raw.starts_with("fade") {
-            let alpha = parse_inner_value(name_raw, 1.0);
-            properties.push(AnimProperty::Opacity(alpha.clamp(0.0, 1.0)));
-        } else if name_raw.starts_with("slide-in-") {
-            properties.push(AnimProperty::TranslateX(0.0));
-            properties.push(AnimProperty::TranslateY(0.0));
-        } else if name_raw.starts_with("slide-out-") {
-            let dir = name_raw.trim_start_matches("slide-out-");
-            let (x, y) = match dir {
-                "left" => (-width, 0.0),
-                "right" => (width, 0.0),
-                "up" => (0.0, -height),
-                "down" => (0.0, height),
-                _ => (0.0, 0.0),
-            };
-            properties.push(AnimProperty::TranslateX(x));
-            properties.push(AnimProperty::TranslateY(y));
-        }
-
-        if parts.len() > 1 {
-            if parts[1].ends_with("ms") {
-                if let Ok(ms) = parts[1].trim_end_matches("ms").parse::<u64>() {
-                    duration = Duration::from_millis(ms);
-                }
-            } else if parts[1].ends_with('s') {
-                if let Ok(s) = parts[1].trim_end_matches('s').parse::<f32>() {
-                    duration = Duration::from_secs_f32(s);
-                }
-            }
-        }
-        if parts.len() > 2 {
-            easing = match parts[2] {
-                "ease-in" => Easing::EaseIn,
-                "ease-out" => Easing::EaseOut,
-                "ease-in-out" => Easing::EaseInOut,
-                _ => Easing::Linear,
-            };
-        }
-    }
-
-    TargetGroup { properties, duration, easing, raw_sequence_fragment: input.to_string() }
+/// Moves a numeric property to each new value over its [`Transition`], on the
+/// frame clock of the widget. Without one, values are set right away.
+#[derive(Clone, Default)]
+pub struct NumericTransition {
+    transition: Rc<Cell<Option<Transition>>>,
+    tick_id: Rc<RefCell<Option<gtk4::TickCallbackId>>>,
 }
 
-// splits chains by semicolon ";"
-fn parse_chain_sequence(input: &str, width: f32, height: f32) -> VecDeque<TargetGroup> {
-    let mut chain = VecDeque::new();
-    if input.is_empty() {
-        return chain;
+impl NumericTransition {
+    pub fn set_transition(&self, transition: Option<Transition>) {
+        self.transition.set(transition);
     }
 
-    for stage in input.split(';') {
-        let trimmed = stage.trim();
-        if !trimmed.is_empty() {
-            chain.push_back(parse_stage_string(trimmed, width, height));
+    /// Move from `from` to `to`, passing every value on the way to `set`.
+    ///
+    /// A transition still running is cut short and the new one starts from `from`.
+    pub fn animate(
+        &self,
+        widget: &impl IsA<gtk4::Widget>,
+        from: f64,
+        to: f64,
+        set: impl Fn(f64) + 'static,
+    ) {
+        if let Some(tick_id) = self.tick_id.take() {
+            tick_id.remove();
         }
+
+        // Unmapped widgets get no frames, which also makes the first value jump
+        let transition = self.transition.get().filter(|t| !t.duration.is_zero());
+        let Some(transition) = transition.filter(|_| widget.is_mapped() && from != to) else {
+            set(to);
+            return;
+        };
+
+        let start_time = OnceCell::new();
+        let tick_id = self.tick_id.clone();
+        let id = widget.add_tick_callback(move |_, frame_clock| {
+            let now = frame_clock.frame_time();
+            let start = *start_time.get_or_init(|| now);
+
+            let elapsed = Duration::from_micros((now - start).max(0) as u64);
+            let norm = (elapsed.as_secs_f32() / transition.duration.as_secs_f32()).min(1.0);
+            set(from + (to - from) * transition.easing.apply(norm) as f64);
+
+            if norm >= 1.0 {
+                // returning Break removes the callback, it must not be removed again
+                tick_id.take();
+                return glib::ControlFlow::Break;
+            }
+            glib::ControlFlow::Continue
+        });
+        self.tick_id.replace(Some(id));
     }
-    chain
 }
 
 mod imp {
     use super::*;
 
-    #[derive(glib::Properties)]
+    #[derive(glib::Properties, Default)]
     #[properties(wrapper_type = super::AnimationWidget)]
     pub struct AnimationWidget {
         #[property(get, set)]
@@ -192,76 +188,30 @@ mod imp {
 
         pub child: RefCell<Option<gtk4::Widget>>,
 
-        pub cur_scale: Cell<f32>,
-        pub cur_rotate: Cell<f32>,
-        pub cur_opacity: Cell<f32>,
-        pub cur_tx: Cell<f32>,
-        pub cur_ty: Cell<f32>,
+        pub sequences: RefCell<HashMap<String, String>>,
+        pub selected: RefCell<String>,
 
-        pub start_scale: Cell<f32>,
-        pub start_rotate: Cell<f32>,
-        pub start_opacity: Cell<f32>,
-        pub start_tx: Cell<f32>,
-        pub start_ty: Cell<f32>,
+        pub pose: Cell<Pose>,
+        /// Where the current stage started from.
+        pub stage_from: Cell<Pose>,
 
-        pub tar_scale: Cell<f32>,
-        pub tar_rotate: Cell<f32>,
-        pub tar_opacity: Cell<f32>,
-        pub tar_tx: Cell<f32>,
-        pub tar_ty: Cell<f32>,
+        pub sequence: RefCell<Option<Sequence>>,
+        pub stage: Cell<usize>,
+        /// How often the sequence was played through.
+        pub plays: Cell<u32>,
+        /// Set on the first frame of a stage, so stages started while unmapped are not skipped.
+        pub stage_start: Cell<Option<Instant>>,
+        /// Notified when the sequence ends, dropped when another one replaces it.
+        pub finished: RefCell<Option<futures::channel::oneshot::Sender<()>>>,
 
-        pub anim_start_time: Cell<Option<Instant>>,
-        pub anim_duration: Cell<Duration>,
-        pub easing: Cell<Easing>,
-
-        // que handling chains
-        pub queue: RefCell<VecDeque<TargetGroup>>,
+        pub allocated: Cell<bool>,
+        /// Sequence played once the widget is allocated, as slides need its size.
+        pub pending: RefCell<Option<String>>,
 
         pub hover_controller: RefCell<Option<gtk4::EventControllerMotion>>,
         pub click_controller: RefCell<Option<gtk4::GestureClick>>,
     }
 
-    impl Default for AnimationWidget {
-        fn default() -> Self {
-            Self {
-                open: RefCell::new(String::new()),
-                close: RefCell::new(String::new()),
-                hover: RefCell::new(String::new()),
-                hoverlost: RefCell::new(String::new()),
-                click: RefCell::new(String::new()),
-                release: RefCell::new(String::new()),
-                child: RefCell::new(None),
-
-                cur_scale: Cell::new(1.0),
-                cur_rotate: Cell::new(0.0),
-                cur_opacity: Cell::new(1.0),
-                cur_tx: Cell::new(0.0),
-                cur_ty: Cell::new(0.0),
-
-                start_scale: Cell::new(1.0),
-                start_rotate: Cell::new(0.0),
-                start_opacity: Cell::new(1.0),
-                start_tx: Cell::new(0.0),
-                start_ty: Cell::new(0.0),
-
-                tar_scale: Cell::new(1.0),
-                tar_rotate: Cell::new(0.0),
-                tar_opacity: Cell::new(1.0),
-                tar_tx: Cell::new(0.0),
-                tar_ty: Cell::new(0.0),
-
-                anim_start_time: Cell::new(None),
-                anim_duration: Cell::new(Duration::from_millis(300)),
-                easing: Cell::new(Easing::Linear),
-
-                queue: RefCell::new(VecDeque::new()),
-
-                hover_controller: RefCell::new(None),
-                click_controller: RefCell::new(None),
-            }
-        }
-    }
-
     #[glib::object_subclass]
     impl ObjectSubclass for AnimationWidget {
         const NAME: &'static str = "AnimationWidget";
@@ -270,67 +220,92 @@ mod imp {
     }
 
     impl AnimationWidget {
+        /// Play `sequence`, either the name of one in `sequences` or written out.
         pub fn transition_to_sequence(&self, sequence: &str) {
+            if !self.allocated.get() {
+                self.pending.replace(Some(sequence.to_string()));
+                return;
+            }
+
+            let sequence = self.parse_sequence(sequence);
+            if !sequence.stages.is_empty() {
+                self.play(sequence);
+            }
+        }
+
+        pub fn parse_sequence(&self, sequence: &str) -> Sequence {
+            let named = self.sequences.borrow().get(sequence).cloned();
+            let sequence = named.as_deref().unwrap_or(sequence);
+
             let width = self.obj().width() as f32;
             let height = self.obj().height() as f32;
+            Sequence::parse(sequence, width, height)
+        }
 
-            let chain = parse_chain_sequence(sequence, width, height);
-            if chain.is_empty() {
-                return;
-            }
+        pub fn play(&self, sequence: Sequence) {
+            self.finished.take();
+            *self.sequence.borrow_mut() = Some(sequence);
+            self.stage.set(0);
+            self.plays.set(0);
+            self.start_stage();
+        }
 
-            *self.queue.borrow_mut() = chain;
-            self.pop_next_stage();
+        fn start_stage(&self) {
+            let from = self.pose.get();
+            self.stage_from.set(from);
+            self.stage_start.set(None);
+
+            // Keyframes at 0% apply before the first frame of the stage is drawn
+            let sequence = self.sequence.borrow();
+            if let Some(stage) = sequence.as_ref().and_then(|s| s.stages.get(self.stage.get())) {
+                self.pose.set(stage.sample(from, Duration::ZERO));
+            }
+            self.obj().queue_draw();
         }
 
-        pub fn pop_next_stage(&self) {
-            let mut queue = self.queue.borrow_mut();
-            if let Some(target_group) = queue.pop_front() {
-                let width = self.obj().width() as f32;
-                let height = self.obj().height() as f32;
-
-                self.start_scale.set(self.cur_scale.get());
-                self.start_rotate.set(self.cur_rotate.get());
-                self.start_opacity.set(self.cur_opacity.get());
-                self.start_tx.set(self.cur_tx.get());
-                self.start_ty.set(self.cur_ty.get());
-
-                let seq_frag = &target_group.raw_sequence_fragment;
-                if seq_frag.contains("slide-in-") {
-                    for prop in &target_group.properties {
-                        if let AnimProperty::TranslateX(_) = prop {
-                            self.start_tx.set(if seq_frag.contains("left") {
-                                -width
-                            } else {
-                                width
-                            });
-                        }
-                        if let AnimProperty::TranslateY(_) = prop {
-                            self.start_ty.set(if seq_frag.contains("up") {
-                                -height
-                            } else {
-                                height
-                            });
-                        }
-                    }
-                }
+        /// Move on to the next stage, going around again while the sequence repeats.
+        fn next_stage(&self) {
+            let mut sequence = self.sequence.borrow_mut();
+            let Some(seq) = sequence.as_ref() else {
+                return;
+            };
 
-                for prop in target_group.properties {
-                    match prop {
-                        AnimProperty::Scale(s) => self.tar_scale.set(s),
-                        AnimProperty::Rotate(r) => self.tar_rotate.set(r),
-                        AnimProperty::Opacity(o) => self.tar_opacity.set(o),
-                        AnimProperty::TranslateX(x) => self.tar_tx.set(x),
-                        AnimProperty::TranslateY(y) => self.tar_ty.set(y),
+            let next = self.stage.get() + 1;
+            if next < seq.stages.len() {
+                self.stage.set(next);
+            } else {
+                self.plays.set(self.plays.get() + 1);
+                if !seq.repeat.plays_again(self.plays.get()) {
+                    *sequence = None;
+                    if let Some(finished) = self.finished.take() {
+                        let _ = finished.send(());
                     }
+                    return;
                 }
+                self.stage.set(0);
+            }
+            drop(sequence);
+            self.start_stage();
+        }
 
-                self.anim_duration.set(target_group.duration);
-                self.easing.set(target_group.easing);
-                self.anim_start_time.set(Some(Instant::now()));
-                self.obj().queue_draw();
-            } else {
-                self.anim_start_time.set(None);
+        fn tick(&self) {
+            let sequence = self.sequence.borrow();
+            let Some(stage) = sequence.as_ref().and_then(|s| s.stages.get(self.stage.get())) else {
+                return;
+            };
+
+            let now = Instant::now();
+            let start = self.stage_start.get().unwrap_or(now);
+            self.stage_start.set(Some(start));
+
+            let elapsed = now.duration_since(start);
+            self.pose.set(stage.sample(self.stage_from.get(), elapsed));
+            self.obj().queue_draw();
+
+            let done = elapsed >= stage.duration();
+            drop(sequence);
+            if done {
+                self.next_stage();
             }
         }
     }
@@ -346,6 +321,12 @@ mod imp {
             self.derived_property(id, pspec)
         }
 
+        fn dispose(&self) {
+            if let Some(child) = self.child.take() {
+                child.unparent();
+            }
+        }
+
         fn constructed(&self) {
             self.parent_constructed();
             let obj = self.obj();
@@ -391,37 +372,7 @@ mod imp {
             *self.click_controller.borrow_mut() = Some(gesture);
 
             obj.add_tick_callback(|widget, _frame_clock| {
-                let imp = widget.imp();
-                if let Some(start_time) = imp.anim_start_time.get() {
-                    let elapsed = Instant::now().duration_since(start_time);
-                    let duration = imp.anim_duration.get();
-
-                    let norm = (elapsed.as_secs_f32() / duration.as_secs_f32()).min(1.0);
-                    let t = imp.easing.get().apply(norm);
-
-                    imp.cur_scale.set(
-                        imp.start_scale.get() + (imp.tar_scale.get() - imp.start_scale.get()) * t,
-                    );
-                    imp.cur_rotate.set(
-                        imp.start_rotate.get()
-                            + (imp.tar_rotate.get() - imp.start_rotate.get()) * t,
-                    );
-                    imp.cur_opacity.set(
-                        imp.start_opacity.get()
-                            + (imp.tar_opacity.get() - imp.start_opacity.get()) * t,
-                    );
-                    imp.cur_tx
-                        .set(imp.start_tx.get() + (imp.tar_tx.get() - imp.start_tx.get()) * t);
-                    imp.cur_ty
-                        .set(imp.start_ty.get() + (imp.tar_ty.get() - imp.start_ty.get()) * t);
-
-                    widget.queue_draw();
-
-                    if norm >= 1.0 {
-                        // process the next in chain
-                        imp.pop_next_stage();
-                    }
-                }
+                widget.imp().tick();
                 glib::ControlFlow::Continue
             });
         }
@@ -447,6 +398,11 @@ mod imp {
             if let Some(ref c) = *self.child.borrow() {
                 c.allocate(width, height, baseline, None);
             }
+
+            self.allocated.set(true);
+            if let Some(pending) = self.pending.take() {
+                self.transition_to_sequence(&pending);
+            }
         }
 
         fn snapshot(&self, snapshot: &gtk4::Snapshot) {
@@ -456,18 +412,17 @@ mod imp {
 
                 let mut transform = gsk::Transform::new();
 
-                transform = transform.translate(&graphene::Point::new(
-                    w / 2.0 + self.cur_tx.get(),
-                    h / 2.0 + self.cur_ty.get(),
-                ));
-                transform = transform.rotate(self.cur_rotate.get());
-                transform = transform.scale(self.cur_scale.get(), self.cur_scale.get());
+                let pose = self.pose.get();
+                transform = transform
+                    .translate(&graphene::Point::new(w / 2.0 + pose.tx, h / 2.0 + pose.ty));
+                transform = transform.rotate(pose.rotate);
+                transform = transform.scale(pose.scale, pose.scale);
                 transform = transform.translate(&graphene::Point::new(-w / 2.0, -h / 2.0));
 
                 snapshot.save();
                 snapshot.transform(Some(&transform));
 
-                let o = self.cur_opacity.get();
+                let o = pose.opacity;
                 if o < 1.0 {
                     snapshot.push_opacity(o as f64);
                     self.obj().snapshot_child(child, snapshot);
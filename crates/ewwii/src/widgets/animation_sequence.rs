@@ -0,0 +1,384 @@
+//! The sequence language of the `Animation` widget.
+//!
+//! A sequence is a chain of stages separated by `;`, played one after the
+//! other. Parts of a stage joined with `+` run in parallel, each with its own
+//! duration and easing, falling back to the last ones given in the stage:
+//!
+//! ```text
+//! scale(1.2) 150ms ease-out + fade(0.6); scale(1) 300ms spring(0.4); repeat(3)
+//! keyframes(0% fade(0) translate(0 -8), 60% fade(1), 100% translate(0 0)) 500ms ease-out
+//! ```
+//!
+//! A trailing `repeat(n)` plays the whole chain `n` times, `repeat(infinite)`
+//! until another sequence replaces it.
+
+pub use ewwii_shared_utils::easing::Easing;
+use ewwii_shared_utils::easing::parse_call;
+use std::time::Duration;
+
+const DEFAULT_DURATION: Duration = Duration::from_millis(300);
+
+/// What the `Animation` widget draws its child with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub scale: f32,
+    pub rotate: f32,
+    pub opacity: f32,
+    pub tx: f32,
+    pub ty: f32,
+}
+
+impl Default for Pose {
+    fn default() -> Self {
+        Pose { scale: 1.0, rotate: 0.0, opacity: 1.0, tx: 0.0, ty: 0.0 }
+    }
+}
+
+impl Pose {
+    fn get(&self, channel: Channel) -> f32 {
+        match channel {
+            Channel::Scale => self.scale,
+            Channel::Rotate => self.rotate,
+            Channel::Opacity => self.opacity,
+            Channel::TranslateX => self.tx,
+            Channel::TranslateY => self.ty,
+        }
+    }
+
+    fn set(&mut self, channel: Channel, value: f32) {
+        match channel {
+            Channel::Scale => self.scale = value,
+            Channel::Rotate => self.rotate = value,
+            Channel::Opacity => self.opacity = value,
+            Channel::TranslateX => self.tx = value,
+            Channel::TranslateY => self.ty = value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    Scale,
+    Rotate,
+    Opacity,
+    TranslateX,
+    TranslateY,
+}
+
+const CHANNELS: [Channel; 5] =
+    [Channel::Scale, Channel::Rotate, Channel::Opacity, Channel::TranslateX, Channel::TranslateY];
+
+/// One channel animated through its keyframes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    pub channel: Channel,
+    /// Offsets in `[0, 1]` and the value reached there, sorted by offset.
+    /// Without one at `0` the track starts from where the channel is.
+    pub frames: Vec<(f32, f32)>,
+    pub duration: Duration,
+    /// Applied to each segment between two keyframes.
+    pub easing: Easing,
+}
+
+impl Track {
+    fn sample(&self, from: f32, elapsed: Duration) -> f32 {
+        let norm = if self.duration.is_zero() {
+            1.0
+        } else {
+            (elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+        };
+
+        let mut prev = (0.0, from);
+        for &(offset, value) in &self.frames {
+            if norm <= offset {
+                let span = offset - prev.0;
+                let t = if span <= 0.0 { 1.0 } else { (norm - prev.0) / span };
+                return prev.1 + (value - prev.1) * self.easing.apply(t);
+            }
+            prev = (offset, value);
+        }
+        prev.1
+    }
+}
+
+/// Tracks played in parallel. The stage is over once the longest one is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stage {
+    pub tracks: Vec<Track>,
+}
+
+impl Stage {
+    pub fn duration(&self) -> Duration {
+        self.tracks.iter().map(|t| t.duration).max().unwrap_or_default()
+    }
+
+    /// The pose `elapsed` into the stage, when it started at `from`.
+    pub fn sample(&self, from: Pose, elapsed: Duration) -> Pose {
+        let mut pose = from;
+        for track in &self.tracks {
+            pose.set(track.channel, track.sample(from.get(track.channel), elapsed));
+        }
+        pose
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repeat {
+    Times(u32),
+    Infinite,
+}
+
+impl Repeat {
+    /// Whether the chain is played again after it was played `plays` times.
+    pub fn plays_again(&self, plays: u32) -> bool {
+        match *self {
+            Repeat::Times(n) => plays < n,
+            Repeat::Infinite => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sequence {
+    pub stages: Vec<Stage>,
+    pub repeat: Repeat,
+}
+
+impl Sequence {
+    /// Back to the untransformed child, used when a selected sequence is deselected.
+    pub fn rest() -> Self {
+        let tracks = CHANNELS
+            .iter()
+            .map(|&channel| Track {
+                channel,
+                frames: vec![(1.0, Pose::default().get(channel))],
+                duration: DEFAULT_DURATION,
+                easing: Easing::EaseOut,
+            })
+            .collect();
+        Sequence { stages: vec![Stage { tracks }], repeat: Repeat::Times(1) }
+    }
+
+    /// How long playing the sequence takes, `None` if it repeats forever.
+    pub fn duration(&self) -> Option<Duration> {
+        let once: Duration = self.stages.iter().map(Stage::duration).sum();
+        match self.repeat {
+            Repeat::Times(n) => Some(once * n),
+            Repeat::Infinite => None,
+        }
+    }
+
+    /// Parse a sequence. `width` and `height` are the distances the slides travel.
+    pub fn parse(input: &str, width: f32, height: f32) -> Self {
+        let mut stages = Vec::new();
+        let mut repeat = Repeat::Times(1);
+
+        for stage in split_top_level(input, |c| c == ';') {
+            if stage.starts_with("repeat") {
+                match parse_repeat(stage) {
+                    Some(r) => repeat = r,
+                    None => log::warn!("Invalid repeat '{}' in animation sequence", stage),
+                }
+                continue;
+            }
+            stages.push(parse_stage(stage, width, height));
+        }
+
+        Sequence { stages, repeat }
+    }
+}
+
+fn parse_repeat(raw: &str) -> Option<Repeat> {
+    let args = raw.strip_prefix("repeat")?.trim().strip_prefix('(')?.strip_suffix(')')?.trim();
+    if args == "infinite" {
+        return Some(Repeat::Infinite);
+    }
+    args.parse::<u32>().ok().map(|n| Repeat::Times(n.max(1)))
+}
+
+fn parse_duration(raw: &str) -> Option<Duration> {
+    if let Some(ms) = raw.strip_suffix("ms") {
+        ms.parse::<u64>().ok().map(Duration::from_millis)
+    } else if let Some(s) = raw.strip_suffix('s') {
+        s.parse::<f32>().ok().filter(|s| *s >= 0.0).map(Duration::from_secs_f32)
+    } else {
+        None
+    }
+}
+
+/// The duration and easing following a property, in either order.
+fn parse_timing(words: &[&str]) -> (Option<Duration>, Option<Easing>) {
+    let (mut duration, mut easing) = (None, None);
+    for word in words {
+        if let Some(d) = parse_duration(word) {
+            duration = Some(d);
+        } else if let Some(e) = Easing::parse(word) {
+            easing = Some(e);
+        } else {
+            log::warn!("Expected a duration or easing in animation sequence, got '{}'", word);
+        }
+    }
+    (duration, easing)
+}
+
+fn parse_stage(input: &str, width: f32, height: f32) -> Stage {
+    let parts: Vec<Vec<&str>> = split_top_level(input, |c| c == '+')
+        .into_iter()
+        .map(|part| split_top_level(part, char::is_whitespace))
+        .filter(|words| !words.is_empty())
+        .collect();
+
+    // Parts without a duration or easing share the last one of the stage
+    let timings: Vec<_> = parts.iter().map(|words| parse_timing(&words[1..])).collect();
+    let duration = timings.iter().rev().find_map(|(d, _)| *d).unwrap_or(DEFAULT_DURATION);
+    let easing = timings.iter().rev().find_map(|(_, e)| *e).unwrap_or_default();
+
+    let mut tracks = Vec::new();
+    for (words, (own_duration, own_easing)) in parts.iter().zip(timings) {
+        let duration = own_duration.unwrap_or(duration);
+        let easing = own_easing.unwrap_or(easing);
+        for (channel, frames) in parse_frames(words[0], width, height) {
+            tracks.push(Track { channel, frames, duration, easing });
+        }
+    }
+
+    Stage { tracks }
+}
+
+/// The keyframes of each channel `property` animates.
+fn parse_frames(property: &str, width: f32, height: f32) -> Vec<(Channel, Vec<(f32, f32)>)> {
+    if let Some(dir) = property.strip_prefix("slide-in-") {
+        let (x, y) = slide_offset(dir, width, height);
+        return vec![
+            (Channel::TranslateX, vec![(0.0, x), (1.0, 0.0)]),
+            (Channel::TranslateY, vec![(0.0, y), (1.0, 0.0)]),
+        ];
+    }
+
+    let Some(inner) = property.strip_prefix("keyframes(").and_then(|p| p.strip_suffix(')')) else {
+        return parse_property(property, width, height)
+            .into_iter()
+            .map(|(channel, value)| (channel, vec![(1.0, value)]))
+            .collect();
+    };
+
+    let mut tracks: Vec<(Channel, Vec<(f32, f32)>)> = Vec::new();
+    for frame in split_top_level(inner, |c| c == ',') {
+        let words = split_top_level(frame, char::is_whitespace);
+        let Some(offset) = words.first().and_then(|w| w.strip_suffix('%')?.parse::<f32>().ok())
+        else {
+            log::warn!("Keyframe '{}' does not start with a percentage", frame);
+            continue;
+        };
+        let offset = (offset / 100.0).clamp(0.0, 1.0);
+
+        for word in &words[1..] {
+            for (channel, value) in parse_property(word, width, height) {
+                match tracks.iter_mut().find(|(c, _)| *c == channel) {
+                    Some((_, frames)) => frames.push((offset, value)),
+                    None => tracks.push((channel, vec![(offset, value)])),
+                }
+            }
+        }
+    }
+
+    for (_, frames) in &mut tracks {
+        frames.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+    tracks
+}
+
+/// The channels a property like `scale(1.2)` sets, and their targets.
+fn parse_property(property: &str, width: f32, height: f32) -> Vec<(Channel, f32)> {
+    let (name, args) = parse_call(property).unwrap_or((property, Vec::new()));
+    let arg = |i: usize, default: f32| args.get(i).copied().unwrap_or(default);
+
+    match name {
+        "scale" => vec![(Channel::Scale, arg(0, 1.0))],
+        "rotate" => vec![(Channel::Rotate, arg(0, 0.0))],
+        "fade" => vec![(Channel::Opacity, arg(0, 1.0).clamp(0.0, 1.0))],
+        "translate" => vec![(Channel::TranslateX, arg(0, 0.0)), (Channel::TranslateY, arg(1, 0.0))],
+        "translate-x" => vec![(Channel::TranslateX, arg(0, 0.0))],
+        "translate-y" => vec![(Channel::TranslateY, arg(0, 0.0))],
+        _ => match name.strip_prefix("slide-out-") {
+            Some(dir) => {
+                let (x, y) = slide_offset(dir, width, height);
+                vec![(Channel::TranslateX, x), (Channel::TranslateY, y)]
+            }
+            None => {
+                log::warn!("Unknown property '{}' in animation sequence", property);
+                Vec::new()
+            }
+        },
+    }
+}
+
+fn slide_offset(dir: &str, width: f32, height: f32) -> (f32, f32) {
+    match dir {
+        "left" => (-width, 0.0),
+        "right" => (width, 0.0),
+        "up" => (0.0, -height),
+        "down" => (0.0, height),
+        _ => (0.0, 0.0),
+    }
+}
+
+/// Split on the chars matching `is_sep` outside of parentheses, dropping empty parts.
+pub(crate) fn split_top_level(input: &str, is_sep: impl Fn(char) -> bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            c if depth == 0 && is_sep(c) => {
+                parts.push(&input[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&input[start..]);
+    parts.into_iter().map(str::trim).filter(|p| !p.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sequence() {
+        let seq = Sequence::parse("scale(1.2) 100ms + fade(0.5) ease-in; repeat(3)", 10.0, 10.0);
+        assert_eq!(seq.repeat, Repeat::Times(3));
+        assert_eq!(seq.stages.len(), 1);
+        // parts share the duration of the stage unless they have their own
+        let tracks = &seq.stages[0].tracks;
+        assert_eq!(tracks[0].channel, Channel::Scale);
+        assert_eq!(tracks[1].channel, Channel::Opacity);
+        assert!(tracks.iter().all(|t| t.duration == Duration::from_millis(100)));
+        assert_eq!((tracks[0].easing, tracks[1].easing), (Easing::EaseIn, Easing::EaseIn));
+        assert_eq!(seq.duration(), Some(Duration::from_millis(300)));
+
+        let keyframes = "keyframes(100% fade(1), 0% fade(0) translate(0, -8))";
+        let input = format!("{keyframes} 1s cubic-bezier(0.4, 0, 0.2, 1); repeat(infinite)");
+        let seq = Sequence::parse(&input, 10.0, 10.0);
+        assert_eq!(seq.repeat, Repeat::Infinite);
+        let tracks = &seq.stages[0].tracks;
+        assert_eq!(tracks[0].frames, vec![(0.0, 0.0), (1.0, 1.0)]);
+        assert_eq!(tracks[1].channel, Channel::TranslateX);
+        assert_eq!(tracks[2].frames, vec![(0.0, -8.0)]);
+        assert_eq!(tracks[0].easing, Easing::CubicBezier(0.4, 0.0, 0.2, 1.0));
+    }
+
+    #[test]
+    fn test_sample_stage() {
+        let stage = parse_stage("keyframes(50% fade(0)) 1s + scale(2) 2s", 0.0, 0.0);
+        assert_eq!(stage.duration(), Duration::from_secs(2));
+
+        let pose = stage.sample(Pose::default(), Duration::from_millis(500));
+        assert_eq!((pose.opacity, pose.scale), (0.0, 1.25));
+        let pose = stage.sample(Pose::default(), Duration::from_secs(2));
+        assert_eq!((pose.opacity, pose.scale), (0.0, 2.0));
+    }
+}
@@ -4,7 +4,7 @@ use gtk4::gdk::prelude::Cast;
 use crate::config::WindowDefinition;
 use crate::widgets::widget_definitions::*;
 
-use ewwii_shared_utils::ast::WidgetNode;
+use ewwii_shared_utils::ast::{hash_props, WidgetNode};
 
 /// Widget input allows us to pass either a widgetnode or a window_def
 /// this is important to make build_gtk_widget standalone without having to
@@ -31,6 +31,16 @@ pub fn build_gtk_widget<'a>(
 fn build_gtk_widget_from_node(
     root_node: &WidgetNode,
     widget_reg: &mut WidgetRegistry,
+) -> Result<gtk4::Widget> {
+    // Property tasks started while building belong to this widget, so a
+    // reload can stop them without touching the rest of the window.
+    let owner = root_node.props().map(hash_props).unwrap_or_default();
+    crate::property_macro::with_task_owner(owner, || build_widget_of_node(root_node, widget_reg))
+}
+
+fn build_widget_of_node(
+    root_node: &WidgetNode,
+    widget_reg: &mut WidgetRegistry,
 ) -> Result<gtk4::Widget> {
     let gtk_widget = match root_node {
         WidgetNode::Box { props, children } => build_gtk_box(props, children, widget_reg)?.upcast(),
@@ -0,0 +1,119 @@
+use crate::updates::api::VarWatcherAPI;
+use crate::{apply_property, bind_property};
+use ewwii_shared_utils::prop::Property;
+use ewwii_shared_utils::prop_utils::{get_string_prop, PropValue};
+use gtk4::glib;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+/// Two-way link between an interactive widget and the global of its `bind` prop.
+///
+/// Values are remembered as they are sent either way, so a widget never writes
+/// back what it was just given and never reapplies what it just wrote.
+#[derive(Clone, Default)]
+pub struct GlobalBinding {
+    var_name: Rc<RefCell<Option<String>>>,
+    last: Rc<RefCell<Option<String>>>,
+    quiet: Rc<Cell<bool>>,
+}
+
+impl GlobalBinding {
+    /// Bind to the global in `value`, calling `set` with every value it takes
+    /// that did not come from this widget.
+    pub fn bind(&self, key: &str, value: &Property, set: impl Fn(&str) + 'static) {
+        let Some(var) = value.as_global_var() else {
+            log::error!("Expected property `{}` to be a global(\"name\")", key);
+            return;
+        };
+        if var.template.is_some() || var.mutation.is_some() {
+            log::error!("Property `{}` can only bind a plain global, not `{}`", key, var.name);
+            return;
+        }
+        *self.var_name.borrow_mut() = Some(var.name.clone());
+
+        let binding = self.clone();
+        bind_property!(&value, &key, get_string_prop, [binding], |value: String| {
+            binding.receive(value, &set)
+        });
+    }
+
+    /// Apply a value the global took, unless this widget sent it.
+    fn receive(&self, value: String, set: impl Fn(&str)) {
+        if self.last.borrow().as_ref() == Some(&value) {
+            return;
+        }
+        *self.last.borrow_mut() = Some(value.clone());
+        self.without_writing(|| set(&value));
+    }
+
+    /// Write a value the user picked into the global.
+    pub fn write(&self, value: String) {
+        if self.quiet.get() || self.last.borrow().as_ref() == Some(&value) {
+            return;
+        }
+        if let Some(var_name) = self.var_name.borrow().as_ref() {
+            *self.last.borrow_mut() = Some(value.clone());
+            VarWatcherAPI::update_with_broadcast(var_name, value);
+        }
+    }
+
+    /// Last value sent either way.
+    pub fn value(&self) -> Option<String> {
+        self.last.borrow().clone()
+    }
+
+    /// Run `f` without writing the changes it makes to the widget.
+    pub fn without_writing(&self, f: impl FnOnce()) {
+        let was_quiet = self.quiet.replace(true);
+        f();
+        self.quiet.set(was_quiet);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_protection() {
+        let var_name = "global_binding_test";
+        VarWatcherAPI::register(var_name, String::new());
+        let mut recv = VarWatcherAPI::subscribe(var_name).unwrap();
+
+        let binding = GlobalBinding::default();
+        *binding.var_name.borrow_mut() = Some(var_name.to_string());
+
+        // A value the user picked is written once.
+        binding.write("a".to_string());
+        assert_eq!(VarWatcherAPI::state_of(var_name), "a");
+        assert!(recv.has_changed().unwrap());
+        recv.mark_unchanged();
+        binding.write("a".to_string());
+        assert!(!recv.has_changed().unwrap());
+
+        // Its echo from the global is not applied back to the widget.
+        let applied = RefCell::new(Vec::new());
+        binding.receive("a".to_string(), |v| applied.borrow_mut().push(v.to_string()));
+        assert!(applied.borrow().is_empty());
+
+        // A value from elsewhere is applied, and the change handler it fires
+        // does not write it back.
+        binding.receive("b".to_string(), |v| {
+            applied.borrow_mut().push(v.to_string());
+            binding.write(v.to_string());
+        });
+        assert_eq!(*applied.borrow(), ["b"]);
+        assert_eq!(binding.value().as_deref(), Some("b"));
+        assert!(!recv.has_changed().unwrap());
+
+        // Nor does the user picking the value the widget already shows.
+        binding.write("b".to_string());
+        assert!(!recv.has_changed().unwrap());
+        assert_eq!(VarWatcherAPI::state_of(var_name), "a");
+
+        binding.write("c".to_string());
+        assert_eq!(VarWatcherAPI::state_of(var_name), "c");
+    }
+}
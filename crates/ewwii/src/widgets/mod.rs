@@ -1,9 +1,12 @@
 pub mod animation;
+pub mod animation_sequence;
 pub mod build_widget;
 pub mod circular_progressbar;
 pub mod ewwii_image;
 pub mod ewwii_label;
+pub mod global_binding;
 pub mod graph;
 pub mod transform;
+pub mod tree_diff;
 pub mod widget_definitions;
 pub mod widget_definitions_helper;
@@ -0,0 +1,191 @@
+//! Diffing the widget trees of a window across config reloads.
+//!
+//! Nodes are matched by position. Their `dyn_id` is derived from that
+//! position, so a matched pair always shares it. Anything the diff cannot
+//! express as a property update is rebuilt from the closest node that changed.
+
+use ewwii_shared_utils::ast::{hash_props, WidgetNode};
+use ewwii_shared_utils::prop::{Property, PropertyMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::mem::discriminant;
+
+/// A change needed to turn an old widget tree into the new one.
+#[derive(Debug)]
+pub enum Patch<'a> {
+    /// Same widget, but some of its props were added or changed.
+    Props { old: &'a PropertyMap, new: &'a PropertyMap },
+    /// The widget and everything below it has to be built again.
+    Replace { old: &'a WidgetNode, new: &'a WidgetNode },
+}
+
+/// Collect the patches turning `old` into `new`, parents before their children.
+pub fn diff_widget_trees<'a>(
+    old: &'a WidgetNode,
+    new: &'a WidgetNode,
+    patches: &mut Vec<Patch<'a>>,
+) {
+    let (Some(old_props), Some(new_props)) = (old.props(), new.props()) else {
+        patches.push(Patch::Replace { old, new });
+        return;
+    };
+
+    let old_children = children(old);
+    let new_children = children(new);
+
+    // A removed prop cannot be unset on a live widget, and children are only
+    // matched one to one.
+    let removed_prop = old_props.0.keys().any(|key| !new_props.0.contains_key(key));
+    if discriminant(old) != discriminant(new)
+        || removed_prop
+        || old_children.len() != new_children.len()
+    {
+        patches.push(Patch::Replace { old, new });
+        return;
+    }
+
+    if hash_props(old_props) != hash_props(new_props) {
+        patches.push(Patch::Props { old: old_props, new: new_props });
+    }
+
+    for (old_child, new_child) in old_children.iter().zip(new_children) {
+        diff_widget_trees(old_child, new_child, patches);
+    }
+}
+
+/// Whether `key` has to be applied again when patching from `old` to `new`.
+///
+/// Bound props always are, since patching stops every task of the widget.
+pub fn needs_update(old: &PropertyMap, new: &PropertyMap, key: &str) -> bool {
+    match (old.get(key), new.get(key)) {
+        (_, Some(value)) if is_bound(value) => true,
+        (Some(old_value), Some(new_value)) => prop_hash(old_value) != prop_hash(new_value),
+        (None, Some(_)) => true,
+        (_, None) => false,
+    }
+}
+
+/// Whether `value` is bound to a global, or holds one like a `classes` map does.
+fn is_bound(value: &Property) -> bool {
+    match value {
+        Property::GlobalVar(_) => true,
+        Property::Map(map) => map.0.values().any(is_bound),
+        Property::Array(items) => items.iter().any(is_bound),
+        _ => false,
+    }
+}
+
+fn prop_hash(value: &Property) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The registry ids of `node` and every widget below it.
+pub fn subtree_ids(node: &WidgetNode) -> Vec<u64> {
+    let mut ids = Vec::new();
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        if let Some(props) = node.props() {
+            ids.push(hash_props(props));
+        }
+        stack.extend(children(node));
+    }
+    ids
+}
+
+/// Child widgets of a container node, empty for everything else.
+pub fn children(node: &WidgetNode) -> &[WidgetNode] {
+    match node {
+        WidgetNode::Box { children, .. }
+        | WidgetNode::FlowBox { children, .. }
+        | WidgetNode::Expander { children, .. }
+        | WidgetNode::Revealer { children, .. }
+        | WidgetNode::Scroll { children, .. }
+        | WidgetNode::OverLay { children, .. }
+        | WidgetNode::Stack { children, .. }
+        | WidgetNode::AspectFrame { children, .. }
+        | WidgetNode::EventBox { children, .. }
+        | WidgetNode::ToolTip { children, .. }
+        | WidgetNode::Custom { children, .. }
+        | WidgetNode::Animation { children, .. } => children,
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ewwii_shared_utils::variables::GlobalVar;
+
+    fn label(text: &str) -> WidgetNode {
+        let mut props = PropertyMap::new();
+        props.insert("text", Property::String(text.to_string()));
+        WidgetNode::Label { props }
+    }
+
+    fn boxed(children: Vec<WidgetNode>) -> WidgetNode {
+        WidgetNode::Box { props: PropertyMap::new(), children }.setup_dyn_ids("bar")
+    }
+
+    #[test]
+    fn test_diff_widget_trees() {
+        let old = boxed(vec![label("a"), label("b")]);
+
+        let new = old.clone();
+        let mut patches = Vec::new();
+        diff_widget_trees(&old, &new, &mut patches);
+        assert!(patches.is_empty());
+
+        let new = boxed(vec![label("a"), label("c")]);
+        let mut patches = Vec::new();
+        diff_widget_trees(&old, &new, &mut patches);
+        assert!(matches!(patches.as_slice(), [Patch::Props { .. }]));
+
+        let new = boxed(vec![label("a"), label("b"), label("c")]);
+        let mut patches = Vec::new();
+        diff_widget_trees(&old, &new, &mut patches);
+        assert!(matches!(patches.as_slice(), [Patch::Replace { old: WidgetNode::Box { .. }, .. }]));
+
+        let new = boxed(vec![label("a"), boxed(vec![])]);
+        let mut patches = Vec::new();
+        diff_widget_trees(&old, &new, &mut patches);
+        assert!(matches!(
+            patches.as_slice(),
+            [Patch::Replace { old: WidgetNode::Label { .. }, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_needs_update() {
+        let mut old = PropertyMap::new();
+        old.insert("text", Property::String("a".to_string()));
+        old.insert("class", Property::String("x".to_string()));
+        let mut new = old.clone();
+        new.insert("text", Property::String("b".to_string()));
+        new.insert("visible", Property::Bool(false));
+
+        assert!(needs_update(&old, &new, "text"));
+        assert!(needs_update(&old, &new, "visible"));
+        assert!(!needs_update(&old, &new, "class"));
+
+        // a `classes` entry bound to a global loses its task on patching too
+        let mut classes = PropertyMap::new();
+        classes.insert(
+            "active",
+            Property::GlobalVar(Box::new(GlobalVar {
+                name: "x".to_string(),
+                initial: Property::None,
+                template: None,
+                mutation: None,
+            })),
+        );
+        old.insert("classes", Property::Map(classes));
+        assert!(needs_update(&old, &old, "classes"));
+    }
+
+    #[test]
+    fn test_subtree_ids() {
+        let tree = boxed(vec![label("a"), boxed(vec![label("b")])]);
+        assert_eq!(subtree_ids(&tree).len(), 4);
+    }
+}
@@ -5,6 +5,7 @@ use crate::{apply_property, apply_property_watch, bind_property};
 use anyhow::{anyhow, bail, Result};
 use ewwii_shared_utils::ast::{hash_props, WidgetNode};
 use ewwii_shared_utils::prop::{Property, PropertyMap};
+use ewwii_shared_utils::schema::parse_prop_update;
 use gtk4::gdk::DragAction;
 use gtk4::{self, prelude::*};
 use gtk4::{gdk, glib};
@@ -14,11 +15,12 @@ use gtk4::{
 };
 use smart_default::SmartDefault;
 
+use super::tree_diff;
 use super::widget_definitions_helper::*;
 use ewwii_shared_utils::prop_utils::*;
 use std::{
     cell::{Cell, RefCell},
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     // cmp::Ordering,
     rc::Rc,
     time::Duration,
@@ -26,10 +28,11 @@ use std::{
 
 // custom widgets
 // use crate::widgets::{circular_progressbar::CircProg, transform::Transform};
-use crate::widgets::animation::AnimationWidget;
+use crate::widgets::animation::{AnimationWidget, NumericTransition, Transition};
 use crate::widgets::circular_progressbar::CircProg;
 use crate::widgets::ewwii_image::EwwiiImage;
 use crate::widgets::ewwii_label::EwwiiLabel;
+use crate::widgets::global_binding::GlobalBinding;
 use crate::widgets::graph::{Graph, RenderType};
 
 thread_local! {
@@ -38,6 +41,8 @@ thread_local! {
 
 pub trait EwwiiWidget {
     fn widget(&self) -> &gtk4::Widget;
+    /// Node name of the widget in the config, used to look up its prop schema.
+    fn type_name(&self) -> &'static str;
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -112,6 +117,88 @@ impl WidgetRegistry {
         Ok(())
     }
 
+    /// Apply the props changed by a reload to the widget built from `old_props`.
+    ///
+    /// Returns false if no such widget is registered.
+    pub fn patch_widget_props(&mut self, old_props: &PropertyMap, new_props: &PropertyMap) -> bool {
+        let old_id = hash_props(old_props);
+        let Some(mut widget) = self.widgets.remove(&old_id) else {
+            return false;
+        };
+
+        // bound props are applied again below, under the new id
+        crate::property_macro::close_property_tasks_of(&HashSet::from([old_id]));
+
+        let new_id = hash_props(new_props);
+        crate::property_macro::with_task_owner(new_id, || {
+            for (key, value) in &new_props.0 {
+                if key != "dyn_id" && tree_diff::needs_update(old_props, new_props, key) {
+                    widget.update_prop(key, value);
+                }
+            }
+        });
+
+        self.widgets.insert(new_id, widget);
+        true
+    }
+
+    /// Build `new_node` and put it where the widget built from `old_node` is.
+    ///
+    /// Returns false, without changing anything, if the old widget is not
+    /// registered or sits in a container it cannot be swapped out of.
+    pub fn replace_widget(&mut self, old_node: &WidgetNode, new_node: &WidgetNode) -> Result<bool> {
+        let Some(old_widget) = old_node
+            .props()
+            .and_then(|props| self.widgets.get(&hash_props(props)))
+            .map(|widget| widget.widget().clone())
+        else {
+            return Ok(false);
+        };
+        let Some(parent) = old_widget.parent() else {
+            return Ok(false);
+        };
+        let single_child = parent.is::<gtk4::Window>()
+            || parent.is::<gtk4::Revealer>()
+            || parent.is::<gtk4::ScrolledWindow>()
+            || parent.is::<gtk4::Viewport>()
+            || parent.is::<gtk4::Expander>()
+            || parent.is::<gtk4::AspectFrame>()
+            || parent.is::<gtk4::FlowBoxChild>()
+            || parent.is::<AnimationWidget>();
+        if !single_child && !parent.is::<gtk4::Box>() && !parent.is::<gtk4::Overlay>() {
+            return Ok(false);
+        }
+
+        self.forget_subtree(old_node);
+        let new_widget = build_gtk_widget(&WidgetInput::BorrowedNode(new_node), self)?;
+
+        if let Some(container) = parent.downcast_ref::<gtk4::Box>() {
+            container.insert_child_after(&new_widget, old_widget.prev_sibling().as_ref());
+            container.remove(&old_widget);
+        } else if let Some(overlay) = parent.downcast_ref::<gtk4::Overlay>() {
+            if overlay.child().as_ref() == Some(&old_widget) {
+                overlay.set_child(Some(&new_widget));
+            } else {
+                overlay.add_overlay(&new_widget);
+                overlay.remove_overlay(&old_widget);
+            }
+        } else if let Some(animation) = parent.downcast_ref::<AnimationWidget>() {
+            animation.set_child(Some(&new_widget));
+        } else {
+            // every remaining parent takes exactly one child
+            parent.set_property("child", Some(&new_widget));
+        }
+
+        Ok(true)
+    }
+
+    /// Drop the widgets built from `node` and its children, and stop their property tasks.
+    pub fn forget_subtree(&mut self, node: &WidgetNode) {
+        let ids: HashSet<u64> = tree_diff::subtree_ids(node).into_iter().collect();
+        self.widgets.retain(|id, _| !ids.contains(id));
+        crate::property_macro::close_property_tasks_of(&ids);
+    }
+
     // pub fn remove_widget(&mut self, widget_id: u64) {
     //     log::trace!("Removing '{}'", widget_id);
     //     if let Some(widget) = self.widgets.remove(&widget_id) {
@@ -175,22 +262,19 @@ impl WidgetRegistry {
         &mut self,
         widget_name: &str,
         property_and_value: (String, String),
-    ) -> bool {
-        if let Some((&id, _)) = self
+    ) -> Result<()> {
+        let widget = self
             .widgets
-            .iter()
-            .find(|(_, widget)| widget.widget().widget_name().as_str() == widget_name)
-        {
-            if let Some(widget) = self.widgets.get_mut(&id) {
-                widget.update_prop(&property_and_value.0, &Property::String(property_and_value.1));
-                return true;
-            }
+            .values_mut()
+            .find(|widget| widget.widget().widget_name().as_str() == widget_name)
+            .ok_or_else(|| anyhow!("Widget with name '{}' not found", widget_name))?;
 
-            log::debug!("[prop_update] Failed to get widget from ID.");
-        }
+        let (key, raw) = property_and_value;
+        let value =
+            parse_prop_update(widget.type_name(), &key, &raw).map_err(|e| anyhow!("{}", e))?;
+        widget.update_prop(&key, &value);
 
-        log::debug!("[prop_update] Failed to find widget.");
-        false
+        Ok(())
     }
 
     pub fn update_class_of_widget_by_name(
@@ -267,6 +351,10 @@ impl EwwiiWidget for BoxWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Box"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -328,6 +416,10 @@ impl EwwiiWidget for OverlayWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "OverLay"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -375,6 +467,10 @@ impl EwwiiWidget for TooltipWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "ToolTip"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -432,6 +528,10 @@ impl EwwiiWidget for AnimationWrapperWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Animation"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -488,6 +588,30 @@ impl EwwiiWidget for AnimationWrapperWidget {
                     gtk_widget.trigger(&v);
                 });
             }
+            "sequences" => {
+                let Some(sequences) = value.as_map() else {
+                    log::error!("Expected property `sequences` to be a map");
+                    return;
+                };
+
+                let sequences = sequences
+                    .into_iter()
+                    .filter_map(|(name, sequence)| match sequence.as_str() {
+                        Some(sequence) => Some((name.clone(), sequence.to_string())),
+                        None => {
+                            log::error!("Animation sequence `{}` must be a string", name);
+                            None
+                        }
+                    })
+                    .collect::<HashMap<_, _>>();
+                self.gtk_widget.set_sequences(sequences);
+            }
+            "sequence" => {
+                let gtk_widget = self.gtk_widget.clone();
+                bind_property!(&value, &key, get_string_prop, [gtk_widget], |v: String| {
+                    gtk_widget.select_sequence(&v);
+                });
+            }
             _ => {
                 resolve_widget_attrs(&self.gtk_widget.clone().upcast::<gtk4::Widget>(), key, value)
             }
@@ -540,6 +664,10 @@ impl EwwiiWidget for EventBoxWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "EventBox"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -962,6 +1090,10 @@ impl EwwiiWidget for FlowBoxWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "FlowBox"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -1066,6 +1198,10 @@ impl EwwiiWidget for StackWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Stack"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -1126,6 +1262,7 @@ impl EwwiiWidget for StackWidget {
 #[derive(Default)]
 struct CircularProgressWidget {
     gtk_widget: CircProg,
+    value_transition: NumericTransition,
 }
 
 impl EwwiiWidget for CircularProgressWidget {
@@ -1133,6 +1270,10 @@ impl EwwiiWidget for CircularProgressWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "CircularProgress"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -1152,10 +1293,16 @@ impl EwwiiWidget for CircularProgressWidget {
         match key {
             "value" => {
                 let widget = self.gtk_widget.clone();
-                bind_property!(&value, &key, get_f64_prop, [widget], |v: f64| {
-                    widget.set_property("value", v.clamp(0.0, 100.0));
+                let transition = self.value_transition.clone();
+                bind_property!(&value, &key, get_f64_prop, [widget, transition], |v: f64| {
+                    let from = widget.property::<f64>("value");
+                    let target = widget.clone();
+                    transition.animate(&widget, from, v.clamp(0.0, 100.0), move |v| {
+                        target.set_property("value", v);
+                    });
                 });
             }
+            "transition" => set_value_transition(&self.value_transition, key, value),
             "start_at" => {
                 let widget = self.gtk_widget.clone();
                 bind_property!(&value, &key, get_f64_prop, [widget], |v: f64| {
@@ -1209,6 +1356,10 @@ impl EwwiiWidget for GraphWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Graph"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -1368,6 +1519,7 @@ impl EwwiiWidget for GraphWidget {
 #[derive(Default)]
 struct ProgressWidget {
     gtk_widget: gtk4::ProgressBar,
+    value_transition: NumericTransition,
 }
 
 impl EwwiiWidget for ProgressWidget {
@@ -1375,6 +1527,10 @@ impl EwwiiWidget for ProgressWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Progress"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -1408,10 +1564,16 @@ impl EwwiiWidget for ProgressWidget {
             }
             "value" => {
                 let gtk_widget = self.gtk_widget.clone();
-                bind_property!(&value, &key, get_f64_prop, [gtk_widget], |bar_value: f64| {
-                    gtk_widget.set_fraction(bar_value / 100f64);
+                let transition = self.value_transition.clone();
+                bind_property!(&value, &key, get_f64_prop, [gtk_widget, transition], |value: f64| {
+                    let from = gtk_widget.fraction() * 100f64;
+                    let target = gtk_widget.clone();
+                    transition.animate(&gtk_widget, from, value, move |v| {
+                        target.set_fraction(v / 100f64);
+                    });
                 });
             }
+            "transition" => set_value_transition(&self.value_transition, key, value),
             "text" => {
                 let gtk_widget = self.gtk_widget.clone();
                 bind_property!(&value, &key, get_string_prop, [gtk_widget], |bar_text: String| {
@@ -1441,6 +1603,10 @@ impl EwwiiWidget for ImageWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Image"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -1526,6 +1692,10 @@ impl EwwiiWidget for ButtonWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Button"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -1729,6 +1899,10 @@ impl EwwiiWidget for LabelWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Label"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -1851,6 +2025,7 @@ struct InputWidget {
     timeout: Rc<RefCell<Duration>>,
     onchange_cmd: Rc<RefCell<String>>,
     onaccept_cmd: Rc<RefCell<String>>,
+    binding: GlobalBinding,
 }
 
 impl EwwiiWidget for InputWidget {
@@ -1858,6 +2033,10 @@ impl EwwiiWidget for InputWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Input"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -1874,6 +2053,7 @@ impl EwwiiWidget for InputWidget {
         let timeout = self.timeout.clone();
         let onchange_cmd = self.onchange_cmd.clone();
         let onaccept_cmd = self.onaccept_cmd.clone();
+        let binding = self.binding.clone();
 
         self.gtk_widget.connect_changed(glib::clone!(
             #[strong]
@@ -1881,6 +2061,7 @@ impl EwwiiWidget for InputWidget {
             #[strong]
             onchange_cmd,
             move |widget| {
+                binding.write(widget.text().to_string());
                 run_command(
                     *timeout.borrow(),
                     &onchange_cmd.borrow(),
@@ -1909,11 +2090,15 @@ impl EwwiiWidget for InputWidget {
     fn update_prop(&mut self, key: &str, value: &Property) {
         match key {
             "value" => {
-                let gtk_widget = self.gtk_widget.clone();
+                let (gtk_widget, binding) = (self.gtk_widget.clone(), self.binding.clone());
                 bind_property!(&value, &key, get_string_prop, [gtk_widget], |value: String| {
-                    gtk_widget.set_text(&value);
+                    binding.without_writing(|| gtk_widget.set_text(&value));
                 });
             }
+            "bind" => {
+                let gtk_widget = self.gtk_widget.clone();
+                self.binding.bind(key, value, move |value| gtk_widget.set_text(value));
+            }
             "placeholder" => {
                 let gtk_widget = self.gtk_widget.clone();
                 bind_property!(&value, &key, get_string_prop, [gtk_widget], |value: String| {
@@ -1962,6 +2147,10 @@ impl EwwiiWidget for CalendarWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Calendar"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -2083,6 +2272,7 @@ struct ComboBoxTextWidget {
     gtk_widget: gtk4::ComboBoxText,
     timeout: Rc<RefCell<Duration>>,
     onchange_cmd: Rc<RefCell<String>>,
+    binding: GlobalBinding,
 }
 
 #[allow(deprecated)]
@@ -2091,6 +2281,10 @@ impl EwwiiWidget for ComboBoxTextWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "ComboBoxText"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -2106,12 +2300,16 @@ impl EwwiiWidget for ComboBoxTextWidget {
 
         let onchange_cmd = self.onchange_cmd.clone();
         let timeout = self.timeout.clone();
+        let binding = self.binding.clone();
         self.gtk_widget.connect_changed(glib::clone!(
             #[strong]
             onchange_cmd,
             #[strong]
             timeout,
             move |combo_box| {
+                if let Some(text) = combo_box.active_text() {
+                    binding.write(text.to_string());
+                }
                 run_command(
                     *timeout.borrow(),
                     &onchange_cmd.borrow(),
@@ -2134,11 +2332,16 @@ impl EwwiiWidget for ComboBoxTextWidget {
                     let apply_items = {
                         let gtk_widget = gtk_widget.clone();
                         let current_items = current_items.clone();
+                        let binding = self.binding.clone();
                         Rc::new(move || {
-                            gtk_widget.remove_all();
-                            for item in current_items.borrow().iter() {
-                                gtk_widget.append_text(item);
-                            }
+                            binding.without_writing(|| {
+                                gtk_widget.remove_all();
+                                for item in current_items.borrow().iter() {
+                                    gtk_widget.append(Some(item.as_str()), item);
+                                }
+                                // the bound item is selected again once it exists
+                                gtk_widget.set_active_id(binding.value().as_deref());
+                            });
                         })
                     };
 
@@ -2152,6 +2355,12 @@ impl EwwiiWidget for ComboBoxTextWidget {
                     }
                 }
             }
+            "bind" => {
+                let gtk_widget = self.gtk_widget.clone();
+                self.binding.bind(key, value, move |value| {
+                    gtk_widget.set_active_id(Some(value));
+                });
+            }
             "timeout" => {
                 let new_timeout =
                     get_duration_prop(value, key).unwrap_or(Duration::from_millis(200));
@@ -2180,6 +2389,10 @@ impl EwwiiWidget for ExpanderWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Expander"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -2237,6 +2450,10 @@ impl EwwiiWidget for RevealerWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Revealer"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -2306,6 +2523,7 @@ struct CheckboxWidget {
     timeout: Rc<RefCell<Duration>>,
     onchecked_cmd: Rc<RefCell<String>>,
     onunchecked_cmd: Rc<RefCell<String>>,
+    binding: GlobalBinding,
 }
 
 impl EwwiiWidget for CheckboxWidget {
@@ -2313,6 +2531,10 @@ impl EwwiiWidget for CheckboxWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Checkbox"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -2329,6 +2551,7 @@ impl EwwiiWidget for CheckboxWidget {
         let timeout = self.timeout.clone();
         let onchecked_cmd = self.onchecked_cmd.clone();
         let onunchecked_cmd = self.onunchecked_cmd.clone();
+        let binding = self.binding.clone();
 
         self.gtk_widget.connect_toggled(glib::clone!(
             #[strong]
@@ -2338,6 +2561,7 @@ impl EwwiiWidget for CheckboxWidget {
             #[strong]
             timeout,
             move |widget| {
+                binding.write(widget.is_active().to_string());
                 let oncheck = &onchecked_cmd.borrow();
                 let onuncheck = &onunchecked_cmd.borrow();
 
@@ -2355,9 +2579,17 @@ impl EwwiiWidget for CheckboxWidget {
     fn update_prop(&mut self, key: &str, value: &Property) {
         match key {
             "checked" => {
-                let gtk_widget = self.gtk_widget.clone();
+                let (gtk_widget, binding) = (self.gtk_widget.clone(), self.binding.clone());
                 bind_property!(&value, &key, get_bool_prop, [gtk_widget], |checked: bool| {
-                    gtk_widget.set_active(checked);
+                    binding.without_writing(|| gtk_widget.set_active(checked));
+                });
+            }
+            "bind" => {
+                let gtk_widget = self.gtk_widget.clone();
+                self.binding.bind(key, value, move |value| {
+                    if let Ok(checked) = value.parse() {
+                        gtk_widget.set_active(checked);
+                    }
                 });
             }
             "timeout" => {
@@ -2399,6 +2631,10 @@ impl EwwiiWidget for ColorButtonWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "ColorButton"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -2459,6 +2695,7 @@ struct ColorChooserEwwiiWidget {
     gtk_widget: gtk4::ColorChooserWidget,
     timeout: Rc<RefCell<Duration>>,
     onchange_cmd: Rc<RefCell<String>>,
+    binding: GlobalBinding,
 }
 
 #[allow(deprecated)]
@@ -2467,6 +2704,10 @@ impl EwwiiWidget for ColorChooserEwwiiWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "ColorChooser"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -2490,6 +2731,11 @@ impl EwwiiWidget for ColorChooserEwwiiWidget {
             }
         ));
 
+        let binding = self.binding.clone();
+        self.gtk_widget.connect_rgba_notify(move |widget| {
+            binding.write(widget.rgba().to_string());
+        });
+
         Ok(self.gtk_widget.clone().upcast())
     }
 
@@ -2501,6 +2747,14 @@ impl EwwiiWidget for ColorChooserEwwiiWidget {
                     gtk_widget.set_use_alpha(use_alpha);
                 });
             }
+            "bind" => {
+                let gtk_widget = self.gtk_widget.clone();
+                self.binding.bind(key, value, move |value| {
+                    if let Ok(color) = gdk::RGBA::parse(value) {
+                        gtk_widget.set_rgba(&color);
+                    }
+                });
+            }
             "timeout" => {
                 let new_timeout =
                     get_duration_prop(value, key).unwrap_or(Duration::from_millis(200));
@@ -2525,6 +2779,8 @@ struct RangeCtrlData {
     cmd_timeout: Duration,
     is_being_dragged: bool,
     last_set_value: Option<f64>,
+    value_transition: NumericTransition,
+    binding: GlobalBinding,
 }
 
 #[derive(Default)]
@@ -2538,6 +2794,10 @@ impl EwwiiWidget for ScaleWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Scale"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -2591,6 +2851,12 @@ impl EwwiiWidget for ScaleWidget {
         ));
 
         self.gtk_widget.add_controller(legacy_controller);
+
+        let binding = self.range_dat.borrow().binding.clone();
+        self.gtk_widget.connect_value_changed(move |widget| {
+            binding.write(widget.value().to_string());
+        });
+
         Ok(self.gtk_widget.clone().upcast())
     }
 
@@ -2670,6 +2936,10 @@ impl EwwiiWidget for ScrolledWindowWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "Scroll"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -2748,6 +3018,10 @@ impl EwwiiWidget for AspectFrameWidget {
         self.gtk_widget.upcast_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        "AspectFrame"
+    }
+
     fn build(
         &mut self,
         props: &PropertyMap,
@@ -3206,6 +3480,39 @@ pub(super) fn build_gtk_scrolledwindow(
 // static DEPRECATED_ATTRS: Lazy<HashSet<&str>> =
 //     Lazy::new(|| ["timeout", "onscroll", "onhover", "cursor"].iter().cloned().collect());
 
+/// Apply the `transition` of a numeric property, dropping it if it is invalid.
+fn set_value_transition(value_transition: &NumericTransition, key: &str, value: &Property) {
+    match Transition::from_prop(value, key) {
+        Ok(transition) => value_transition.set_transition(Some(transition)),
+        Err(e) => {
+            log::error!("Invalid transition: {}", e);
+            value_transition.set_transition(None);
+        }
+    }
+}
+
+/// Key of the classes set by the `class` prop in the data of a widget.
+const PROP_CLASSES_KEY: &str = "ewwii-prop-classes";
+
+/// Replace the classes set by the `class` prop with the ones in `class_str`.
+///
+/// Only classes added here are ever removed, so the classes of GTK, of `classes`
+/// and of the window stay, even when a bound `class` arrives after them.
+fn set_prop_classes(gtk_widget: &gtk4::Widget, class_str: &str) {
+    // SAFETY: the key is only ever used with a `Vec<String>`
+    let previous = unsafe { gtk_widget.steal_data::<Vec<String>>(PROP_CLASSES_KEY) };
+    for class in previous.unwrap_or_default() {
+        gtk_widget.remove_css_class(&class);
+    }
+
+    let classes: Vec<String> = class_str.split_whitespace().map(String::from).collect();
+    for class in &classes {
+        gtk_widget.add_css_class(class);
+    }
+    // SAFETY: see above
+    unsafe { gtk_widget.set_data(PROP_CLASSES_KEY, classes) };
+}
+
 /// Code that applies css/scss to widgets.
 fn resolve_widget_attrs(gtk_widget: &gtk4::Widget, key: &str, value: &Property) {
     // // checking deprecated keys
@@ -3224,17 +3531,26 @@ fn resolve_widget_attrs(gtk_widget: &gtk4::Widget, key: &str, value: &Property)
         }
         "class" => {
             bind_property!(&value, "class", get_string_prop, [gtk_widget], |class_str: String| {
-                // remove all classes
-                for class in gtk_widget.css_classes() {
-                    gtk_widget.remove_css_class(&class);
-                }
-
-                // then apply the classes
-                for class in class_str.split_whitespace() {
-                    gtk_widget.add_css_class(class);
-                }
+                set_prop_classes(&gtk_widget, &class_str);
             });
         }
+        "classes" => {
+            let Some(classes) = value.as_map() else {
+                log::error!("Expected property `classes` to be a map");
+                return;
+            };
+
+            for (class, enabled) in classes {
+                let class = class.clone();
+                bind_property!(enabled, "classes", get_bool_prop, [gtk_widget], |v: bool| {
+                    if v {
+                        gtk_widget.add_css_class(&class);
+                    } else {
+                        gtk_widget.remove_css_class(&class);
+                    }
+                });
+            }
+        }
         "style" => {
             bind_property!(&value, "style", get_string_prop, [gtk_widget], |style_str: String| {
                 let css_provider = gtk4::CssProvider::new();
@@ -3344,10 +3660,27 @@ fn resolve_range_attrs(
             bind_property!(&value, &key, get_f64_prop, [gtk_widget, range_dat], |v: f64| {
                 if !range_dat.borrow().is_being_dragged {
                     range_dat.borrow_mut().last_set_value = Some(v);
-                    gtk_widget.set_value(v);
+                    let value_transition = range_dat.borrow().value_transition.clone();
+                    let (target, range_dat) = (gtk_widget.clone(), range_dat.clone());
+                    value_transition.animate(&gtk_widget, gtk_widget.value(), v, move |v| {
+                        // dragging takes over from a running transition
+                        let range_dat = range_dat.borrow();
+                        if !range_dat.is_being_dragged {
+                            range_dat.binding.without_writing(|| target.set_value(v));
+                        }
+                    });
+                }
+            });
+        }
+        "bind" => {
+            let gtk_widget = gtk_widget.clone();
+            range_dat.borrow().binding.bind(key, value, move |value| {
+                if let Ok(value) = value.parse() {
+                    gtk_widget.set_value(value);
                 }
             });
         }
+        "transition" => set_value_transition(&range_dat.borrow().value_transition, key, value),
         "timeout" => {
             let new_timeout = get_duration_prop(value, key).unwrap_or(Duration::from_millis(200));
             range_dat.borrow_mut().cmd_timeout = new_timeout;
@@ -3362,3 +3695,32 @@ fn resolve_range_attrs(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::property_macro::{template_shutdown, with_task_owner};
+
+    fn label(text: &str) -> WidgetNode {
+        let mut props = PropertyMap::new();
+        props.insert("text", Property::String(text.to_string()));
+        WidgetNode::Label { props }
+    }
+
+    #[test]
+    fn test_forget_subtree_stops_tasks() {
+        let window = WidgetNode::Box { props: PropertyMap::new(), children: vec![label("a")] };
+        let other = label("b");
+
+        let window_tasks: Vec<_> = tree_diff::subtree_ids(&window)
+            .into_iter()
+            .map(|id| with_task_owner(id, template_shutdown))
+            .collect();
+        let other_task = with_task_owner(hash_props(other.props().unwrap()), template_shutdown);
+
+        WidgetRegistry::new().forget_subtree(&window);
+        assert_eq!(window_tasks.len(), 2);
+        assert!(window_tasks.iter().all(|shutdown| *shutdown.borrow()));
+        assert!(!*other_task.borrow());
+    }
+}
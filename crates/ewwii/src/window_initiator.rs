@@ -1,5 +1,6 @@
 use crate::{
     config::WindowDefinition,
+    widgets::animation_sequence::split_top_level,
     window::{
         backend_window_options::BackendWindowOptions,
         coords::NumWithUnit,
@@ -24,6 +25,9 @@ pub struct WindowInitiator {
     pub geometry: Option<WindowGeometry>,
     pub monitor: Option<MonitorIdentifier>,
     pub waited_close: Option<Duration>,
+    /// Animation sequences the window opens and closes with, presets already expanded.
+    pub open_animation: Option<String>,
+    pub close_animation: Option<String>,
     pub name: String,
     pub resizable: bool,
     pub stacking: WindowStacking,
@@ -41,6 +45,15 @@ impl WindowInitiator {
             Some(val) => Some(get_duration_prop(val, "waited_close")?),
             None => None,
         };
+        let anchor = geometry.map(|g| g.anchor_point).unwrap_or(DEFAULT_ANCHOR);
+        let open_animation = properties
+            .get("open_animation")
+            .map(|val| parse_window_animation(val, "open_animation", anchor, true))
+            .transpose()?;
+        let close_animation = properties
+            .get("close_animation")
+            .map(|val| parse_window_animation(val, "close_animation", anchor, false))
+            .transpose()?;
         let monitor = args.monitor.clone().or_else(|| {
             properties
                 .get("monitor")?
@@ -53,6 +66,8 @@ impl WindowInitiator {
             geometry,
             monitor,
             waited_close,
+            open_animation,
+            close_animation,
             name: window_def.name.clone(),
             resizable: properties.get("resizable").and_then(|d| d.as_bool()).unwrap_or(true),
             stacking: match properties.get("stacking") {
@@ -67,6 +82,56 @@ impl WindowInitiator {
     // }
 }
 
+const DEFAULT_ANCHOR: AnchorPoint =
+    AnchorPoint { x: AnchorAlignment::Center, y: AnchorAlignment::Start };
+
+/// Expand the `slide`, `fade` and `scale` presets of an open or close animation
+/// into an `Animation` sequence. Anything else is taken as a sequence already.
+///
+/// `slide` moves from and to the edge the window is anchored to, and the
+/// presets keep the duration and easing written after them. A window anchored
+/// to the center on both axes has no such edge and slides from and to below,
+/// like `slide-in-down` and `slide-out-down`.
+fn parse_window_animation(
+    val: &Property,
+    key: &str,
+    anchor: AnchorPoint,
+    opening: bool,
+) -> Result<String> {
+    let raw = val.as_str().ok_or_else(|| anyhow!("Expected property `{}` to be a string", key))?;
+
+    let edge = match (anchor.y, anchor.x) {
+        (AnchorAlignment::Start, _) => "up",
+        (AnchorAlignment::End, _) => "down",
+        (_, AnchorAlignment::Start) => "left",
+        (_, AnchorAlignment::End) => "right",
+        _ => "down",
+    };
+
+    let expand = |part: &str| {
+        let words = split_top_level(part, char::is_whitespace);
+        let sequence = match (words[0], opening) {
+            ("slide", true) => format!("slide-in-{}", edge),
+            ("slide", false) => format!("slide-out-{}", edge),
+            ("fade", true) => "keyframes(0% fade(0), 100% fade(1))".to_string(),
+            ("fade", false) => "fade(0)".to_string(),
+            ("scale", true) => "keyframes(0% scale(0.9), 100% scale(1))".to_string(),
+            ("scale", false) => "scale(0.9)".to_string(),
+            _ => return part.to_string(),
+        };
+        std::iter::once(sequence.as_str())
+            .chain(words[1..].iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let stages = split_top_level(raw, |c| c == ';').into_iter().map(|stage| {
+        split_top_level(stage, |c| c == '+').into_iter().map(expand).collect::<Vec<_>>().join(" + ")
+    });
+
+    Ok(stages.collect::<Vec<_>>().join("; "))
+}
+
 fn parse_geometry(
     val: &Property,
     args: &WindowArguments,
@@ -80,8 +145,7 @@ fn parse_geometry(
     let mut geom = WindowGeometry {
         offset: get_coords_from_map(map, "x", "y")?,
         size: get_coords_from_map(map, "width", "height")?,
-        anchor_point: anchor
-            .unwrap_or(AnchorPoint { x: AnchorAlignment::Center, y: AnchorAlignment::Start }),
+        anchor_point: anchor.unwrap_or(DEFAULT_ANCHOR),
     };
 
     if override_geom {
@@ -133,3 +197,47 @@ fn anchor_point_from_str(s: &str) -> Result<AnchorPoint> {
         _ => Err(anyhow!("Expected 1 or 2 words like 'center' or 'top left'")),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(raw: &str, anchor: &str, opening: bool) -> String {
+        let anchor = anchor_point_from_str(anchor).unwrap();
+        parse_window_animation(&Property::String(raw.into()), "open_animation", anchor, opening)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_slide_edges() {
+        assert_eq!(expand("slide", "top center", true), "slide-in-up");
+        assert_eq!(expand("slide", "bottom right", true), "slide-in-down");
+        assert_eq!(expand("slide", "center left", true), "slide-in-left");
+        assert_eq!(expand("slide", "center right", false), "slide-out-right");
+        assert_eq!(expand("slide", "center", true), "slide-in-down");
+        assert_eq!(expand("slide", "center", false), "slide-out-down");
+    }
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(
+            expand("slide 250ms ease-out + fade", "top center", true),
+            "slide-in-up 250ms ease-out + keyframes(0% fade(0), 100% fade(1))"
+        );
+        assert_eq!(
+            expand("scale ease-in + fade 100ms", "top", false),
+            "scale(0.9) ease-in + fade(0) 100ms"
+        );
+        assert_eq!(expand("fade; repeat(2)", "top", false), "fade(0); repeat(2)");
+    }
+
+    #[test]
+    fn test_raw_sequences() {
+        let raw = "keyframes(0% fade(0) translate(0 -8), 100% fade(1)) 500ms + scale(1.2)";
+        assert_eq!(expand(raw, "top", true), raw);
+        assert_eq!(
+            expand("slide-in-left 200ms + translate(4 0); repeat(infinite)", "top", true),
+            "slide-in-left 200ms + translate(4 0); repeat(infinite)"
+        );
+    }
+}
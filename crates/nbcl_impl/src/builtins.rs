@@ -4,6 +4,10 @@ use std::collections::HashMap;
 use tokio::sync::mpsc::UnboundedSender;
 
 pub fn register_all_nodes(engine: &mut NbclEngine) {
+    // Widget props are left loose here as nbcl can't express enums or
+    // suggest fixes. They are checked against `schema` once the tree is
+    // translated instead (see translate.rs).
+
     // == Primitive nodes (nodes that does not take in children) ==
     macro_rules! register_primitive {
         ($name:expr) => {
@@ -74,9 +78,12 @@ pub fn register_all_nodes(engine: &mut NbclEngine) {
     poll_args.insert("initial".to_string(), Type::Str);
     poll_args.insert("interval".to_string(), Type::Str);
     poll_args.insert("skip_unchanged".to_string(), Type::Bool);
+    poll_args.insert("mock".to_string(), Type::Any);
 
     listen_args.insert("cmd".to_string(), Type::Str);
     listen_args.insert("initial".to_string(), Type::Str);
+    listen_args.insert("source".to_string(), Type::Str);
+    listen_args.insert("mock".to_string(), Type::Any);
 
     script_args.insert("every".to_string(), Type::Str);
     script_args.insert("on".to_string(), Type::Str);
@@ -103,6 +110,16 @@ pub fn register_all_nodes(engine: &mut NbclEngine) {
         child_count: Some((0, 0)),
     });
 
+    let mut css_var_args = HashMap::new();
+    css_var_args.insert("value".to_string(), Type::Any);
+
+    engine.register_node(NativeNodeSchema {
+        type_name: "CssVar".into(),
+        enforce_id: true,
+        validation: PropValidation::Strict(css_var_args),
+        child_count: Some((0, 0)),
+    });
+
     engine.register_node(NativeNodeSchema {
         type_name: "Window".into(),
         enforce_id: true,
@@ -1,6 +1,7 @@
 use codespan_reporting::diagnostic::{Diagnostic, Label};
 use codespan_reporting::files::SimpleFiles;
 use codespan_reporting::term::{self, termcolor::Buffer};
+use ewwii_shared_utils::schema::{PropError, PropErrors};
 use nbcl::context::EvalContext;
 use nbcl::error::{NbclError, Span};
 
@@ -17,34 +18,55 @@ pub fn handle_nbcl_err(
         }
     }
 
+    let (err_label, message, hint, span) = split_nbcl_err(err);
+    format_codespan_error(err_label, code, message, hint, span, file_id)
+}
+
+/// An error located in the source it was reported for, for consumers that
+/// render diagnostics themselves such as the language server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceDiagnostic {
+    pub label: &'static str,
+    pub message: String,
+    pub hint: Option<String>,
+    /// Byte range in the source, `None` if the error has no location.
+    pub range: Option<std::ops::Range<usize>>,
+}
+
+pub fn nbcl_err_to_diagnostic(err: NbclError, code: &str) -> SourceDiagnostic {
+    let (label, message, hint, span) = split_nbcl_err(err);
+    let range = if span.start != span.end {
+        Some(span.start..span.end)
+    } else if span.line > 0 {
+        Some(line_span(code, span.line))
+    } else {
+        None
+    };
+
+    SourceDiagnostic { label, message, hint, range }
+}
+
+pub fn prop_err_to_diagnostic(err: &PropError) -> SourceDiagnostic {
+    SourceDiagnostic {
+        label: "Property Error",
+        message: err.to_string(),
+        hint: err.hint.clone(),
+        range: err.range.clone(),
+    }
+}
+
+fn split_nbcl_err(err: NbclError) -> (&'static str, String, Option<String>, Span) {
     match err {
-        NbclError::Parse { message, hint, span } => format_codespan_error(
-            "Parse Error",
-            code,
-            message,
-            hint,
-            span.unwrap_or(Span::dummy()),
-            file_id,
-        ),
-        NbclError::Ast { message, hint, span } => format_codespan_error(
-            "Ast Error",
-            code,
-            message,
-            hint,
-            span.unwrap_or(Span::dummy()),
-            file_id,
-        ),
-        NbclError::IO { message, hint, path: _ } => {
-            format_codespan_error("IO Error", code, message, hint, Span::dummy(), file_id)
+        NbclError::Parse { message, hint, span } => {
+            ("Parse Error", message, hint, span.unwrap_or(Span::dummy()))
+        }
+        NbclError::Ast { message, hint, span } => {
+            ("Ast Error", message, hint, span.unwrap_or(Span::dummy()))
+        }
+        NbclError::IO { message, hint, path: _ } => ("IO Error", message, hint, Span::dummy()),
+        NbclError::Runtime { message, hint, span } => {
+            ("Runtime Error", message, hint, span.unwrap_or(Span::dummy()))
         }
-        NbclError::Runtime { message, hint, span } => format_codespan_error(
-            "Runtime Error",
-            code,
-            message,
-            hint,
-            span.unwrap_or(Span::dummy()),
-            file_id,
-        ),
     }
 }
 
@@ -89,6 +111,42 @@ fn format_codespan_error(
     String::from_utf8(buffer.into_inner()).unwrap()
 }
 
+/// Turns [`PropErrors`] into a formatted report, other errors are returned as is.
+pub fn handle_translate_err(
+    err: anyhow::Error,
+    code: &str,
+    file_id: Option<&str>,
+) -> anyhow::Error {
+    match err.downcast::<PropErrors>() {
+        Ok(PropErrors(errors)) => anyhow::anyhow!(format_prop_errors(&errors, code, file_id)),
+        Err(err) => err,
+    }
+}
+
+/// Return the property errors formatted as a String
+fn format_prop_errors(errors: &[PropError], code: &str, file_id: Option<&str>) -> String {
+    let mut files = SimpleFiles::new();
+    let file_id = files.add(file_id.unwrap_or("<nbcl>").to_string(), code);
+
+    let mut buffer = Buffer::ansi();
+    let config = term::Config::default();
+
+    for error in errors {
+        let message = format!("[Property Error] {}", error);
+        let notes = error.hint.iter().map(|h| format!("hint: {}", h)).collect();
+
+        let labels = (error.range.iter())
+            .map(|r| Label::primary(file_id, r.clone()).with_message(&error.message))
+            .collect();
+
+        let diagnostic =
+            Diagnostic::error().with_message(&message).with_labels(labels).with_notes(notes);
+        term::emit(&mut buffer, &config, &files, &diagnostic).unwrap();
+    }
+
+    String::from_utf8(buffer.into_inner()).unwrap()
+}
+
 fn line_span(code: &str, line_number: usize) -> std::ops::Range<usize> {
     let mut offset = 0;
     for (i, line) in code.lines().enumerate() {
@@ -99,3 +157,34 @@ fn line_span(code: &str, line_number: usize) -> std::ops::Range<usize> {
     }
     0..code.len()
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::NbclConfigParser;
+
+    const CODE: &str = r#"
+Box {
+    halgin = "start"
+    Label {
+        text = "{ halgin }"
+        halign = "ends"
+    }
+}
+"#;
+
+    #[test]
+    fn test_prop_error_ranges() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let parser = NbclConfigParser::new(tx);
+
+        let mut located: Vec<_> =
+            (parser.diagnose(CODE, true).into_iter()).map(|d| &CODE[d.range.unwrap()]).collect();
+        located.sort();
+        assert_eq!(located, ["\"ends\"", "\"start\""]);
+
+        // A missing property points at the widget
+        let diagnostics = parser.diagnose("GtkUI { file = \"a.ui\" }", true);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, Some(0..5));
+    }
+}
@@ -0,0 +1,210 @@
+use futures::StreamExt;
+use tokio::sync::{mpsc, watch};
+use zbus::{
+    message,
+    proxy::CacheProperties,
+    zvariant::{OwnedValue, Value as DBusValue},
+    Connection, MatchRule, MessageStream,
+};
+
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const SETTINGS_IFACE: &str = "org.freedesktop.portal.Settings";
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+
+#[zbus::proxy(
+    interface = "org.freedesktop.portal.Settings",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait Settings {
+    fn read_one(&self, namespace: &str, key: &str) -> zbus::Result<OwnedValue>;
+
+    /// Deprecated in favour of `ReadOne`, but the only one older portals have.
+    fn read(&self, namespace: &str, key: &str) -> zbus::Result<OwnedValue>;
+}
+
+/// Color scheme the user prefers, as reported by the desktop portal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorScheme {
+    #[default]
+    NoPreference,
+    Dark,
+    Light,
+}
+
+impl ColorScheme {
+    fn from_portal(value: u32) -> Self {
+        match value {
+            1 => ColorScheme::Dark,
+            2 => ColorScheme::Light,
+            _ => ColorScheme::NoPreference,
+        }
+    }
+}
+
+/// The `org.freedesktop.appearance` settings ewwii follows.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Appearance {
+    pub color_scheme: ColorScheme,
+    /// As `#rrggbb`, `None` if the user did not pick one
+    pub accent_color: Option<String>,
+}
+
+/// Reads one appearance setting, falling back to `Read` on portals without `ReadOne`.
+async fn read_setting(settings: &SettingsProxy<'_>, key: &str) -> zbus::Result<DBusValue<'static>> {
+    let value = match settings.read_one(APPEARANCE_NAMESPACE, key).await {
+        Ok(value) => value,
+        Err(zbus::Error::MethodError(name, ..))
+            if name.as_str() == "org.freedesktop.DBus.Error.UnknownMethod" =>
+        {
+            settings.read(APPEARANCE_NAMESPACE, key).await?
+        }
+        Err(e) => return Err(e),
+    };
+
+    Ok(value.into())
+}
+
+/// Accent colors are rgb doubles in `[0, 1]`, anything else means "unset".
+fn accent_to_hex((r, g, b): (f64, f64, f64)) -> Option<String> {
+    if ![r, g, b].iter().all(|c| (0.0..=1.0).contains(c)) {
+        return None;
+    }
+
+    let [r, g, b] = [r, g, b].map(|c| (c * 255.0).round() as u8);
+    Some(format!("#{r:02x}{g:02x}{b:02x}"))
+}
+
+async fn read_appearance(conn: &Connection) -> zbus::Result<Appearance> {
+    let settings =
+        SettingsProxy::builder(conn).cache_properties(CacheProperties::No).build().await?;
+
+    // `Read` wraps the value in one more variant, `downcast` strips it
+    let color_scheme = read_setting(&settings, "color-scheme").await?.downcast::<u32>()?;
+    // Portals older than the accent color setting report it as not found
+    let accent_color = match read_setting(&settings, "accent-color").await {
+        Ok(value) => value.downcast::<(f64, f64, f64)>().ok().and_then(accent_to_hex),
+        Err(_) => None,
+    };
+
+    Ok(Appearance { color_scheme: ColorScheme::from_portal(color_scheme), accent_color })
+}
+
+/// Streams the appearance settings of the desktop portal, starting with the
+/// current ones. `None` is sent while the portal cannot be read.
+pub async fn watch_appearance(
+    tx: mpsc::Sender<Option<Appearance>>,
+    shutdown_rx: watch::Receiver<bool>,
+) -> zbus::Result<()> {
+    let conn = Connection::session().await?;
+    watch_appearance_on(&conn, tx, shutdown_rx).await
+}
+
+async fn watch_appearance_on(
+    conn: &Connection,
+    tx: mpsc::Sender<Option<Appearance>>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> zbus::Result<()> {
+    let rule = MatchRule::builder()
+        .msg_type(message::Type::Signal)
+        .path(PORTAL_PATH)?
+        .interface(SETTINGS_IFACE)?
+        .member("SettingChanged")?
+        .build();
+    let mut changes = MessageStream::for_match_rule(rule, conn, None).await?;
+
+    let mut last = None;
+    loop {
+        let appearance = read_appearance(conn).await.ok();
+        if last.as_ref() != Some(&appearance) {
+            if tx.send(appearance.clone()).await.is_err() {
+                break;
+            }
+            last = Some(appearance);
+        }
+
+        // Only changes to the appearance namespace are interesting
+        loop {
+            tokio::select! {
+                Some(Ok(msg)) = changes.next() => {
+                    let namespace = msg.body().deserialize::<(String, String, OwnedValue)>();
+                    if namespace.is_ok_and(|(namespace, ..)| namespace == APPEARANCE_NAMESPACE) {
+                        break;
+                    }
+                }
+                res = shutdown_rx.changed() => {
+                    if res.is_err() || *shutdown_rx.borrow() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::apilib::mock_bus;
+    use std::sync::{Arc, Mutex};
+    use zbus::{fdo, interface, names::BusName};
+
+    /// The color scheme and accent color served by the mock portal.
+    type PortalSettings = Arc<Mutex<(u32, (f64, f64, f64))>>;
+
+    /// A portal that only implements the deprecated `Read`.
+    struct MockPortal {
+        settings: PortalSettings,
+    }
+
+    #[interface(name = "org.freedesktop.portal.Settings")]
+    impl MockPortal {
+        fn read(&self, namespace: &str, key: &str) -> fdo::Result<OwnedValue> {
+            let (color_scheme, accent_color) = *self.settings.lock().unwrap();
+            let value = match (namespace, key) {
+                (APPEARANCE_NAMESPACE, "color-scheme") => DBusValue::from(color_scheme),
+                (APPEARANCE_NAMESPACE, "accent-color") => DBusValue::from(accent_color),
+                _ => return Err(fdo::Error::Failed("Requested setting not found".into())),
+            };
+
+            Ok(OwnedValue::try_from(DBusValue::new(value)).unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_appearance() {
+        let settings: PortalSettings = Arc::new(Mutex::new((1, (1.0, 0.5, 0.0))));
+        let (server, client) =
+            mock_bus(PORTAL_PATH, MockPortal { settings: settings.clone() }).await;
+
+        let (tx, mut rx) = mpsc::channel(4);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let watcher =
+            tokio::spawn(async move { watch_appearance_on(&client, tx, shutdown_rx).await });
+
+        let appearance = rx.recv().await.unwrap().unwrap();
+        assert_eq!(appearance.color_scheme, ColorScheme::Dark);
+        assert_eq!(appearance.accent_color.as_deref(), Some("#ff8000"));
+
+        // an accent color out of range means "unset"
+        *settings.lock().unwrap() = (2, (-1.0, -1.0, -1.0));
+        server
+            .emit_signal(
+                None::<BusName<'_>>,
+                PORTAL_PATH,
+                SETTINGS_IFACE,
+                "SettingChanged",
+                &(APPEARANCE_NAMESPACE, "color-scheme", DBusValue::from(2u32)),
+            )
+            .await
+            .unwrap();
+
+        let appearance = rx.recv().await.unwrap().unwrap();
+        assert_eq!(appearance, Appearance { color_scheme: ColorScheme::Light, accent_color: None });
+
+        shutdown_tx.send(true).unwrap();
+        watcher.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,758 @@
+use crate::runtime_err;
+use nbcl::{error::Result, Value};
+use notify::{RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{mpsc, watch};
+
+/// The index built by the last lookup, reused until one of the
+/// applications directories changes.
+static INDEX: Mutex<Option<AppIndex>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct AppEntry {
+    /// Desktop file ID, e.g. `org.gnome.Nautilus.desktop`
+    id: String,
+    path: PathBuf,
+    name: String,
+    generic_name: String,
+    comment: String,
+    icon: String,
+    exec: String,
+    working_dir: String,
+    keywords: Vec<String>,
+    categories: Vec<String>,
+    terminal: bool,
+    /// Set for `NoDisplay`, `Hidden`, non-application entries and entries
+    /// not meant for the current desktop. Hidden entries still shadow
+    /// entries with the same ID in lower priority directories.
+    hidden: bool,
+}
+
+impl AppEntry {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "id": self.id,
+            "name": self.name,
+            "generic_name": self.generic_name,
+            "comment": self.comment,
+            "icon": self.icon,
+            "exec": self.exec,
+            "keywords": self.keywords,
+            "categories": self.categories,
+            "terminal": self.terminal,
+        })
+    }
+
+    fn to_value(&self) -> Value {
+        let list = |items: &[String]| Value::List(items.iter().cloned().map(Value::Str).collect());
+
+        Value::Map(vec![
+            ("id".into(), Value::Str(self.id.clone())),
+            ("name".into(), Value::Str(self.name.clone())),
+            ("generic_name".into(), Value::Str(self.generic_name.clone())),
+            ("comment".into(), Value::Str(self.comment.clone())),
+            ("icon".into(), Value::Str(self.icon.clone())),
+            ("exec".into(), Value::Str(self.exec.clone())),
+            ("keywords".into(), list(&self.keywords)),
+            ("categories".into(), list(&self.categories)),
+            ("terminal".into(), Value::Bool(self.terminal)),
+        ])
+    }
+}
+
+struct AppIndex {
+    /// Modification times of every scanned directory, a file being
+    /// added or removed in any of them invalidates the index.
+    stamps: Vec<(PathBuf, Option<SystemTime>)>,
+    apps: Vec<AppEntry>,
+}
+
+/// `applications` directories in order of precedence.
+fn application_dirs() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".local/share"));
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    std::iter::once(data_home)
+        .chain(data_dirs.split(':').filter(|d| !d.is_empty()).map(PathBuf::from))
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+/// Locale names to look up localized keys with, most specific first.
+///
+/// `de_DE.UTF-8@euro` gives `de_DE@euro`, `de_DE`, `de@euro` and `de`.
+fn locale_variants(locale: &str) -> Vec<String> {
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale, None),
+    };
+    // the encoding isn't part of the lookup
+    let locale = locale.split('.').next().unwrap_or_default();
+    if locale.is_empty() || locale == "C" || locale == "POSIX" {
+        return Vec::new();
+    }
+
+    let (lang, country) = match locale.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (locale, None),
+    };
+
+    let mut variants = Vec::new();
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        variants.push(format!("{lang}_{country}@{modifier}"));
+    }
+    if let Some(country) = country {
+        variants.push(format!("{lang}_{country}"));
+    }
+    if let Some(modifier) = modifier {
+        variants.push(format!("{lang}@{modifier}"));
+    }
+    variants.push(lang.to_string());
+
+    variants
+}
+
+fn current_locales() -> Vec<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .map(|locale| locale_variants(&locale))
+        .unwrap_or_default()
+}
+
+/// Handles the escape sequences of desktop entry string values.
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => result.push(' '),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+/// Splits a `;` separated list value, keeping escaped `\;` in the items.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(';') => current.push(';'),
+                Some(other) => {
+                    current.push('\\');
+                    current.push(other);
+                }
+                None => current.push('\\'),
+            },
+            ';' => items.push(unescape(&std::mem::take(&mut current))),
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        items.push(unescape(&current));
+    }
+
+    items.retain(|item| !item.trim().is_empty());
+    items
+}
+
+fn parse_bool(value: Option<&String>) -> bool {
+    value.is_some_and(|v| v.trim() == "true")
+}
+
+/// Parses the `[Desktop Entry]` group of a desktop file.
+fn parse_desktop_entry(
+    id: &str,
+    path: &Path,
+    contents: &str,
+    locales: &[String],
+    desktops: &[String],
+) -> AppEntry {
+    let mut keys = HashMap::new();
+    let mut in_entry_group = false;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            in_entry_group = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_entry_group {
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            // the first occurrence of a key wins
+            keys.entry(key.trim().to_string()).or_insert_with(|| value.trim().to_string());
+        }
+    }
+
+    let raw = |key: &str| -> Option<&String> {
+        locales
+            .iter()
+            .find_map(|locale| keys.get(&format!("{key}[{locale}]")))
+            .or_else(|| keys.get(key))
+    };
+    let string = |key: &str| raw(key).map(|v| unescape(v)).unwrap_or_default();
+    let list = |key: &str| raw(key).map(|v| split_list(v)).unwrap_or_default();
+    let desktop_list = |key: &str| keys.get(key).map(|v| split_list(v)).unwrap_or_default();
+
+    let only_show_in = desktop_list("OnlyShowIn");
+    let not_show_in = desktop_list("NotShowIn");
+    let shown_here = (only_show_in.is_empty() || only_show_in.iter().any(|d| desktops.contains(d)))
+        && !not_show_in.iter().any(|d| desktops.contains(d));
+
+    let entry = AppEntry {
+        id: id.to_string(),
+        path: path.to_path_buf(),
+        name: string("Name"),
+        generic_name: string("GenericName"),
+        comment: string("Comment"),
+        icon: string("Icon"),
+        exec: keys.get("Exec").map(|v| unescape(v)).unwrap_or_default(),
+        working_dir: keys.get("Path").map(|v| unescape(v)).unwrap_or_default(),
+        keywords: list("Keywords"),
+        categories: desktop_list("Categories"),
+        terminal: parse_bool(keys.get("Terminal")),
+        hidden: false,
+    };
+
+    let hidden = keys.get("Type").map(String::as_str) != Some("Application")
+        || parse_bool(keys.get("NoDisplay"))
+        || parse_bool(keys.get("Hidden"))
+        || entry.name.is_empty()
+        || entry.exec.is_empty()
+        || !shown_here;
+
+    AppEntry { hidden, ..entry }
+}
+
+/// Collects desktop files below `dir`, with IDs derived from their
+/// path relative to `base` (`kde/foo.desktop` becomes `kde-foo.desktop`).
+fn collect_desktop_files(
+    base: &Path,
+    dir: &Path,
+    files: &mut Vec<(String, PathBuf)>,
+    stamps: &mut Vec<(PathBuf, Option<SystemTime>)>,
+) {
+    stamps.push((dir.to_path_buf(), modified(dir)));
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    let mut entries: Vec<_> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_desktop_files(base, &path, files, stamps);
+        } else if path.extension().is_some_and(|ext| ext == "desktop") {
+            let Ok(relative) = path.strip_prefix(base) else {
+                continue;
+            };
+            let id = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("-");
+            files.push((id, path));
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn build_index(dirs: &[PathBuf]) -> AppIndex {
+    let locales = current_locales();
+    let desktops: Vec<String> = std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|d| !d.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let mut stamps = Vec::new();
+    let mut seen = HashSet::new();
+    let mut apps = Vec::new();
+
+    for dir in dirs {
+        let mut files = Vec::new();
+        collect_desktop_files(dir, dir, &mut files, &mut stamps);
+
+        for (id, path) in files {
+            // Directories earlier in the list take precedence
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+
+            match fs::read_to_string(&path) {
+                Ok(contents) => {
+                    apps.push(parse_desktop_entry(&id, &path, &contents, &locales, &desktops))
+                }
+                Err(e) => log::debug!("Failed to read {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    apps.retain(|app| !app.hidden);
+    apps.sort_by_cached_key(|app| app.name.to_lowercase());
+
+    AppIndex { stamps, apps }
+}
+
+/// Runs `f` with the applications index, rebuilding it if any of the
+/// applications directories changed since it was built.
+fn with_index<T>(f: impl FnOnce(&[AppEntry]) -> T) -> T {
+    let mut index = INDEX.lock().unwrap();
+
+    let is_stale = match &*index {
+        Some(index) => index.stamps.iter().any(|(dir, stamp)| modified(dir) != *stamp),
+        None => true,
+    };
+    if is_stale {
+        *index = Some(build_index(&application_dirs()));
+    }
+
+    f(&index.as_ref().unwrap().apps)
+}
+
+/// Scores how well `query` matches `text`, both expected in lowercase.
+fn match_score(query: &str, text: &str) -> u32 {
+    if text.is_empty() {
+        return 0;
+    }
+    if text == query {
+        return 100;
+    }
+    if text.starts_with(query) {
+        return 80;
+    }
+    if text.split(|c: char| !c.is_alphanumeric()).any(|word| word.starts_with(query)) {
+        return 60;
+    }
+    if text.contains(query) {
+        return 40;
+    }
+
+    // Fuzzy match, every query char has to appear in order.
+    // Matches spread over the text rank lower than tight ones.
+    let mut query_chars = query.chars().peekable();
+    let mut gaps = 0;
+    let mut started = false;
+    for c in text.chars() {
+        match query_chars.peek() {
+            Some(&q) if q == c => {
+                started = true;
+                query_chars.next();
+            }
+            Some(_) if started => gaps += 1,
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    if query_chars.peek().is_some() {
+        0
+    } else {
+        20u32.saturating_sub(gaps).max(1)
+    }
+}
+
+fn app_score(query: &str, app: &AppEntry) -> u32 {
+    let exec_name = app
+        .exec
+        .split_whitespace()
+        .next()
+        .and_then(|cmd| cmd.rsplit('/').next())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let fields = [
+        (4, app.name.to_lowercase()),
+        (2, app.generic_name.to_lowercase()),
+        (2, exec_name),
+        (1, app.comment.to_lowercase()),
+    ];
+
+    let field_score = fields.iter().map(|(weight, text)| weight * match_score(query, text));
+    let list_score = app
+        .keywords
+        .iter()
+        .map(|k| (2, k))
+        .chain(app.categories.iter().map(|c| (1, c)))
+        .map(|(weight, text)| weight * match_score(query, &text.to_lowercase()));
+
+    field_score.chain(list_score).max().unwrap_or(0)
+}
+
+/// Apps matching `query`, best match first.
+fn search_apps<'a>(apps: &'a [AppEntry], query: &str) -> Vec<&'a AppEntry> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return apps.iter().collect();
+    }
+
+    let mut results: Vec<_> = apps
+        .iter()
+        .map(|app| (app_score(&query, app), app))
+        .filter(|(score, _)| *score > 0)
+        .collect();
+    // the sort is stable so equally ranked apps stay sorted by name
+    results.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    results.into_iter().map(|(_, app)| app).collect()
+}
+
+/// Splits an `Exec` value into arguments following the quoting rules
+/// of the desktop entry spec.
+fn split_exec(exec: &str) -> Vec<(String, bool)> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut quoted = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                quoted = true;
+            }
+            '\\' if in_quotes => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if quoted || !current.is_empty() {
+                    args.push((std::mem::take(&mut current), quoted));
+                    quoted = false;
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if quoted || !current.is_empty() {
+        args.push((current, quoted));
+    }
+
+    args
+}
+
+/// Builds the command line to launch `app` with, expanding field codes.
+/// ewwii never passes files or URLs, so those codes expand to nothing.
+/// Apps that need a terminal are run with `terminal -e`.
+fn expand_exec(app: &AppEntry, terminal: &str) -> Vec<String> {
+    let mut command = Vec::new();
+
+    for (arg, quoted) in split_exec(&app.exec) {
+        // Field codes aren't allowed in quoted arguments
+        if quoted {
+            command.push(arg);
+            continue;
+        }
+
+        match arg.as_str() {
+            "%f" | "%F" | "%u" | "%U" => continue,
+            "%i" => {
+                if !app.icon.is_empty() {
+                    command.push("--icon".to_string());
+                    command.push(app.icon.clone());
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut expanded = String::new();
+        let mut chars = arg.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('%') => expanded.push('%'),
+                Some('c') => expanded.push_str(&app.name),
+                Some('k') => expanded.push_str(&app.path.to_string_lossy()),
+                // %f %u %i and the deprecated codes
+                _ => {}
+            }
+        }
+
+        if !expanded.is_empty() {
+            command.push(expanded);
+        }
+    }
+
+    if app.terminal {
+        command.splice(0..0, [terminal.to_string(), "-e".to_string()]);
+    }
+
+    command
+}
+
+fn launch_app(app: &AppEntry) -> std::io::Result<()> {
+    let terminal = std::env::var("TERMINAL").unwrap_or_else(|_| "xterm".to_string());
+    let command = expand_exec(app, &terminal);
+    let Some((program, args)) = command.split_first() else {
+        return Err(std::io::Error::other("Exec is empty"));
+    };
+
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Keep the app alive when ewwii is stopped
+        .process_group(0);
+    if !app.working_dir.is_empty() {
+        cmd.current_dir(&app.working_dir);
+    }
+
+    let mut child = cmd.spawn()?;
+    std::thread::spawn(move || child.wait());
+
+    Ok(())
+}
+
+/// Streams the application index as JSON. Used by `Listen { source = "apps" }`.
+pub async fn stream_state(
+    tx: mpsc::Sender<String>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> notify::Result<()> {
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(event) if !event.kind.is_access() => {
+                let _ = event_tx.send(());
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("Error while watching application directories: {}", e),
+        })?;
+
+    for dir in application_dirs().iter().filter(|dir| dir.is_dir()) {
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+    }
+
+    loop {
+        let apps = with_index(|apps| apps.iter().map(AppEntry::to_json).collect::<Vec<_>>());
+        if tx.send(serde_json::Value::Array(apps).to_string()).await.is_err() {
+            break;
+        }
+
+        tokio::select! {
+            Some(()) = event_rx.recv() => {
+                // Package managers touch many files at once, coalesce them into one update
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                while event_rx.try_recv().is_ok() {}
+                // Edits in place don't change the directory mtime, so force a rebuild
+                *INDEX.lock().unwrap() = None;
+            }
+            res = shutdown_rx.changed() => {
+                if res.is_err() || *shutdown_rx.borrow() {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn list(_args: Vec<Value>) -> Result<Value> {
+    Ok(with_index(|apps| Value::List(apps.iter().map(AppEntry::to_value).collect())))
+}
+
+// query: &str
+pub fn search(args: Vec<Value>) -> Result<Value> {
+    let Value::Str(query) = &args[0] else {
+        return Err(runtime_err!("Query must be a string"));
+    };
+
+    Ok(with_index(|apps| {
+        Value::List(search_apps(apps, query).into_iter().map(AppEntry::to_value).collect())
+    }))
+}
+
+// id: &str
+pub fn launch(args: Vec<Value>) -> Result<Value> {
+    let Value::Str(id) = &args[0] else {
+        return Err(runtime_err!("App id must be a string"));
+    };
+    // Accept the id with or without the .desktop suffix
+    let id = if id.ends_with(".desktop") { id.clone() } else { format!("{id}.desktop") };
+
+    let app = with_index(|apps| apps.iter().find(|app| app.id == id).cloned())
+        .ok_or_else(|| runtime_err!("No application with id '{}' found", id))?;
+
+    launch_app(&app).map_err(|e| runtime_err!("Failed to launch {}: {}", app.name, e))?;
+
+    Ok(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIREFOX: &str = r#"
+[Desktop Entry]
+Type=Application
+Name=Firefox
+Name[de]=Firefox Browser
+GenericName=Web Browser
+Comment=Browse the\sWorld Wide Web
+Icon=firefox
+Exec=firefox %u
+Keywords=Internet;WWW;Browser\;Web;
+Categories=Network;WebBrowser;
+
+[Desktop Action new-window]
+Name=New Window
+Exec=firefox --new-window
+"#;
+
+    fn entry(id: &str, contents: &str) -> AppEntry {
+        parse_desktop_entry(id, Path::new("/apps/test.desktop"), contents, &[], &[])
+    }
+
+    #[test]
+    fn test_locale_variants() {
+        assert_eq!(locale_variants("de_DE.UTF-8@euro"), ["de_DE@euro", "de_DE", "de@euro", "de"]);
+        assert_eq!(locale_variants("en_US.UTF-8"), ["en_US", "en"]);
+        assert!(locale_variants("C.UTF-8").is_empty());
+    }
+
+    #[test]
+    fn test_parse_desktop_entry() {
+        let app = entry("firefox.desktop", FIREFOX);
+        assert_eq!(app.name, "Firefox");
+        assert_eq!(app.exec, "firefox %u");
+        assert_eq!(app.comment, "Browse the World Wide Web");
+        assert_eq!(app.keywords, ["Internet", "WWW", "Browser;Web"]);
+        assert_eq!(app.categories, ["Network", "WebBrowser"]);
+        assert!(!app.hidden);
+
+        let locales = locale_variants("de_DE.UTF-8");
+        let localized = parse_desktop_entry("firefox.desktop", &app.path, FIREFOX, &locales, &[]);
+        assert_eq!(localized.name, "Firefox Browser");
+        assert_eq!(localized.generic_name, "Web Browser");
+
+        assert!(
+            entry("a.desktop", "[Desktop Entry]\nType=Application\nName=A\nExec=a\nNoDisplay=true")
+                .hidden
+        );
+        assert!(entry("b.desktop", "[Desktop Entry]\nType=Link\nName=B\nURL=https://b").hidden);
+
+        let gnome_only = "[Desktop Entry]\nType=Application\nName=C\nExec=c\nOnlyShowIn=GNOME;";
+        assert!(entry("c.desktop", gnome_only).hidden);
+        let on_gnome =
+            parse_desktop_entry("c.desktop", &app.path, gnome_only, &[], &["GNOME".into()]);
+        assert!(!on_gnome.hidden);
+    }
+
+    #[test]
+    fn test_expand_exec() {
+        let mut app = entry("firefox.desktop", FIREFOX);
+        assert_eq!(expand_exec(&app, "xterm"), ["firefox"]);
+
+        app.exec = r#"sh -c "echo \"100%\"" %i --name=%c %%"#.to_string();
+        assert_eq!(
+            expand_exec(&app, "xterm"),
+            ["sh", "-c", "echo \"100%\"", "--icon", "firefox", "--name=Firefox", "%"]
+        );
+
+        app.exec = "htop".to_string();
+        app.terminal = true;
+        assert_eq!(expand_exec(&app, "foot"), ["foot", "-e", "htop"]);
+    }
+
+    #[test]
+    fn test_search_ranking() {
+        let app = |name: &str, exec: &str, keywords: &[&str]| AppEntry {
+            name: name.to_string(),
+            exec: exec.to_string(),
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+            ..Default::default()
+        };
+        let apps = [
+            app("Files", "nautilus", &["folder", "explorer"]),
+            app("Firefox", "firefox", &["browser"]),
+            app("GNU Image Manipulation Program", "gimp", &[]),
+            app("Terminal", "foot", &["shell"]),
+        ];
+        let names = |query: &str| -> Vec<String> {
+            search_apps(&apps, query).into_iter().map(|a| a.name.clone()).collect()
+        };
+
+        assert_eq!(names("fi"), ["Files", "Firefox"]);
+        assert_eq!(names("fire"), ["Firefox"]);
+        assert_eq!(names("browser"), ["Firefox"]);
+        assert_eq!(names("gimp"), ["GNU Image Manipulation Program"]);
+        assert_eq!(names("image"), ["GNU Image Manipulation Program"]);
+        assert_eq!(names("trml"), ["Terminal"]);
+        assert_eq!(names("").len(), 4);
+        assert!(names("zzz").is_empty());
+    }
+
+    #[test]
+    fn test_build_index() {
+        let root = std::env::temp_dir().join(format!("ewwii-apps-test-{}", std::process::id()));
+        let high = root.join("high/applications");
+        let low = root.join("low/applications");
+        fs::create_dir_all(high.join("kde")).unwrap();
+        fs::create_dir_all(&low).unwrap();
+
+        let desktop =
+            |name: &str| format!("[Desktop Entry]\nType=Application\nName={name}\nExec=app");
+        fs::write(high.join("editor.desktop"), desktop("Editor (user)")).unwrap();
+        fs::write(high.join("kde/konsole.desktop"), desktop("Konsole")).unwrap();
+        fs::write(high.join("hidden.desktop"), "[Desktop Entry]\nHidden=true").unwrap();
+        fs::write(low.join("editor.desktop"), desktop("Editor (system)")).unwrap();
+        fs::write(low.join("hidden.desktop"), desktop("Hidden")).unwrap();
+        fs::write(low.join("notes.txt"), "not a desktop file").unwrap();
+
+        let index = build_index(&[high, low]);
+        fs::remove_dir_all(&root).unwrap();
+
+        let ids: Vec<_> = index.apps.iter().map(|a| (a.id.as_str(), a.name.as_str())).collect();
+        assert_eq!(ids, [("editor.desktop", "Editor (user)"), ("kde-konsole.desktop", "Konsole")]);
+    }
+}
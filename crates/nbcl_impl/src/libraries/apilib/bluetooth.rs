@@ -0,0 +1,601 @@
+use super::with_system_bus;
+use crate::runtime_err;
+use futures::{FutureExt, StreamExt};
+use nbcl::{error::Result, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use zbus::{
+    fdo::{ManagedObjects, ObjectManagerProxy},
+    message,
+    proxy::CacheProperties,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value as DBusValue},
+    Connection, MatchRule, MessageStream,
+};
+
+const BLUEZ_SERVICE: &str = "org.bluez";
+const BLUEZ_ADAPTER_IFACE: &str = "org.bluez.Adapter1";
+const BLUEZ_DEVICE_IFACE: &str = "org.bluez.Device1";
+const BLUEZ_BATTERY_IFACE: &str = "org.bluez.Battery1";
+
+/// System bus connection kept open for as long as ewwii runs. BlueZ stops a
+/// discovery as soon as the client that started it disconnects, so discovery
+/// is started and stopped on it, and the `bluetooth` data source shares it.
+static SHARED_BUS: Mutex<Option<Connection>> = Mutex::new(None);
+
+#[zbus::proxy(interface = "org.bluez.Adapter1", default_service = "org.bluez")]
+trait Adapter {
+    fn start_discovery(&self) -> zbus::Result<()>;
+    fn stop_discovery(&self) -> zbus::Result<()>;
+    fn remove_device(&self, device: &ObjectPath<'_>) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn set_powered(&self, value: bool) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(interface = "org.bluez.Device1", default_service = "org.bluez")]
+trait Device {
+    fn connect(&self) -> zbus::Result<()>;
+    fn disconnect(&self) -> zbus::Result<()>;
+    fn pair(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn set_trusted(&self, value: bool) -> zbus::Result<()>;
+}
+
+type Properties = HashMap<String, OwnedValue>;
+
+#[derive(Debug, Clone, Default)]
+struct AdapterInfo {
+    path: OwnedObjectPath,
+    /// Last path segment, e.g. `hci0`
+    name: String,
+    alias: String,
+    address: String,
+    powered: bool,
+    discovering: bool,
+}
+
+impl AdapterInfo {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "alias": self.alias,
+            "address": self.address,
+            "powered": self.powered,
+            "discovering": self.discovering,
+        })
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Map(vec![
+            ("name".into(), Value::Str(self.name.clone())),
+            ("alias".into(), Value::Str(self.alias.clone())),
+            ("address".into(), Value::Str(self.address.clone())),
+            ("powered".into(), Value::Bool(self.powered)),
+            ("discovering".into(), Value::Bool(self.discovering)),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct DeviceInfo {
+    path: OwnedObjectPath,
+    adapter: OwnedObjectPath,
+    /// Falls back to the address for devices without a name
+    name: String,
+    address: String,
+    icon: String,
+    paired: bool,
+    connected: bool,
+    /// Percentage, only known for devices exposing `org.bluez.Battery1`
+    battery: Option<u8>,
+}
+
+impl DeviceInfo {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "address": self.address,
+            "icon": self.icon,
+            "paired": self.paired,
+            "connected": self.connected,
+            "battery": self.battery,
+        })
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Map(vec![
+            ("name".into(), Value::Str(self.name.clone())),
+            ("address".into(), Value::Str(self.address.clone())),
+            ("icon".into(), Value::Str(self.icon.clone())),
+            ("paired".into(), Value::Bool(self.paired)),
+            ("connected".into(), Value::Bool(self.connected)),
+            ("battery".into(), self.battery.map_or(Value::Null, |b| Value::Int(b as i64))),
+        ])
+    }
+}
+
+fn get_str(props: &Properties, key: &str) -> String {
+    props.get(key).and_then(|v| <&str>::try_from(v).ok()).unwrap_or_default().to_string()
+}
+
+fn get_bool(props: &Properties, key: &str) -> bool {
+    props.get(key).and_then(|v| bool::try_from(v).ok()).unwrap_or(false)
+}
+
+/// Splits the BlueZ object tree into adapters and devices.
+fn parse_objects(objects: &ManagedObjects) -> (Vec<AdapterInfo>, Vec<DeviceInfo>) {
+    let mut adapters = Vec::new();
+    let mut devices = Vec::new();
+
+    for (path, interfaces) in objects {
+        let interface = |name: &str| {
+            interfaces.iter().find(|(iface, _)| iface.as_str() == name).map(|(_, props)| props)
+        };
+
+        if let Some(props) = interface(BLUEZ_ADAPTER_IFACE) {
+            adapters.push(AdapterInfo {
+                path: path.clone(),
+                name: path.as_str().rsplit('/').next().unwrap_or_default().to_string(),
+                alias: get_str(props, "Alias"),
+                address: get_str(props, "Address"),
+                powered: get_bool(props, "Powered"),
+                discovering: get_bool(props, "Discovering"),
+            });
+        }
+
+        if let Some(props) = interface(BLUEZ_DEVICE_IFACE) {
+            let address = get_str(props, "Address");
+            let name = match get_str(props, "Alias") {
+                alias if !alias.is_empty() => alias,
+                _ => address.clone(),
+            };
+
+            devices.push(DeviceInfo {
+                path: path.clone(),
+                adapter: match props.get("Adapter").map(|v| &**v) {
+                    Some(DBusValue::ObjectPath(p)) => p.clone().into(),
+                    _ => OwnedObjectPath::default(),
+                },
+                name,
+                address,
+                icon: get_str(props, "Icon"),
+                paired: get_bool(props, "Paired"),
+                connected: get_bool(props, "Connected"),
+                battery: interface(BLUEZ_BATTERY_IFACE)
+                    .and_then(|b| b.get("Percentage"))
+                    .and_then(|v| u8::try_from(v).ok()),
+            });
+        }
+    }
+
+    adapters.sort_by(|a, b| a.path.as_str().cmp(b.path.as_str()));
+    // Connected devices first, then paired ones, then whatever discovery found
+    devices.sort_by(|a, b| {
+        b.connected.cmp(&a.connected).then(b.paired.cmp(&a.paired)).then(a.name.cmp(&b.name))
+    });
+
+    (adapters, devices)
+}
+
+async fn read_objects(conn: &Connection) -> zbus::Result<(Vec<AdapterInfo>, Vec<DeviceInfo>)> {
+    let manager = ObjectManagerProxy::builder(conn)
+        .destination(BLUEZ_SERVICE)?
+        .path("/")?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    Ok(parse_objects(&manager.get_managed_objects().await?))
+}
+
+fn snapshot(adapters: &[AdapterInfo], devices: &[DeviceInfo]) -> serde_json::Value {
+    // The first adapter is the one the control functions act on
+    let default = adapters.first().cloned().unwrap_or_default();
+
+    serde_json::json!({
+        "available": !adapters.is_empty(),
+        "powered": default.powered,
+        "discovering": default.discovering,
+        "connected": devices.iter().filter(|d| d.connected).map(DeviceInfo::to_json).collect::<Vec<_>>(),
+        "adapters": adapters.iter().map(AdapterInfo::to_json).collect::<Vec<_>>(),
+        "devices": devices.iter().map(DeviceInfo::to_json).collect::<Vec<_>>(),
+    })
+}
+
+async fn default_adapter(conn: &Connection) -> zbus::Result<AdapterProxy<'static>> {
+    let (adapters, _) = read_objects(conn).await?;
+    let adapter = adapters
+        .into_iter()
+        .next()
+        .ok_or_else(|| zbus::Error::Failure("No bluetooth adapter found".to_string()))?;
+
+    AdapterProxy::builder(conn)
+        .path(adapter.path)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+}
+
+async fn find_device(conn: &Connection, address: &str) -> zbus::Result<DeviceInfo> {
+    let (_, devices) = read_objects(conn).await?;
+    devices
+        .into_iter()
+        .find(|d| d.address.eq_ignore_ascii_case(address))
+        .ok_or_else(|| zbus::Error::Failure(format!("Device {} not found", address)))
+}
+
+async fn device_proxy(conn: &Connection, address: &str) -> zbus::Result<DeviceProxy<'static>> {
+    let device = find_device(conn, address).await?;
+    DeviceProxy::builder(conn)
+        .path(device.path)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+}
+
+async fn pair_device(conn: &Connection, address: &str) -> zbus::Result<()> {
+    let device = device_proxy(conn, address).await?;
+    device.pair().await?;
+    // Without trust, BlueZ asks for authorization on every reconnect
+    device.set_trusted(true).await
+}
+
+async fn remove_device(conn: &Connection, address: &str) -> zbus::Result<()> {
+    let device = find_device(conn, address).await?;
+    let adapter = AdapterProxy::builder(conn)
+        .path(device.adapter)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    adapter.remove_device(&ObjectPath::from(&device.path)).await
+}
+
+async fn shared_bus() -> zbus::Result<Connection> {
+    if let Some(conn) = SHARED_BUS.lock().unwrap().clone() {
+        return Ok(conn);
+    }
+    let conn = Connection::system().await?;
+    Ok(SHARED_BUS.lock().unwrap().get_or_insert(conn).clone())
+}
+
+/// Runs `f` on the shared system bus connection and waits for it.
+fn with_shared_bus<T, F, Fut>(f: F) -> zbus::Result<T>
+where
+    F: FnOnce(Connection) -> Fut,
+    Fut: Future<Output = zbus::Result<T>>,
+{
+    futures::executor::block_on(async { f(shared_bus().await?).await })
+}
+
+/// Streams the bluetooth state as JSON. Used by `Listen { source = "bluetooth" }`.
+pub async fn stream_state(
+    tx: mpsc::Sender<String>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> zbus::Result<()> {
+    let conn = shared_bus().await?;
+
+    // Covers InterfacesAdded/Removed as well as PropertiesChanged on every object
+    let rule = MatchRule::builder().msg_type(message::Type::Signal).sender(BLUEZ_SERVICE)?.build();
+    let mut bluez_signals = MessageStream::for_match_rule(rule, &conn, None).await?;
+
+    loop {
+        // bluetoothd may not be running yet, report it as unavailable until it is
+        let (adapters, devices) = read_objects(&conn).await.unwrap_or_default();
+        if tx.send(snapshot(&adapters, &devices).to_string()).await.is_err() {
+            break;
+        }
+
+        tokio::select! {
+            Some(_) = bluez_signals.next() => {
+                // Discovery emits a lot of RSSI updates, coalesce them into one update
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                while let Some(Some(_)) = bluez_signals.next().now_or_never() {}
+            }
+            res = shutdown_rx.changed() => {
+                if res.is_err() || *shutdown_rx.borrow() {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn adapters(_args: Vec<Value>) -> Result<Value> {
+    let (adapters, _) = with_system_bus(|conn| async move { read_objects(&conn).await })
+        .map_err(|e| runtime_err!("Failed to read bluetooth adapters: {e}"))?;
+
+    Ok(Value::List(adapters.iter().map(AdapterInfo::to_value).collect()))
+}
+
+pub fn devices(_args: Vec<Value>) -> Result<Value> {
+    let (_, devices) = with_system_bus(|conn| async move { read_objects(&conn).await })
+        .map_err(|e| runtime_err!("Failed to read bluetooth devices: {e}"))?;
+
+    Ok(Value::List(devices.iter().map(DeviceInfo::to_value).collect()))
+}
+
+pub fn power_on(_args: Vec<Value>) -> Result<Value> {
+    with_system_bus(|conn| async move { default_adapter(&conn).await?.set_powered(true).await })
+        .map_err(|e| runtime_err!("Failed to power on bluetooth: {e}"))?;
+
+    Ok(Value::Null)
+}
+
+pub fn power_off(_args: Vec<Value>) -> Result<Value> {
+    with_system_bus(|conn| async move { default_adapter(&conn).await?.set_powered(false).await })
+        .map_err(|e| runtime_err!("Failed to power off bluetooth: {e}"))?;
+
+    Ok(Value::Null)
+}
+
+pub fn start_discovery(_args: Vec<Value>) -> Result<Value> {
+    with_shared_bus(|conn| async move { default_adapter(&conn).await?.start_discovery().await })
+        .map_err(|e| runtime_err!("Failed to start bluetooth discovery: {e}"))?;
+
+    Ok(Value::Null)
+}
+
+pub fn stop_discovery(_args: Vec<Value>) -> Result<Value> {
+    with_shared_bus(|conn| async move { default_adapter(&conn).await?.stop_discovery().await })
+        .map_err(|e| runtime_err!("Failed to stop bluetooth discovery: {e}"))?;
+
+    Ok(Value::Null)
+}
+
+// address: &str
+pub fn connect(args: Vec<Value>) -> Result<Value> {
+    let Value::Str(address) = &args[0] else {
+        return Err(runtime_err!("Address must be a string"));
+    };
+
+    with_system_bus(|conn| async move { device_proxy(&conn, address).await?.connect().await })
+        .map_err(|e| runtime_err!("Failed to connect to {}: {e}", address))?;
+
+    Ok(Value::Null)
+}
+
+// address: &str
+pub fn disconnect(args: Vec<Value>) -> Result<Value> {
+    let Value::Str(address) = &args[0] else {
+        return Err(runtime_err!("Address must be a string"));
+    };
+
+    with_system_bus(|conn| async move { device_proxy(&conn, address).await?.disconnect().await })
+        .map_err(|e| runtime_err!("Failed to disconnect from {}: {e}", address))?;
+
+    Ok(Value::Null)
+}
+
+// address: &str
+pub fn pair(args: Vec<Value>) -> Result<Value> {
+    let Value::Str(address) = &args[0] else {
+        return Err(runtime_err!("Address must be a string"));
+    };
+
+    with_system_bus(|conn| async move { pair_device(&conn, address).await })
+        .map_err(|e| runtime_err!("Failed to pair with {}: {e}", address))?;
+
+    Ok(Value::Null)
+}
+
+// address: &str
+pub fn remove(args: Vec<Value>) -> Result<Value> {
+    let Value::Str(address) = &args[0] else {
+        return Err(runtime_err!("Address must be a string"));
+    };
+
+    with_system_bus(|conn| async move { remove_device(&conn, address).await })
+        .map_err(|e| runtime_err!("Failed to remove {}: {e}", address))?;
+
+    Ok(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::apilib::mock_bus;
+    use std::sync::{Arc, Mutex};
+    use zbus::{fdo::ObjectManager, interface};
+
+    const ADAPTER_PATH: &str = "/org/bluez/hci0";
+    const HEADPHONES_PATH: &str = "/org/bluez/hci0/dev_00_11_22_33_44_55";
+    const PHONE_PATH: &str = "/org/bluez/hci0/dev_66_77_88_99_AA_BB";
+
+    type Calls = Arc<Mutex<Vec<String>>>;
+
+    struct MockAdapter {
+        powered: bool,
+        calls: Calls,
+    }
+
+    #[interface(name = "org.bluez.Adapter1")]
+    impl MockAdapter {
+        fn start_discovery(&self) {
+            self.calls.lock().unwrap().push("start_discovery".to_string());
+        }
+
+        fn remove_device(&self, device: ObjectPath<'_>) {
+            self.calls.lock().unwrap().push(format!("remove {device}"));
+        }
+
+        #[zbus(property)]
+        fn address(&self) -> String {
+            "AA:AA:AA:AA:AA:AA".to_string()
+        }
+
+        #[zbus(property)]
+        fn alias(&self) -> String {
+            "laptop".to_string()
+        }
+
+        #[zbus(property)]
+        fn powered(&self) -> bool {
+            self.powered
+        }
+
+        #[zbus(property)]
+        fn set_powered(&mut self, value: bool) {
+            self.powered = value;
+        }
+
+        #[zbus(property)]
+        fn discovering(&self) -> bool {
+            false
+        }
+    }
+
+    struct MockDevice {
+        alias: &'static str,
+        address: &'static str,
+        paired: bool,
+        connected: bool,
+        calls: Calls,
+    }
+
+    #[interface(name = "org.bluez.Device1")]
+    impl MockDevice {
+        fn connect(&self) {
+            self.calls.lock().unwrap().push(format!("connect {}", self.address));
+        }
+
+        fn disconnect(&self) {
+            self.calls.lock().unwrap().push(format!("disconnect {}", self.address));
+        }
+
+        fn pair(&self) {
+            self.calls.lock().unwrap().push(format!("pair {}", self.address));
+        }
+
+        #[zbus(property)]
+        fn adapter(&self) -> OwnedObjectPath {
+            OwnedObjectPath::try_from(ADAPTER_PATH).unwrap()
+        }
+
+        #[zbus(property)]
+        fn alias(&self) -> String {
+            self.alias.to_string()
+        }
+
+        #[zbus(property)]
+        fn address(&self) -> String {
+            self.address.to_string()
+        }
+
+        #[zbus(property)]
+        fn paired(&self) -> bool {
+            self.paired
+        }
+
+        #[zbus(property)]
+        fn connected(&self) -> bool {
+            self.connected
+        }
+
+        #[zbus(property)]
+        fn trusted(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn set_trusted(&mut self, value: bool) {
+            self.calls.lock().unwrap().push(format!("trust {} {value}", self.address));
+        }
+    }
+
+    struct MockBattery;
+
+    #[interface(name = "org.bluez.Battery1")]
+    impl MockBattery {
+        #[zbus(property)]
+        fn percentage(&self) -> u8 {
+            80
+        }
+    }
+
+    /// Serves a fake BlueZ object tree on a private peer-to-peer bus and
+    /// returns the client side along with the log of method calls.
+    async fn mock_bluez() -> (Connection, Connection, Calls) {
+        let calls = Calls::default();
+
+        let headphones = MockDevice {
+            alias: "Headphones",
+            address: "00:11:22:33:44:55",
+            paired: true,
+            connected: true,
+            calls: calls.clone(),
+        };
+        let phone = MockDevice {
+            alias: "",
+            address: "66:77:88:99:AA:BB",
+            paired: false,
+            connected: false,
+            calls: calls.clone(),
+        };
+
+        let (server, client) = mock_bus("/", ObjectManager).await;
+
+        let objects = server.object_server();
+        let adapter = MockAdapter { powered: true, calls: calls.clone() };
+        objects.at(ADAPTER_PATH, adapter).await.unwrap();
+        objects.at(HEADPHONES_PATH, headphones).await.unwrap();
+        objects.at(HEADPHONES_PATH, MockBattery).await.unwrap();
+        objects.at(PHONE_PATH, phone).await.unwrap();
+
+        (server, client, calls)
+    }
+
+    #[tokio::test]
+    async fn test_read_objects() {
+        let (_server, conn, _) = mock_bluez().await;
+        let (adapters, devices) = read_objects(&conn).await.unwrap();
+
+        assert_eq!(adapters.len(), 1);
+        assert_eq!(adapters[0].name, "hci0");
+        assert!(adapters[0].powered);
+
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].name, "Headphones");
+        assert_eq!(devices[0].battery, Some(80));
+        assert_eq!(devices[0].adapter.as_str(), ADAPTER_PATH);
+        assert!(devices[0].connected && devices[0].paired);
+        // unnamed devices are shown by address
+        assert_eq!(devices[1].name, "66:77:88:99:AA:BB");
+        assert_eq!(devices[1].battery, None);
+
+        let state = snapshot(&adapters, &devices);
+        assert_eq!(state["powered"], true);
+        assert_eq!(state["connected"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_controls() {
+        let (_server, conn, calls) = mock_bluez().await;
+
+        default_adapter(&conn).await.unwrap().set_powered(false).await.unwrap();
+        default_adapter(&conn).await.unwrap().start_discovery().await.unwrap();
+        device_proxy(&conn, "00:11:22:33:44:55").await.unwrap().disconnect().await.unwrap();
+        pair_device(&conn, "66:77:88:99:aa:bb").await.unwrap();
+        remove_device(&conn, "66:77:88:99:AA:BB").await.unwrap();
+        find_device(&conn, "FF:FF:FF:FF:FF:FF").await.unwrap_err();
+
+        let (adapters, _) = read_objects(&conn).await.unwrap();
+        assert!(!adapters[0].powered);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "start_discovery".to_string(),
+                "disconnect 00:11:22:33:44:55".to_string(),
+                "pair 66:77:88:99:AA:BB".to_string(),
+                "trust 66:77:88:99:AA:BB true".to_string(),
+                format!("remove {PHONE_PATH}"),
+            ]
+        );
+    }
+}
@@ -1,10 +1,20 @@
+mod appearance;
+mod apps;
+mod bluetooth;
 mod linux;
+mod mpris;
+mod session;
 mod wifi;
 
 use nbcl::{
     library::{Library, LibraryItem},
     NbclEngine, Type,
 };
+use std::future::Future;
+use tokio::sync::{mpsc, watch};
+use zbus::Connection;
+
+pub use appearance::{watch_appearance, Appearance, ColorScheme};
 
 pub fn register_api_lib(engine: &mut NbclEngine) {
     let linux = LibraryItem::define("linux")
@@ -28,8 +38,103 @@ pub fn register_api_lib(engine: &mut NbclEngine) {
         .with_fn("disconnect", vec![], Type::Null, wifi::disconnect)
         .with_fn("disable_adapter", vec![], Type::Null, wifi::disable_adapter)
         .with_fn("enable_adapter", vec![], Type::Null, wifi::enable_adapter)
-        .with_fn("get_adapter_connectivity", vec![], Type::Null, wifi::get_adapter_connectivity);
+        .with_fn("get_adapter_connectivity", vec![], Type::Str, wifi::get_adapter_connectivity);
+
+    let mpris = LibraryItem::define("mpris")
+        .with_fn("players", vec![], Type::List, mpris::players)
+        .with_fn("current", vec![], Type::Map, mpris::current)
+        .with_fn("play", vec![], Type::Null, mpris::play)
+        .with_fn("pause", vec![], Type::Null, mpris::pause)
+        .with_fn("play_pause", vec![], Type::Null, mpris::play_pause)
+        .with_fn("next", vec![], Type::Null, mpris::next)
+        .with_fn("previous", vec![], Type::Null, mpris::previous)
+        .with_fn("seek", vec![Type::Any], Type::Null, mpris::seek);
+
+    let bluetooth = LibraryItem::define("bluetooth")
+        .with_fn("adapters", vec![], Type::List, bluetooth::adapters)
+        .with_fn("devices", vec![], Type::List, bluetooth::devices)
+        .with_fn("power_on", vec![], Type::Null, bluetooth::power_on)
+        .with_fn("power_off", vec![], Type::Null, bluetooth::power_off)
+        .with_fn("start_discovery", vec![], Type::Null, bluetooth::start_discovery)
+        .with_fn("stop_discovery", vec![], Type::Null, bluetooth::stop_discovery)
+        .with_fn("connect", vec![Type::Str], Type::Null, bluetooth::connect)
+        .with_fn("disconnect", vec![Type::Str], Type::Null, bluetooth::disconnect)
+        .with_fn("pair", vec![Type::Str], Type::Null, bluetooth::pair)
+        .with_fn("remove", vec![Type::Str], Type::Null, bluetooth::remove);
+
+    let session = LibraryItem::define("session")
+        .with_fn("suspend", vec![], Type::Null, session::suspend)
+        .with_fn("hibernate", vec![], Type::Null, session::hibernate)
+        .with_fn("reboot", vec![], Type::Null, session::reboot)
+        .with_fn("poweroff", vec![], Type::Null, session::poweroff)
+        .with_fn("lock_session", vec![], Type::Null, session::lock_session)
+        .with_fn("can_suspend", vec![], Type::Bool, session::can_suspend)
+        .with_fn("can_hibernate", vec![], Type::Bool, session::can_hibernate)
+        .with_fn("can_reboot", vec![], Type::Bool, session::can_reboot)
+        .with_fn("can_poweroff", vec![], Type::Bool, session::can_poweroff)
+        .with_fn("inhibit", vec![Type::Str, Type::Str], Type::Null, session::inhibit)
+        .with_fn("release_inhibit", vec![Type::Str], Type::Null, session::release_inhibit)
+        .with_fn("is_inhibited", vec![Type::Str], Type::Bool, session::is_inhibited);
 
-    let api_lib = Library::new("api".into(), vec![linux, wifi]);
+    let apps = LibraryItem::define("apps")
+        .with_fn("list", vec![], Type::List, apps::list)
+        .with_fn("search", vec![Type::Str], Type::List, apps::search)
+        .with_fn("launch", vec![Type::Str], Type::Null, apps::launch);
+
+    let api_lib = Library::new("api".into(), vec![linux, wifi, mpris, bluetooth, session, apps]);
     engine.register_library(api_lib);
 }
+
+/// Stream values from a builtin data source into `tx` until shutdown.
+///
+/// This backs the `source` property of `Listen`, the accepted names are
+/// [`DATA_SOURCES`](ewwii_shared_utils::schema::DATA_SOURCES).
+pub async fn stream_source(
+    source: &str,
+    tx: mpsc::Sender<String>,
+    shutdown_rx: watch::Receiver<bool>,
+) -> anyhow::Result<()> {
+    match source {
+        "mpris" => mpris::stream_state(tx, shutdown_rx).await?,
+        "wifi" => wifi::stream_state(tx, shutdown_rx).await?,
+        "bluetooth" => bluetooth::stream_state(tx, shutdown_rx).await?,
+        "apps" => apps::stream_state(tx, shutdown_rx).await?,
+        other => anyhow::bail!("Unknown data source: {other}"),
+    }
+
+    Ok(())
+}
+
+/// Runs `f` on a fresh system bus connection and waits for it.
+fn with_system_bus<T, F, Fut>(f: F) -> zbus::Result<T>
+where
+    F: FnOnce(Connection) -> Fut,
+    Fut: Future<Output = zbus::Result<T>>,
+{
+    futures::executor::block_on(async {
+        let conn = Connection::system().await?;
+        f(conn).await
+    })
+}
+
+/// Serves `object` at `path` on a private peer-to-peer bus and returns the
+/// server and client side. Further objects can be added to the object server
+/// of the server side.
+#[cfg(test)]
+async fn mock_bus(
+    path: &str,
+    object: impl zbus::object_server::Interface,
+) -> (Connection, Connection) {
+    let (server_sock, client_sock) = std::os::unix::net::UnixStream::pair().unwrap();
+
+    let server = zbus::connection::Builder::async_io_unix_stream(server_sock)
+        .server(zbus::Guid::generate())
+        .unwrap()
+        .p2p()
+        .serve_at(path, object)
+        .unwrap()
+        .build();
+    let client = zbus::connection::Builder::async_io_unix_stream(client_sock).p2p().build();
+
+    futures::try_join!(server, client).unwrap()
+}
@@ -0,0 +1,381 @@
+use crate::runtime_err;
+use futures::StreamExt;
+use nbcl::{error::Result, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use zbus::{
+    fdo::DBusProxy,
+    message,
+    proxy::CacheProperties,
+    zvariant::{OwnedValue, Value as DBusValue},
+    Connection, MatchRule, MessageStream,
+};
+
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// The player shown by the `mpris` data source. Control functions
+/// target this player so that they act on what the user sees.
+static ACTIVE_PLAYER: Mutex<Option<String>> = Mutex::new(None);
+
+#[zbus::proxy(interface = "org.mpris.MediaPlayer2", default_path = "/org/mpris/MediaPlayer2")]
+trait MediaPlayer2 {
+    #[zbus(property)]
+    fn identity(&self) -> zbus::Result<String>;
+}
+
+#[zbus::proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait Player {
+    fn play(&self) -> zbus::Result<()>;
+    fn pause(&self) -> zbus::Result<()>;
+    fn play_pause(&self) -> zbus::Result<()>;
+    fn next(&self) -> zbus::Result<()>;
+    fn previous(&self) -> zbus::Result<()>;
+    fn seek(&self, offset: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> zbus::Result<i64>;
+}
+
+#[derive(Debug, Clone, Default)]
+struct PlayerInfo {
+    /// Bus name without the `org.mpris.MediaPlayer2.` prefix
+    name: String,
+    identity: String,
+    status: String,
+    title: String,
+    artist: String,
+    album: String,
+    art_url: String,
+    /// In seconds
+    position: i64,
+    /// In seconds
+    length: i64,
+}
+
+impl PlayerInfo {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "player": self.name,
+            "identity": self.identity,
+            "status": self.status,
+            "title": self.title,
+            "artist": self.artist,
+            "album": self.album,
+            "art_url": self.art_url,
+            "position": self.position,
+            "length": self.length,
+        })
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Map(vec![
+            ("player".into(), Value::Str(self.name.clone())),
+            ("identity".into(), Value::Str(self.identity.clone())),
+            ("status".into(), Value::Str(self.status.clone())),
+            ("title".into(), Value::Str(self.title.clone())),
+            ("artist".into(), Value::Str(self.artist.clone())),
+            ("album".into(), Value::Str(self.album.clone())),
+            ("art_url".into(), Value::Str(self.art_url.clone())),
+            ("position".into(), Value::Int(self.position)),
+            ("length".into(), Value::Int(self.length)),
+        ])
+    }
+}
+
+async fn list_players(conn: &Connection) -> zbus::Result<Vec<String>> {
+    let dbus = DBusProxy::new(conn).await?;
+    let mut names: Vec<String> = dbus
+        .list_names()
+        .await?
+        .into_iter()
+        .map(|n| n.to_string())
+        .filter(|n| n.starts_with(MPRIS_PREFIX))
+        .collect();
+    names.sort();
+
+    Ok(names)
+}
+
+async fn player_proxy<'a>(conn: &Connection, bus_name: &'a str) -> zbus::Result<PlayerProxy<'a>> {
+    PlayerProxy::builder(conn)
+        .destination(bus_name)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+}
+
+async fn read_player(conn: &Connection, bus_name: &str) -> zbus::Result<PlayerInfo> {
+    let player = player_proxy(conn, bus_name).await?;
+    let identity = match MediaPlayer2Proxy::builder(conn)
+        .destination(bus_name)?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+    {
+        Ok(p) => p.identity().await.unwrap_or_default(),
+        Err(_) => String::new(),
+    };
+
+    let metadata = player.metadata().await.unwrap_or_default();
+    let meta_str =
+        |key: &str| metadata.get(key).and_then(|v| dbus_to_string(v)).unwrap_or_default();
+
+    Ok(PlayerInfo {
+        name: bus_name.trim_start_matches(MPRIS_PREFIX).to_string(),
+        identity,
+        status: player.playback_status().await.unwrap_or_else(|_| "Stopped".to_string()),
+        title: meta_str("xesam:title"),
+        artist: meta_str("xesam:artist"),
+        album: meta_str("xesam:album"),
+        art_url: meta_str("mpris:artUrl"),
+        // Not every player implements Position
+        position: player.position().await.unwrap_or(0) / 1_000_000,
+        length: metadata.get("mpris:length").and_then(|v| dbus_to_i64(v)).unwrap_or(0) / 1_000_000,
+    })
+}
+
+async fn read_players(conn: &Connection) -> zbus::Result<Vec<PlayerInfo>> {
+    let mut players = Vec::new();
+    for bus_name in list_players(conn).await? {
+        match read_player(conn, &bus_name).await {
+            Ok(info) => players.push(info),
+            Err(e) => log::debug!("Skipping MPRIS player {}: {}", bus_name, e),
+        }
+    }
+
+    Ok(players)
+}
+
+/// Picks the player to show. A playing player always wins, preferring the
+/// previously active one so that two playing players don't flicker.
+fn pick_active(players: &[PlayerInfo], previous: Option<&str>) -> Option<usize> {
+    let is_previous = |p: &PlayerInfo| previous == Some(p.name.as_str());
+
+    players
+        .iter()
+        .position(|p| p.status == "Playing" && is_previous(p))
+        .or_else(|| players.iter().position(|p| p.status == "Playing"))
+        .or_else(|| players.iter().position(is_previous))
+        .or_else(|| (!players.is_empty()).then_some(0))
+}
+
+fn snapshot(players: &[PlayerInfo]) -> serde_json::Value {
+    let mut active_player = ACTIVE_PLAYER.lock().unwrap();
+    let active = pick_active(players, active_player.as_deref());
+    *active_player = active.map(|i| players[i].name.clone());
+
+    let mut state = match active {
+        Some(i) => players[i].to_json(),
+        None => PlayerInfo { status: "Stopped".to_string(), ..Default::default() }.to_json(),
+    };
+    state["players"] = serde_json::Value::Array(players.iter().map(PlayerInfo::to_json).collect());
+
+    state
+}
+
+/// Streams the MPRIS state as JSON. Used by `Listen { source = "mpris" }`.
+pub async fn stream_state(
+    tx: mpsc::Sender<String>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> zbus::Result<()> {
+    let conn = Connection::session().await?;
+    let dbus = DBusProxy::new(&conn).await?;
+    let mut owner_changes = dbus.receive_name_owner_changed().await?;
+
+    // PropertiesChanged and Seeked are both emitted on the MPRIS object path
+    let rule = MatchRule::builder().msg_type(message::Type::Signal).path(MPRIS_PATH)?.build();
+    let mut player_signals = MessageStream::for_match_rule(rule, &conn, None).await?;
+
+    loop {
+        // A failed read is retried on the next signal instead of ending the stream
+        let state = match read_players(&conn).await {
+            Ok(players) => Some(snapshot(&players)),
+            Err(e) => {
+                log::warn!("Failed to read MPRIS players: {}", e);
+                None
+            }
+        };
+        // Position isn't signalled while playing, so it has to be polled
+        let playing = state.as_ref().is_some_and(|state| state["status"] == "Playing");
+
+        if let Some(state) = state {
+            if tx.send(state.to_string()).await.is_err() {
+                break;
+            }
+        }
+
+        loop {
+            tokio::select! {
+                Some(change) = owner_changes.next() => {
+                    let is_player = change
+                        .args()
+                        .map(|a| a.name().as_str().starts_with(MPRIS_PREFIX))
+                        .unwrap_or(false);
+                    if is_player {
+                        break;
+                    }
+                }
+                Some(_) = player_signals.next() => break,
+                _ = tokio::time::sleep(Duration::from_secs(1)), if playing => break,
+                res = shutdown_rx.changed() => {
+                    if res.is_err() || *shutdown_rx.borrow() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn dbus_to_string(value: &DBusValue) -> Option<String> {
+    match value {
+        DBusValue::Str(s) => Some(s.to_string()),
+        DBusValue::ObjectPath(p) => Some(p.to_string()),
+        // xesam:artist is a list of artists
+        DBusValue::Array(a) => {
+            Some(a.inner().iter().filter_map(dbus_to_string).collect::<Vec<_>>().join(", "))
+        }
+        DBusValue::Value(v) => dbus_to_string(v),
+        _ => None,
+    }
+}
+
+fn dbus_to_i64(value: &DBusValue) -> Option<i64> {
+    // mpris:length should be an i64, but some players send other integer types
+    match value {
+        DBusValue::I64(n) => Some(*n),
+        DBusValue::U64(n) => Some(*n as i64),
+        DBusValue::I32(n) => Some(*n as i64),
+        DBusValue::U32(n) => Some(*n as i64),
+        DBusValue::F64(n) => Some(*n as i64),
+        DBusValue::Value(v) => dbus_to_i64(v),
+        _ => None,
+    }
+}
+
+/// Runs a control method on the active player.
+fn control<F, Fut>(action: &str, f: F) -> Result<Value>
+where
+    F: FnOnce(PlayerProxy<'static>) -> Fut,
+    Fut: std::future::Future<Output = zbus::Result<()>>,
+{
+    futures::executor::block_on(async {
+        let conn = Connection::session().await?;
+        let players = read_players(&conn).await?;
+        let active = {
+            let active_player = ACTIVE_PLAYER.lock().unwrap();
+            pick_active(&players, active_player.as_deref())
+        };
+
+        let Some(i) = active else {
+            return Ok(false);
+        };
+
+        let bus_name = format!("{}{}", MPRIS_PREFIX, players[i].name);
+        let player = PlayerProxy::builder(&conn)
+            .destination(bus_name)?
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?;
+        f(player).await?;
+
+        Ok(true)
+    })
+    .map_err(|e: zbus::Error| runtime_err!("Failed to {} MPRIS player: {}", action, e))
+    .and_then(|found| {
+        if found {
+            Ok(Value::Null)
+        } else {
+            Err(runtime_err!("No MPRIS player found"))
+        }
+    })
+}
+
+pub fn players(_args: Vec<Value>) -> Result<Value> {
+    let players = futures::executor::block_on(async {
+        let conn = Connection::session().await?;
+        read_players(&conn).await
+    })
+    .map_err(|e| runtime_err!("Failed to read MPRIS players: {}", e))?;
+
+    Ok(Value::List(players.iter().map(PlayerInfo::to_value).collect()))
+}
+
+pub fn current(_args: Vec<Value>) -> Result<Value> {
+    let players = futures::executor::block_on(async {
+        let conn = Connection::session().await?;
+        read_players(&conn).await
+    })
+    .map_err(|e| runtime_err!("Failed to read MPRIS players: {}", e))?;
+
+    let active_player = ACTIVE_PLAYER.lock().unwrap();
+    match pick_active(&players, active_player.as_deref()) {
+        Some(i) => Ok(players[i].to_value()),
+        None => Ok(Value::Map(Vec::new())),
+    }
+}
+
+pub fn play(_args: Vec<Value>) -> Result<Value> {
+    control("play", |p| async move { p.play().await })
+}
+
+pub fn pause(_args: Vec<Value>) -> Result<Value> {
+    control("pause", |p| async move { p.pause().await })
+}
+
+pub fn play_pause(_args: Vec<Value>) -> Result<Value> {
+    control("toggle", |p| async move { p.play_pause().await })
+}
+
+pub fn next(_args: Vec<Value>) -> Result<Value> {
+    control("skip", |p| async move { p.next().await })
+}
+
+pub fn previous(_args: Vec<Value>) -> Result<Value> {
+    control("rewind", |p| async move { p.previous().await })
+}
+
+// offset: seconds, negative to seek backwards
+pub fn seek(args: Vec<Value>) -> Result<Value> {
+    let offset = match &args[0] {
+        Value::Int(i) => *i as f64,
+        Value::Float(f) => *f,
+        _ => return Err(runtime_err!("Seek offset must be a number of seconds")),
+    };
+
+    let micros = (offset * 1_000_000.0) as i64;
+    control("seek", |p| async move { p.seek(micros).await })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(name: &str, status: &str) -> PlayerInfo {
+        PlayerInfo { name: name.into(), status: status.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_pick_active_player() {
+        let players = vec![player("mpv", "Paused"), player("spotify", "Playing")];
+        assert_eq!(pick_active(&players, None), Some(1));
+        assert_eq!(pick_active(&players, Some("mpv")), Some(1));
+
+        let players = vec![player("mpv", "Paused"), player("spotify", "Stopped")];
+        assert_eq!(pick_active(&players, Some("spotify")), Some(1));
+        assert_eq!(pick_active(&players, Some("vlc")), Some(0));
+        assert_eq!(pick_active(&[], None), None);
+    }
+}
@@ -0,0 +1,309 @@
+use super::with_system_bus;
+use crate::runtime_err;
+use nbcl::{error::Result, Value};
+use std::sync::Mutex;
+use zbus::{proxy::CacheProperties, zvariant::OwnedFd, Connection};
+
+/// Lock types logind accepts in `Inhibit`.
+const INHIBIT_WHAT: &[&str] = &[
+    "shutdown",
+    "sleep",
+    "idle",
+    "handle-power-key",
+    "handle-suspend-key",
+    "handle-hibernate-key",
+    "handle-lid-switch",
+];
+
+/// Inhibitors taken through `api.session.inhibit`. logind releases an
+/// inhibitor once its file descriptor is closed, so dropping the fd is
+/// all it takes to release one.
+static INHIBITORS: Mutex<Vec<(String, OwnedFd)>> = Mutex::new(Vec::new());
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    fn suspend(&self, interactive: bool) -> zbus::Result<()>;
+    fn hibernate(&self, interactive: bool) -> zbus::Result<()>;
+    fn reboot(&self, interactive: bool) -> zbus::Result<()>;
+    fn power_off(&self, interactive: bool) -> zbus::Result<()>;
+
+    fn can_suspend(&self) -> zbus::Result<String>;
+    fn can_hibernate(&self) -> zbus::Result<String>;
+    fn can_reboot(&self) -> zbus::Result<String>;
+    fn can_power_off(&self) -> zbus::Result<String>;
+
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto"
+)]
+trait Session {
+    fn lock(&self) -> zbus::Result<()>;
+}
+
+async fn manager(conn: &Connection) -> zbus::Result<ManagerProxy<'static>> {
+    ManagerProxy::builder(conn).cache_properties(CacheProperties::No).build().await
+}
+
+/// `challenge` means polkit will ask for authentication, which still
+/// counts as allowed since the user can go through with it.
+fn is_allowed(answer: &str) -> bool {
+    matches!(answer, "yes" | "challenge")
+}
+
+/// Normalizes a colon separated list of lock types, e.g. `sleep:idle`.
+fn parse_inhibit_what(what: &str) -> std::result::Result<String, String> {
+    let mut locks: Vec<&str> = what.split(':').map(str::trim).filter(|w| !w.is_empty()).collect();
+    if locks.is_empty() {
+        return Err("no inhibitor type given".to_string());
+    }
+
+    if let Some(invalid) = locks.iter().find(|w| !INHIBIT_WHAT.contains(w)) {
+        return Err(format!(
+            "unknown inhibitor type '{}', expected one of: {}",
+            invalid,
+            INHIBIT_WHAT.join(", ")
+        ));
+    }
+
+    locks.sort_unstable();
+    locks.dedup();
+    Ok(locks.join(":"))
+}
+
+fn power_action<F, Fut>(action: &str, f: F) -> Result<Value>
+where
+    F: FnOnce(ManagerProxy<'static>) -> Fut,
+    Fut: std::future::Future<Output = zbus::Result<()>>,
+{
+    with_system_bus(|conn| async move { f(manager(&conn).await?).await })
+        .map_err(|e| runtime_err!("Failed to {}: {e}", action))?;
+
+    Ok(Value::Null)
+}
+
+/// Asks logind whether an action is allowed, with `f` calling the matching `Can*` method.
+async fn allowed<F, Fut>(conn: &Connection, f: F) -> zbus::Result<bool>
+where
+    F: FnOnce(ManagerProxy<'static>) -> Fut,
+    Fut: std::future::Future<Output = zbus::Result<String>>,
+{
+    Ok(is_allowed(&f(manager(conn).await?).await?))
+}
+
+fn capability<F, Fut>(action: &str, f: F) -> Result<Value>
+where
+    F: FnOnce(ManagerProxy<'static>) -> Fut,
+    Fut: std::future::Future<Output = zbus::Result<String>>,
+{
+    let allowed = with_system_bus(|conn| async move { allowed(&conn, f).await })
+        .map_err(|e| runtime_err!("Failed to check whether {} is allowed: {e}", action))?;
+
+    Ok(Value::Bool(allowed))
+}
+
+fn holds_inhibitor(what: &str) -> bool {
+    INHIBITORS.lock().unwrap().iter().any(|(w, _)| w == what)
+}
+
+/// Takes an inhibitor for `what` unless one is held already.
+///
+/// The inhibitors are not locked while waiting for logind, so a slow answer
+/// does not stall the other session functions. If another inhibitor for `what`
+/// was taken meanwhile, the new one is released right away.
+async fn take_inhibitor(conn: &Connection, what: String, why: &str) -> zbus::Result<()> {
+    if holds_inhibitor(&what) {
+        return Ok(());
+    }
+
+    let fd = manager(conn).await?.inhibit(&what, "ewwii", why, "block").await?;
+    let mut inhibitors = INHIBITORS.lock().unwrap();
+    if !inhibitors.iter().any(|(w, _)| *w == what) {
+        inhibitors.push((what, fd));
+    }
+
+    Ok(())
+}
+
+pub fn suspend(_args: Vec<Value>) -> Result<Value> {
+    power_action("suspend", |m| async move { m.suspend(true).await })
+}
+
+pub fn hibernate(_args: Vec<Value>) -> Result<Value> {
+    power_action("hibernate", |m| async move { m.hibernate(true).await })
+}
+
+pub fn reboot(_args: Vec<Value>) -> Result<Value> {
+    power_action("reboot", |m| async move { m.reboot(true).await })
+}
+
+pub fn poweroff(_args: Vec<Value>) -> Result<Value> {
+    power_action("power off", |m| async move { m.power_off(true).await })
+}
+
+pub fn lock_session(_args: Vec<Value>) -> Result<Value> {
+    with_system_bus(|conn| async move {
+        SessionProxy::builder(&conn)
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await?
+            .lock()
+            .await
+    })
+    .map_err(|e| runtime_err!("Failed to lock session: {e}"))?;
+
+    Ok(Value::Null)
+}
+
+pub fn can_suspend(_args: Vec<Value>) -> Result<Value> {
+    capability("suspend", |m| async move { m.can_suspend().await })
+}
+
+pub fn can_hibernate(_args: Vec<Value>) -> Result<Value> {
+    capability("hibernate", |m| async move { m.can_hibernate().await })
+}
+
+pub fn can_reboot(_args: Vec<Value>) -> Result<Value> {
+    capability("reboot", |m| async move { m.can_reboot().await })
+}
+
+pub fn can_poweroff(_args: Vec<Value>) -> Result<Value> {
+    capability("power off", |m| async move { m.can_power_off().await })
+}
+
+// what: &str, why: &str
+pub fn inhibit(args: Vec<Value>) -> Result<Value> {
+    let Value::Str(what) = &args[0] else {
+        return Err(runtime_err!("Inhibitor type must be a string"));
+    };
+    let Value::Str(why) = &args[1] else {
+        return Err(runtime_err!("Reason must be a string"));
+    };
+    let what = parse_inhibit_what(what).map_err(|e| runtime_err!("Failed to inhibit: {e}"))?;
+
+    with_system_bus(|conn| {
+        let what = what.clone();
+        async move { take_inhibitor(&conn, what, why).await }
+    })
+    .map_err(|e| runtime_err!("Failed to inhibit {}: {e}", what))?;
+
+    Ok(Value::Null)
+}
+
+// what: &str
+pub fn release_inhibit(args: Vec<Value>) -> Result<Value> {
+    let Value::Str(what) = &args[0] else {
+        return Err(runtime_err!("Inhibitor type must be a string"));
+    };
+    let what = parse_inhibit_what(what).map_err(|e| runtime_err!("Failed to release: {e}"))?;
+
+    INHIBITORS.lock().unwrap().retain(|(w, _)| *w != what);
+
+    Ok(Value::Null)
+}
+
+// what: &str
+pub fn is_inhibited(args: Vec<Value>) -> Result<Value> {
+    let Value::Str(what) = &args[0] else {
+        return Err(runtime_err!("Inhibitor type must be a string"));
+    };
+    let what = parse_inhibit_what(what).map_err(|e| runtime_err!("{e}"))?;
+
+    Ok(Value::Bool(holds_inhibitor(&what)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::apilib::mock_bus;
+    use std::io::Read;
+    use std::os::unix::net::UnixStream;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use zbus::interface;
+
+    /// Inhibitors handed out, as the arguments of `Inhibit` and the end of
+    /// the socket logind would keep.
+    type Inhibited = Arc<Mutex<Vec<(String, UnixStream)>>>;
+
+    struct MockManager {
+        inhibited: Inhibited,
+    }
+
+    #[interface(name = "org.freedesktop.login1.Manager")]
+    impl MockManager {
+        fn can_suspend(&self) -> String {
+            "challenge".into()
+        }
+
+        fn can_hibernate(&self) -> String {
+            "na".into()
+        }
+
+        fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> OwnedFd {
+            let (kept, handed_out) = UnixStream::pair().unwrap();
+            let args = format!("{} {} {} {}", what, who, why, mode);
+            self.inhibited.lock().unwrap().push((args, kept));
+            std::os::fd::OwnedFd::from(handed_out).into()
+        }
+    }
+
+    async fn mock_logind() -> (Connection, Connection, Inhibited) {
+        let inhibited = Inhibited::default();
+        let logind = MockManager { inhibited: inhibited.clone() };
+        let (server, client) = mock_bus("/org/freedesktop/login1", logind).await;
+        (server, client, inhibited)
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        assert!(is_allowed("yes"));
+        assert!(is_allowed("challenge"));
+        assert!(!is_allowed("no"));
+        assert!(!is_allowed("na"));
+    }
+
+    #[test]
+    fn test_parse_inhibit_what() {
+        assert_eq!(parse_inhibit_what("idle").unwrap(), "idle");
+        assert_eq!(parse_inhibit_what("sleep:idle:sleep").unwrap(), "idle:sleep");
+        assert!(parse_inhibit_what("").is_err());
+        assert!(parse_inhibit_what("idle:nap").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_allowed() {
+        let (_server, conn, _) = mock_logind().await;
+
+        assert!(allowed(&conn, |m| async move { m.can_suspend().await }).await.unwrap());
+        assert!(!allowed(&conn, |m| async move { m.can_hibernate().await }).await.unwrap());
+        assert!(allowed(&conn, |m| async move { m.can_reboot().await }).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_inhibit() {
+        let (_server, conn, inhibited) = mock_logind().await;
+        let what = || vec![Value::Str("handle-lid-switch".into())];
+
+        take_inhibitor(&conn, "handle-lid-switch".into(), "presenting").await.unwrap();
+        take_inhibitor(&conn, "handle-lid-switch".into(), "again").await.unwrap();
+        assert_eq!(is_inhibited(what()).unwrap(), Value::Bool(true));
+
+        let (args, mut kept) = inhibited.lock().unwrap().pop().unwrap();
+        assert_eq!(args, "handle-lid-switch ewwii presenting block");
+        assert!(inhibited.lock().unwrap().is_empty());
+
+        // logind sees the inhibitor released once its fd is closed
+        release_inhibit(what()).unwrap();
+        assert_eq!(is_inhibited(what()).unwrap(), Value::Bool(false));
+        kept.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(kept.read(&mut [0; 1]).unwrap(), 0);
+    }
+}
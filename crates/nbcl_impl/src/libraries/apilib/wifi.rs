@@ -1,49 +1,523 @@
+use super::with_system_bus;
 use crate::runtime_err;
+use futures::{FutureExt, StreamExt};
 use nbcl::{error::Result, Value};
-use std::process::Command;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use zbus::{
+    fdo::PropertiesProxy,
+    message,
+    names::InterfaceName,
+    proxy::CacheProperties,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value as DBusValue},
+    Connection, MatchRule, MessageStream,
+};
 
-pub fn scan(_args: Vec<Value>) -> Result<Value> {
-    let output = Command::new("nmcli")
-        .args(["-t", "-f", "SSID,SIGNAL,SECURITY", "dev", "wifi"])
-        .output()
-        .map_err(|e| runtime_err!("Failed to run nmcli: {e}"))?;
-
-    let stdout = String::from_utf8(output.stdout)
-        .map_err(|e| runtime_err!("Invalid UTF-8 output from nmcli: {e}"))?;
-
-    let mut result = Vec::new();
-    for line in stdout.lines() {
-        let parts: Vec<&str> = line.split(':').collect();
-        if parts.len() != 3 {
+const NM_SERVICE: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const NM_AP_IFACE: &str = "org.freedesktop.NetworkManager.AccessPoint";
+const NM_DEVICE_TYPE_WIFI: u32 = 2;
+
+// NM80211ApFlags and NM80211ApSecurityFlags
+const NM_AP_FLAGS_PRIVACY: u32 = 0x1;
+const NM_AP_SEC_KEY_MGMT_PSK: u32 = 0x100;
+const NM_AP_SEC_KEY_MGMT_802_1X: u32 = 0x200;
+const NM_AP_SEC_KEY_MGMT_SAE: u32 = 0x400;
+const NM_AP_SEC_KEY_MGMT_OWE: u32 = 0x800;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.NetworkManager",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager"
+)]
+trait NetworkManager {
+    fn get_devices(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+    fn activate_connection(
+        &self,
+        connection: &ObjectPath<'_>,
+        device: &ObjectPath<'_>,
+        specific_object: &ObjectPath<'_>,
+    ) -> zbus::Result<OwnedObjectPath>;
+    fn add_and_activate_connection(
+        &self,
+        connection: HashMap<&str, HashMap<&str, DBusValue<'_>>>,
+        device: &ObjectPath<'_>,
+        specific_object: &ObjectPath<'_>,
+    ) -> zbus::Result<(OwnedObjectPath, OwnedObjectPath)>;
+
+    #[zbus(property)]
+    fn wireless_enabled(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn set_wireless_enabled(&self, value: bool) -> zbus::Result<()>;
+    #[zbus(property)]
+    fn connectivity(&self) -> zbus::Result<u32>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.NetworkManager.Device",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait Device {
+    fn disconnect(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn device_type(&self) -> zbus::Result<u32>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.NetworkManager.Device.Wireless",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait Wireless {
+    fn get_all_access_points(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+    fn request_scan(&self, options: HashMap<&str, DBusValue<'_>>) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn active_access_point(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.NetworkManager.Settings",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager/Settings"
+)]
+trait Settings {
+    fn list_connections(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.NetworkManager.Settings.Connection",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait SettingsConnection {
+    fn get_settings(&self) -> zbus::Result<HashMap<String, HashMap<String, OwnedValue>>>;
+    fn update(&self, properties: HashMap<String, HashMap<String, OwnedValue>>) -> zbus::Result<()>;
+}
+
+/// How a connection to an access point is secured.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum KeyMgmt {
+    #[default]
+    Open,
+    /// Enhanced open, encrypted without a password
+    Owe,
+    Wep,
+    Psk,
+    Sae,
+    /// 802.1X, which needs more than a password
+    Enterprise,
+}
+
+impl KeyMgmt {
+    fn from_flags(flags: u32, wpa_flags: u32, rsn_flags: u32) -> Self {
+        let key_mgmt = wpa_flags | rsn_flags;
+        // Networks offering both PSK and SAE are connected with PSK, like nmcli does
+        if key_mgmt & NM_AP_SEC_KEY_MGMT_PSK != 0 {
+            KeyMgmt::Psk
+        } else if key_mgmt & NM_AP_SEC_KEY_MGMT_SAE != 0 {
+            KeyMgmt::Sae
+        } else if key_mgmt & NM_AP_SEC_KEY_MGMT_802_1X != 0 {
+            KeyMgmt::Enterprise
+        } else if key_mgmt & NM_AP_SEC_KEY_MGMT_OWE != 0 {
+            KeyMgmt::Owe
+        } else if flags & NM_AP_FLAGS_PRIVACY != 0 {
+            KeyMgmt::Wep
+        } else {
+            KeyMgmt::Open
+        }
+    }
+
+    /// The `802-11-wireless-security` settings to connect with `password`,
+    /// `None` if the network has no security to set up.
+    fn security_settings(
+        self,
+        password: Option<&str>,
+    ) -> zbus::Result<Option<HashMap<&'static str, DBusValue<'_>>>> {
+        let (key_mgmt, secret_key) = match self {
+            KeyMgmt::Open => return Ok(None),
+            KeyMgmt::Owe => ("owe", None),
+            KeyMgmt::Wep => ("none", Some("wep-key0")),
+            KeyMgmt::Psk => ("wpa-psk", Some("psk")),
+            KeyMgmt::Sae => ("sae", Some("psk")),
+            KeyMgmt::Enterprise => {
+                return Err(zbus::Error::Failure(
+                    "802.1X networks have to be set up in NetworkManager first".to_string(),
+                ))
+            }
+        };
+
+        let mut security = HashMap::from([("key-mgmt", DBusValue::from(key_mgmt))]);
+        if let (Some(key), Some(password)) = (secret_key, password) {
+            security.insert(key, DBusValue::from(password));
+        }
+        Ok(Some(security))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct AccessPointInfo {
+    path: OwnedObjectPath,
+    ssid: String,
+    bssid: String,
+    signal: u8,
+    /// In MHz
+    frequency: u32,
+    security: String,
+    key_mgmt: KeyMgmt,
+    saved: bool,
+    active: bool,
+}
+
+impl AccessPointInfo {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "ssid": self.ssid,
+            "bssid": self.bssid,
+            "signal": self.signal,
+            "frequency": self.frequency,
+            "security": self.security,
+            "saved": self.saved,
+            "active": self.active,
+        })
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Map(vec![
+            ("ssid".into(), Value::Str(self.ssid.clone())),
+            ("bssid".into(), Value::Str(self.bssid.clone())),
+            ("signal".into(), Value::Int(self.signal as i64)),
+            ("frequency".into(), Value::Int(self.frequency as i64)),
+            ("security".into(), Value::Str(self.security.clone())),
+            ("saved".into(), Value::Bool(self.saved)),
+            ("active".into(), Value::Bool(self.active)),
+        ])
+    }
+}
+
+/// Describes the security of an access point the same way `nmcli` does.
+fn security_label(flags: u32, wpa_flags: u32, rsn_flags: u32) -> String {
+    let mut label = Vec::new();
+
+    if flags & NM_AP_FLAGS_PRIVACY != 0 && wpa_flags == 0 && rsn_flags == 0 {
+        label.push("WEP");
+    }
+    if wpa_flags != 0 {
+        label.push("WPA1");
+    }
+    if rsn_flags & NM_AP_SEC_KEY_MGMT_SAE != 0 {
+        label.push("WPA3");
+    } else if rsn_flags != 0 {
+        label.push("WPA2");
+    }
+    if (wpa_flags | rsn_flags) & NM_AP_SEC_KEY_MGMT_802_1X != 0 {
+        label.push("802.1X");
+    }
+
+    label.join(" ")
+}
+
+fn connectivity_label(connectivity: u32) -> &'static str {
+    match connectivity {
+        1 => "none",
+        2 => "portal",
+        3 => "limited",
+        4 => "full",
+        _ => "unknown",
+    }
+}
+
+/// Reads a byte array such as an SSID.
+fn dbus_to_bytes(value: &DBusValue) -> Vec<u8> {
+    match value {
+        DBusValue::Array(a) => a
+            .inner()
+            .iter()
+            .filter_map(|b| match b {
+                DBusValue::U8(b) => Some(*b),
+                _ => None,
+            })
+            .collect(),
+        DBusValue::Value(v) => dbus_to_bytes(v),
+        _ => Vec::new(),
+    }
+}
+
+async fn proxy<'p, P>(conn: &Connection, path: ObjectPath<'p>) -> zbus::Result<P>
+where
+    P: From<zbus::Proxy<'p>> + zbus::proxy::Defaults,
+{
+    zbus::proxy::Builder::new(conn).path(path)?.cache_properties(CacheProperties::No).build().await
+}
+
+async fn network_manager(conn: &Connection) -> zbus::Result<NetworkManagerProxy<'static>> {
+    NetworkManagerProxy::builder(conn).cache_properties(CacheProperties::No).build().await
+}
+
+async fn wifi_devices(conn: &Connection) -> zbus::Result<Vec<OwnedObjectPath>> {
+    let mut devices = Vec::new();
+    for path in network_manager(conn).await?.get_devices().await? {
+        let device: DeviceProxy = proxy(conn, ObjectPath::from(&path)).await?;
+        if device.device_type().await? == NM_DEVICE_TYPE_WIFI {
+            devices.push(path);
+        }
+    }
+
+    Ok(devices)
+}
+
+async fn first_wifi_device(conn: &Connection) -> zbus::Result<OwnedObjectPath> {
+    wifi_devices(conn)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| zbus::Error::Failure("No wifi device found".to_string()))
+}
+
+/// Saved wifi connections keyed by SSID
+async fn saved_connections(conn: &Connection) -> zbus::Result<HashMap<Vec<u8>, OwnedObjectPath>> {
+    let settings =
+        SettingsProxy::builder(conn).cache_properties(CacheProperties::No).build().await?;
+
+    let mut saved = HashMap::new();
+    for path in settings.list_connections().await? {
+        let connection: SettingsConnectionProxy = proxy(conn, ObjectPath::from(&path)).await?;
+        let Ok(config) = connection.get_settings().await else {
+            continue;
+        };
+
+        let is_wifi = config
+            .get("connection")
+            .and_then(|c| c.get("type"))
+            .is_some_and(|t| matches!(&**t, DBusValue::Str(s) if s.as_str() == "802-11-wireless"));
+        if !is_wifi {
             continue;
         }
-        let map = vec![
-            ("ssid".into(), Value::Str(parts[0].into())),
-            ("signal".into(), Value::Str(parts[1].into())),
-            ("security".into(), Value::Str(parts[2].into())),
-        ];
-        result.push(Value::Map(map));
+
+        if let Some(ssid) = config.get("802-11-wireless").and_then(|w| w.get("ssid")) {
+            saved.insert(dbus_to_bytes(ssid), path);
+        }
     }
-    Ok(Value::List(result))
+
+    Ok(saved)
 }
 
-pub fn current_connection(_args: Vec<Value>) -> Result<Value> {
-    let output = Command::new("nmcli")
-        .args(["-t", "-f", "ACTIVE,SSID,SIGNAL,SECURITY", "device", "wifi", "list"])
-        .output()
-        .map_err(|e| runtime_err!("Failed to run nmcli: {e}"))?;
-    let stdout = String::from_utf8(output.stdout)
-        .map_err(|e| runtime_err!("Invalid UTF-8 output: {}", e))?;
-    let mut map = Vec::new();
-    if let Some(line) = stdout.lines().find(|l| l.starts_with("yes:")) {
-        let parts: Vec<&str> = line.split(':').collect();
-        if parts.len() == 4 {
-            map.push(("ssid".into(), Value::Str(parts[1].into())));
-            map.push(("signal".into(), Value::Str(parts[2].into())));
-            map.push(("security".into(), Value::Str(parts[3].into())));
+async fn read_access_point(
+    conn: &Connection,
+    path: OwnedObjectPath,
+) -> zbus::Result<(Vec<u8>, AccessPointInfo)> {
+    let props = PropertiesProxy::builder(conn)
+        .destination(NM_SERVICE)?
+        .path(path.clone())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+    let all = props.get_all(InterfaceName::from_static_str_unchecked(NM_AP_IFACE)).await?;
+
+    let ssid = all.get("Ssid").map(|v| dbus_to_bytes(v)).unwrap_or_default();
+    let get_u32 = |key: &str| all.get(key).and_then(|v| u32::try_from(v).ok()).unwrap_or(0);
+    let (flags, wpa_flags, rsn_flags) =
+        (get_u32("Flags"), get_u32("WpaFlags"), get_u32("RsnFlags"));
+
+    let info = AccessPointInfo {
+        path,
+        // SSIDs are raw bytes and aren't guaranteed to be UTF-8
+        ssid: String::from_utf8_lossy(&ssid).into_owned(),
+        bssid: all
+            .get("HwAddress")
+            .and_then(|v| <&str>::try_from(v).ok())
+            .unwrap_or_default()
+            .to_string(),
+        signal: all.get("Strength").and_then(|v| u8::try_from(v).ok()).unwrap_or(0),
+        frequency: get_u32("Frequency"),
+        security: security_label(flags, wpa_flags, rsn_flags),
+        key_mgmt: KeyMgmt::from_flags(flags, wpa_flags, rsn_flags),
+        saved: false,
+        active: false,
+    };
+
+    Ok((ssid, info))
+}
+
+async fn access_points(conn: &Connection) -> zbus::Result<Vec<AccessPointInfo>> {
+    let saved = saved_connections(conn).await.unwrap_or_default();
+
+    let mut access_points = Vec::new();
+    for device_path in wifi_devices(conn).await? {
+        let wireless: WirelessProxy = proxy(conn, ObjectPath::from(&device_path)).await?;
+        let active_ap = wireless.active_access_point().await.ok();
+
+        for ap_path in wireless.get_all_access_points().await? {
+            // Access points can vanish between listing and reading them
+            let Ok((ssid, mut info)) = read_access_point(conn, ap_path).await else {
+                continue;
+            };
+            if ssid.is_empty() {
+                continue;
+            }
+
+            info.saved = saved.contains_key(&ssid);
+            info.active = active_ap.as_ref() == Some(&info.path);
+            access_points.push(info);
+        }
+    }
+
+    access_points.sort_by(|a, b| b.active.cmp(&a.active).then(b.signal.cmp(&a.signal)));
+    Ok(access_points)
+}
+
+async fn read_state(conn: &Connection) -> zbus::Result<serde_json::Value> {
+    let nm = network_manager(conn).await?;
+    let networks = access_points(conn).await.unwrap_or_default();
+
+    let mut state = match networks.iter().find(|ap| ap.active) {
+        Some(ap) => ap.to_json(),
+        None => AccessPointInfo::default().to_json(),
+    };
+    state["connected"] = networks.iter().any(|ap| ap.active).into();
+    state["enabled"] = nm.wireless_enabled().await.unwrap_or(false).into();
+    state["connectivity"] = connectivity_label(nm.connectivity().await.unwrap_or(0)).into();
+    state["networks"] = networks.iter().map(AccessPointInfo::to_json).collect();
+
+    Ok(state)
+}
+
+async fn connect_to(conn: &Connection, ssid: &str, password: Option<&str>) -> zbus::Result<()> {
+    let nm = network_manager(conn).await?;
+    let device = first_wifi_device(conn).await?;
+
+    let ap = access_points(conn)
+        .await?
+        .into_iter()
+        .filter(|ap| ap.ssid == ssid)
+        .max_by_key(|ap| ap.signal)
+        .ok_or_else(|| zbus::Error::Failure(format!("Network {} not found", ssid)))?;
+
+    // Reuse the saved profile so that NM doesn't pile up duplicate connections
+    if let Some(saved) = saved_connections(conn).await?.get(ssid.as_bytes()) {
+        // A new password replaces the saved one, which may be outdated
+        if password.is_some() {
+            if let Some(security) = ap.key_mgmt.security_settings(password)? {
+                update_security(conn, saved, security).await?;
+            }
+        }
+
+        nm.activate_connection(
+            &ObjectPath::from(saved),
+            &ObjectPath::from(&device),
+            &ObjectPath::from(&ap.path),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut wireless = HashMap::new();
+    wireless.insert("ssid", DBusValue::from(ssid.as_bytes().to_vec()));
+
+    let mut connection = HashMap::new();
+    connection.insert("802-11-wireless", wireless);
+    if let Some(security) = ap.key_mgmt.security_settings(password)? {
+        connection.insert("802-11-wireless-security", security);
+    }
+
+    nm.add_and_activate_connection(
+        connection,
+        &ObjectPath::from(&device),
+        &ObjectPath::from(&ap.path),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Writes `security` into the saved profile at `path`, keeping its other settings.
+async fn update_security(
+    conn: &Connection,
+    path: &OwnedObjectPath,
+    security: HashMap<&str, DBusValue<'_>>,
+) -> zbus::Result<()> {
+    let connection: SettingsConnectionProxy = proxy(conn, ObjectPath::from(path)).await?;
+    let mut settings = connection.get_settings().await?;
+
+    let section = settings.entry("802-11-wireless-security".to_string()).or_default();
+    for (key, value) in security {
+        section.insert(key.to_string(), value.try_to_owned()?);
+    }
+
+    connection.update(settings).await
+}
+
+async fn disconnect_device(conn: &Connection) -> zbus::Result<()> {
+    let device: DeviceProxy = proxy(conn, first_wifi_device(conn).await?.into()).await?;
+    device.disconnect().await
+}
+
+/// Streams the wifi state as JSON. Used by `Listen { source = "wifi" }`.
+pub async fn stream_state(
+    tx: mpsc::Sender<String>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> zbus::Result<()> {
+    let conn = Connection::system().await?;
+
+    let rule = MatchRule::builder()
+        .msg_type(message::Type::Signal)
+        .sender(NM_SERVICE)?
+        .path_namespace(NM_PATH)?
+        .build();
+    let mut nm_signals = MessageStream::for_match_rule(rule, &conn, None).await?;
+
+    loop {
+        // A failed read (e.g. an access point vanishing mid-scan) is retried on the next signal
+        match read_state(&conn).await {
+            Ok(state) => {
+                if tx.send(state.to_string()).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => log::warn!("Failed to read the wifi state: {}", e),
+        }
+
+        tokio::select! {
+            Some(_) = nm_signals.next() => {
+                // Coalesce bursts of signals (e.g. a finished scan) into one update
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                while let Some(Some(_)) = nm_signals.next().now_or_never() {}
+            }
+            res = shutdown_rx.changed() => {
+                if res.is_err() || *shutdown_rx.borrow() {
+                    break;
+                }
+            }
         }
     }
-    Ok(Value::Map(map))
+
+    Ok(())
+}
+
+pub fn scan(_args: Vec<Value>) -> Result<Value> {
+    let networks = with_system_bus(|conn| async move {
+        for device_path in wifi_devices(&conn).await? {
+            let wireless: WirelessProxy = proxy(&conn, device_path.into()).await?;
+            // NM rejects scans requested too often, the cached results are still fine
+            if let Err(e) = wireless.request_scan(HashMap::new()).await {
+                log::debug!("Wifi scan request was rejected: {}", e);
+            }
+        }
+
+        access_points(&conn).await
+    })
+    .map_err(|e| runtime_err!("Failed to scan wifi networks: {e}"))?;
+
+    Ok(Value::List(networks.iter().map(AccessPointInfo::to_value).collect()))
+}
+
+pub fn current_connection(_args: Vec<Value>) -> Result<Value> {
+    let networks = with_system_bus(|conn| async move { access_points(&conn).await })
+        .map_err(|e| runtime_err!("Failed to read wifi networks: {e}"))?;
+
+    match networks.iter().find(|ap| ap.active) {
+        Some(ap) => Ok(ap.to_value()),
+        None => Ok(Value::Map(Vec::new())),
+    }
 }
 
 // ssid: &str, password: &str
@@ -55,16 +529,10 @@ pub fn connect(args: Vec<Value>) -> Result<Value> {
         return Err(runtime_err!("PASSWORD must be a string"));
     };
 
-    let args = vec!["dev", "wifi", "connect", ssid, "password", password];
-    let status = Command::new("nmcli")
-        .args(&args)
-        .status()
-        .map_err(|e| runtime_err!("Failed to run nmcli: {e}"))?;
-    if status.success() {
-        Ok(Value::Null)
-    } else {
-        Err(runtime_err!("Failed to connect to {}", ssid))
-    }
+    with_system_bus(|conn| async move { connect_to(&conn, ssid, Some(password)).await })
+        .map_err(|e| runtime_err!("Failed to connect to {}: {e}", ssid))?;
+
+    Ok(Value::Null)
 }
 
 // ssid: &str
@@ -73,82 +541,339 @@ pub fn connect_without_password(args: Vec<Value>) -> Result<Value> {
         return Err(runtime_err!("SSID must be a string"));
     };
 
-    let args = vec!["dev", "wifi", "connect", ssid];
-    let status = Command::new("nmcli")
-        .args(&args)
-        .status()
-        .map_err(|e| runtime_err!("Failed to run nmcli: {e}"))?;
-    if status.success() {
-        Ok(Value::Null)
-    } else {
-        Err(runtime_err!("Failed to connect to {}", ssid))
-    }
+    with_system_bus(|conn| async move { connect_to(&conn, ssid, None).await })
+        .map_err(|e| runtime_err!("Failed to connect to {}: {e}", ssid))?;
+
+    Ok(Value::Null)
 }
 
 pub fn disconnect(_args: Vec<Value>) -> Result<Value> {
-    use std::process::Command;
+    with_system_bus(|conn| async move { disconnect_device(&conn).await })
+        .map_err(|e| runtime_err!("Failed to disconnect: {e}"))?;
 
-    // Get current active SSID
-    let output = Command::new("nmcli")
-        .args(["-t", "-f", "active,ssid", "dev", "wifi"])
-        .output()
-        .map_err(|e| runtime_err!("Failed to run nmcli: {e}"))?;
+    Ok(Value::Null)
+}
 
-    let stdout = String::from_utf8(output.stdout)
-        .map_err(|e| runtime_err!("Invalid UTF-8 from nmcli: {e}"))?;
+pub fn disable_adapter(_args: Vec<Value>) -> Result<Value> {
+    with_system_bus(|conn| async move {
+        network_manager(&conn).await?.set_wireless_enabled(false).await
+    })
+    .map_err(|e| runtime_err!("Failed to disable adapter: {e}"))?;
 
-    let ssid = stdout
-        .lines()
-        .find(|line| line.starts_with("yes:"))
-        .and_then(|line| line.split(':').nth(1))
-        .ok_or(runtime_err!("No active Wi-Fi connection"))?;
+    Ok(Value::Null)
+}
 
-    let status = Command::new("nmcli")
-        .args(["connection", "down", "id", ssid])
-        .status()
-        .map_err(|e| runtime_err!("Failed to disconnect from {}: {e}", ssid))?;
+pub fn enable_adapter(_args: Vec<Value>) -> Result<Value> {
+    with_system_bus(|conn| async move {
+        network_manager(&conn).await?.set_wireless_enabled(true).await
+    })
+    .map_err(|e| runtime_err!("Failed to enable adapter: {e}"))?;
 
-    if status.success() {
-        Ok(Value::Null)
-    } else {
-        Err(runtime_err!("Failed to disconnect from {}", ssid))
-    }
+    Ok(Value::Null)
 }
 
-pub fn disable_adapter(_args: Vec<Value>) -> Result<Value> {
-    let status = Command::new("nmcli")
-        .args(["networking", "off"])
-        .status()
-        .map_err(|e| runtime_err!("Failed to run nmcli: {e}"))?;
-    if status.success() {
-        Ok(Value::Null)
-    } else {
-        Err(runtime_err!("Failed to disable adapter"))
-    }
+pub fn get_adapter_connectivity(_args: Vec<Value>) -> Result<Value> {
+    let connectivity =
+        with_system_bus(|conn| async move { network_manager(&conn).await?.connectivity().await })
+            .map_err(|e| runtime_err!("Failed to get connectivity: {e}"))?;
+
+    Ok(Value::Str(connectivity_label(connectivity).to_string()))
 }
 
-pub fn enable_adapter(_args: Vec<Value>) -> Result<Value> {
-    let status = Command::new("nmcli")
-        .args(["networking", "on"])
-        .status()
-        .map_err(|e| runtime_err!("Failed to run nmcli: {e}"))?;
-    if status.success() {
-        Ok(Value::Null)
-    } else {
-        Err(runtime_err!("Failed to enable"))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libraries::apilib::mock_bus;
+    use std::sync::{Arc, Mutex};
+    use zbus::{interface, zvariant::Value};
+
+    const DEVICE_PATH: &str = "/org/freedesktop/NetworkManager/Devices/1";
+    const HOME_AP_PATH: &str = "/org/freedesktop/NetworkManager/AccessPoint/1";
+    const CAFE_AP_PATH: &str = "/org/freedesktop/NetworkManager/AccessPoint/2";
+    const SAVED_PATH: &str = "/org/freedesktop/NetworkManager/Settings/1";
+
+    fn path(p: &str) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(p).unwrap()
     }
-}
 
-pub fn get_adapter_connectivity(_args: Vec<Value>) -> Result<Value> {
-    let output = Command::new("nmcli")
-        .args(["networking", "connectivity"])
-        .output()
-        .map_err(|e| runtime_err!("Failed to run nmcli: {e}"))?;
-
-    if output.status.success() {
-        let connectivity = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        Ok(Value::Str(connectivity))
-    } else {
-        Err(runtime_err!("Failed to get connectivity"))
+    #[derive(Default)]
+    struct MockNetworkManager {
+        wireless_enabled: bool,
+        activated: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[interface(name = "org.freedesktop.NetworkManager")]
+    impl MockNetworkManager {
+        fn get_devices(&self) -> Vec<OwnedObjectPath> {
+            vec![path(DEVICE_PATH)]
+        }
+
+        fn activate_connection(
+            &self,
+            connection: ObjectPath<'_>,
+            _device: ObjectPath<'_>,
+            _specific_object: ObjectPath<'_>,
+        ) -> OwnedObjectPath {
+            self.activated.lock().unwrap().push(connection.to_string());
+            path("/org/freedesktop/NetworkManager/ActiveConnection/1")
+        }
+
+        fn add_and_activate_connection(
+            &self,
+            connection: HashMap<String, HashMap<String, OwnedValue>>,
+            _device: ObjectPath<'_>,
+            specific_object: ObjectPath<'_>,
+        ) -> (OwnedObjectPath, OwnedObjectPath) {
+            let key_mgmt = connection
+                .get("802-11-wireless-security")
+                .and_then(|s| s.get("key-mgmt"))
+                .and_then(|k| <&str>::try_from(k).ok())
+                .unwrap_or_default();
+            self.activated.lock().unwrap().push(format!("new {specific_object} {key_mgmt}"));
+            (path(SAVED_PATH), path("/org/freedesktop/NetworkManager/ActiveConnection/1"))
+        }
+
+        #[zbus(property)]
+        fn wireless_enabled(&self) -> bool {
+            self.wireless_enabled
+        }
+
+        #[zbus(property)]
+        fn set_wireless_enabled(&mut self, value: bool) {
+            self.wireless_enabled = value;
+        }
+
+        #[zbus(property)]
+        fn connectivity(&self) -> u32 {
+            4
+        }
+    }
+
+    struct MockDevice;
+
+    #[interface(name = "org.freedesktop.NetworkManager.Device")]
+    impl MockDevice {
+        fn disconnect(&self) {}
+
+        #[zbus(property)]
+        fn device_type(&self) -> u32 {
+            NM_DEVICE_TYPE_WIFI
+        }
+    }
+
+    struct MockWireless;
+
+    #[interface(name = "org.freedesktop.NetworkManager.Device.Wireless")]
+    impl MockWireless {
+        fn get_all_access_points(&self) -> Vec<OwnedObjectPath> {
+            vec![path(CAFE_AP_PATH), path(HOME_AP_PATH)]
+        }
+
+        fn request_scan(&self, _options: HashMap<String, OwnedValue>) {}
+
+        #[zbus(property)]
+        fn active_access_point(&self) -> OwnedObjectPath {
+            path(HOME_AP_PATH)
+        }
+    }
+
+    struct MockAccessPoint {
+        ssid: &'static str,
+        bssid: &'static str,
+        strength: u8,
+        frequency: u32,
+        rsn_flags: u32,
+    }
+
+    #[interface(name = "org.freedesktop.NetworkManager.AccessPoint")]
+    impl MockAccessPoint {
+        #[zbus(property)]
+        fn ssid(&self) -> Vec<u8> {
+            self.ssid.as_bytes().to_vec()
+        }
+
+        #[zbus(property)]
+        fn hw_address(&self) -> String {
+            self.bssid.to_string()
+        }
+
+        #[zbus(property)]
+        fn strength(&self) -> u8 {
+            self.strength
+        }
+
+        #[zbus(property)]
+        fn frequency(&self) -> u32 {
+            self.frequency
+        }
+
+        #[zbus(property)]
+        fn flags(&self) -> u32 {
+            if self.rsn_flags == 0 {
+                0
+            } else {
+                NM_AP_FLAGS_PRIVACY
+            }
+        }
+
+        #[zbus(property)]
+        fn wpa_flags(&self) -> u32 {
+            0
+        }
+
+        #[zbus(property)]
+        fn rsn_flags(&self) -> u32 {
+            self.rsn_flags
+        }
+    }
+
+    struct MockSettings;
+
+    #[interface(name = "org.freedesktop.NetworkManager.Settings")]
+    impl MockSettings {
+        fn list_connections(&self) -> Vec<OwnedObjectPath> {
+            vec![path(SAVED_PATH)]
+        }
+    }
+
+    struct MockSavedConnection {
+        activated: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[interface(name = "org.freedesktop.NetworkManager.Settings.Connection")]
+    impl MockSavedConnection {
+        fn get_settings(&self) -> HashMap<String, HashMap<String, OwnedValue>> {
+            let owned = |v: Value<'_>| v.try_to_owned().unwrap();
+
+            HashMap::from([
+                (
+                    "connection".to_string(),
+                    HashMap::from([("type".to_string(), owned(Value::from("802-11-wireless")))]),
+                ),
+                (
+                    "802-11-wireless".to_string(),
+                    HashMap::from([("ssid".to_string(), owned(Value::from(b"Home:Net".to_vec())))]),
+                ),
+            ])
+        }
+
+        fn update(&self, properties: HashMap<String, HashMap<String, OwnedValue>>) {
+            let security = &properties["802-11-wireless-security"];
+            let get = |key: &str| <&str>::try_from(&security[key]).unwrap().to_string();
+            let kept_ssid = properties["802-11-wireless"].contains_key("ssid");
+            let update = format!("update {} {} {}", get("key-mgmt"), get("psk"), kept_ssid);
+            self.activated.lock().unwrap().push(update);
+        }
+    }
+
+    /// Serves a fake NetworkManager on a private peer-to-peer bus and
+    /// returns the client side along with the activation log.
+    async fn mock_nm() -> (Connection, Connection, Arc<Mutex<Vec<String>>>) {
+        let activated = Arc::new(Mutex::new(Vec::new()));
+
+        let home = MockAccessPoint {
+            ssid: "Home:Net",
+            bssid: "AA:BB:CC:DD:EE:01",
+            strength: 70,
+            frequency: 5180,
+            rsn_flags: 0x188,
+        };
+        let cafe = MockAccessPoint {
+            ssid: "Cafe",
+            bssid: "AA:BB:CC:DD:EE:02",
+            strength: 90,
+            frequency: 2412,
+            rsn_flags: 0,
+        };
+
+        let nm = MockNetworkManager { wireless_enabled: true, activated: activated.clone() };
+        let (server, client) = mock_bus(NM_PATH, nm).await;
+
+        let objects = server.object_server();
+        objects.at(DEVICE_PATH, MockDevice).await.unwrap();
+        objects.at(DEVICE_PATH, MockWireless).await.unwrap();
+        objects.at(HOME_AP_PATH, home).await.unwrap();
+        objects.at(CAFE_AP_PATH, cafe).await.unwrap();
+        objects.at("/org/freedesktop/NetworkManager/Settings", MockSettings).await.unwrap();
+        let saved = MockSavedConnection { activated: activated.clone() };
+        objects.at(SAVED_PATH, saved).await.unwrap();
+
+        (server, client, activated)
+    }
+
+    #[test]
+    fn test_security_label() {
+        assert_eq!(security_label(0, 0, 0), "");
+        assert_eq!(security_label(NM_AP_FLAGS_PRIVACY, 0, 0), "WEP");
+        assert_eq!(security_label(NM_AP_FLAGS_PRIVACY, 0x188, 0x188), "WPA1 WPA2");
+        assert_eq!(security_label(NM_AP_FLAGS_PRIVACY, 0, 0x400), "WPA3");
+        assert_eq!(security_label(NM_AP_FLAGS_PRIVACY, 0, 0x200), "WPA2 802.1X");
+    }
+
+    #[test]
+    fn test_key_mgmt() {
+        assert_eq!(KeyMgmt::from_flags(0, 0, 0), KeyMgmt::Open);
+        assert_eq!(KeyMgmt::from_flags(0, 0, 0x800), KeyMgmt::Owe);
+        assert_eq!(KeyMgmt::from_flags(NM_AP_FLAGS_PRIVACY, 0, 0), KeyMgmt::Wep);
+        assert_eq!(KeyMgmt::from_flags(NM_AP_FLAGS_PRIVACY, 0x188, 0), KeyMgmt::Psk);
+        assert_eq!(KeyMgmt::from_flags(NM_AP_FLAGS_PRIVACY, 0, 0x400), KeyMgmt::Sae);
+        assert_eq!(KeyMgmt::from_flags(NM_AP_FLAGS_PRIVACY, 0, 0x500), KeyMgmt::Psk);
+        assert_eq!(KeyMgmt::from_flags(NM_AP_FLAGS_PRIVACY, 0, 0x200), KeyMgmt::Enterprise);
+
+        let security = KeyMgmt::Sae.security_settings(Some("secret")).unwrap().unwrap();
+        assert_eq!(security["key-mgmt"], Value::from("sae"));
+        assert_eq!(security["psk"], Value::from("secret"));
+        assert!(KeyMgmt::Open.security_settings(Some("secret")).unwrap().is_none());
+        assert!(KeyMgmt::Enterprise.security_settings(None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_access_points() {
+        let (_server, conn, _) = mock_nm().await;
+        let networks = access_points(&conn).await.unwrap();
+
+        assert_eq!(networks.len(), 2);
+        // the active network comes first, even with a weaker signal
+        assert_eq!(networks[0].ssid, "Home:Net");
+        assert_eq!(networks[0].bssid, "AA:BB:CC:DD:EE:01");
+        assert_eq!(networks[0].frequency, 5180);
+        assert_eq!(networks[0].security, "WPA2");
+        assert!(networks[0].saved && networks[0].active);
+        assert_eq!(networks[1].ssid, "Cafe");
+        assert!(!networks[1].saved && !networks[1].active);
+    }
+
+    #[tokio::test]
+    async fn test_read_state() {
+        let (_server, conn, _) = mock_nm().await;
+        let state = read_state(&conn).await.unwrap();
+
+        assert_eq!(state["connected"], true);
+        assert_eq!(state["ssid"], "Home:Net");
+        assert_eq!(state["connectivity"], "full");
+        assert_eq!(state["networks"].as_array().unwrap().len(), 2);
+
+        network_manager(&conn).await.unwrap().set_wireless_enabled(false).await.unwrap();
+        assert_eq!(read_state(&conn).await.unwrap()["enabled"], false);
+    }
+
+    #[tokio::test]
+    async fn test_connect() {
+        let (_server, conn, activated) = mock_nm().await;
+
+        connect_to(&conn, "Home:Net", None).await.unwrap();
+        connect_to(&conn, "Home:Net", Some("new-secret")).await.unwrap();
+        connect_to(&conn, "Cafe", Some("ignored")).await.unwrap();
+        connect_to(&conn, "Office", Some("secret")).await.unwrap_err();
+
+        assert_eq!(
+            *activated.lock().unwrap(),
+            vec![
+                SAVED_PATH.to_string(),
+                "update wpa-psk new-secret true".to_string(),
+                SAVED_PATH.to_string(),
+                format!("new {CAFE_AP_PATH} "),
+            ]
+        );
     }
 }
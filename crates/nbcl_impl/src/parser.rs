@@ -1,8 +1,10 @@
+use crate::errors::SourceDiagnostic;
 use crate::{builtins, errors, libraries, translate};
 use anyhow::{anyhow, Result};
 use ewwii_plugin_api::IpcRequest;
 use ewwii_shared_utils::ast::WidgetNode;
 use ewwii_shared_utils::prop::Callback;
+use ewwii_shared_utils::schema::PropErrors;
 use nbcl::{context::EvalContext, NbclEngine, Value};
 use tokio::sync::mpsc::UnboundedSender;
 
@@ -41,10 +43,52 @@ impl NbclConfigParser {
         self.ctx = Some(eval_ctx);
 
         // translate the tree
-        let wnode = WidgetNode::Tree(translate::to_widgetnode(tree.root_nodes)?);
+        let nodes = translate::to_widgetnode(tree.root_nodes, code)
+            .map_err(|e| errors::handle_translate_err(e, code, file_id))?;
+        let wnode = WidgetNode::Tree(nodes);
         Ok(wnode.setup_dyn_ids("root"))
     }
 
+    /// Check `code` without keeping anything from it, returning every error
+    /// found with its location. Only parses unless `evaluate` is set.
+    pub fn diagnose(&self, code: &str, evaluate: bool) -> Vec<SourceDiagnostic> {
+        let source_ast = match self.engine.parse_str(code) {
+            Ok(ast) => ast,
+            Err(e) => return vec![errors::nbcl_err_to_diagnostic(e, code)],
+        };
+
+        if !evaluate {
+            return Vec::new();
+        }
+
+        let mut eval_ctx = EvalContext::from(&self.engine);
+        let tree = match self.engine.eval_ast_with_eval_ctx(source_ast, &mut eval_ctx) {
+            Ok(tree) => tree,
+            Err(e) => {
+                let mut diag = errors::nbcl_err_to_diagnostic(e, code);
+                // Spans of errors inside imported files point into those files
+                if let Some(file) = eval_ctx.get_current_file() {
+                    diag.message = format!("{} (in {})", diag.message, file.display());
+                    diag.range = None;
+                }
+                return vec![diag];
+            }
+        };
+
+        match translate::to_widgetnode(tree.root_nodes, code)
+            .map_err(|e| e.downcast::<PropErrors>())
+        {
+            Ok(_) => Vec::new(),
+            Err(Ok(PropErrors(errs))) => errs.iter().map(errors::prop_err_to_diagnostic).collect(),
+            Err(Err(e)) => vec![SourceDiagnostic {
+                label: "Error",
+                message: e.to_string(),
+                hint: None,
+                range: None,
+            }],
+        }
+    }
+
     pub fn eval_code_snippet(&mut self, code: &str) -> Result<WidgetNode> {
         let tree = self
             .engine
@@ -52,7 +96,8 @@ impl NbclConfigParser {
             .map_err(|e| anyhow!(errors::handle_nbcl_err(e, code, Some("<dyn_eval>"), None)))?;
 
         // translate the tree
-        let mut all_nodes = translate::to_widgetnode(tree.root_nodes)?;
+        let mut all_nodes = translate::to_widgetnode(tree.root_nodes, code)
+            .map_err(|e| errors::handle_translate_err(e, code, Some("<dyn_eval>")))?;
 
         if all_nodes.len() != 1 {
             anyhow::bail!(
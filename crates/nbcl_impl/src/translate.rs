@@ -1,93 +1,129 @@
 use anyhow::{Context, Result};
 use ewwii_shared_utils::ast::WidgetNode;
 use ewwii_shared_utils::prop::{Property, PropertyMap};
+use ewwii_shared_utils::schema::{self, PropError, PropErrors};
 use nbcl::ast::resolved::ResolvedNode;
+use nbcl::error::Span;
+use nbcl::Value;
+use std::collections::HashMap;
+use std::ops::Range;
 
 macro_rules! handle_primitive {
-    ($variant:ident, $node:expr) => {{
-        let mut props = PropertyMap::from_nbcl($node.props);
+    ($variant:ident, $node:expr, $code:expr, $errors:expr) => {{
+        let (mut props, spans) = split_props($node.props);
         if let Some(id) = $node.id {
             props.insert("widget_name", Property::String(id));
         }
+        let found = schema::validate_props(stringify!($variant), &props);
+        $errors.extend(locate(found, &spans, &$node.span, $code));
         WidgetNode::$variant { props }
     }};
 }
 
 macro_rules! handle_with_children {
-    ($variant:ident, $node:expr) => {{
-        let mut props = PropertyMap::from_nbcl($node.props);
-        let children = to_widgetnode($node.children)?;
+    ($variant:ident, $node:expr, $code:expr, $errors:expr) => {{
+        let (mut props, spans) = split_props($node.props);
+        let children = translate_nodes($node.children, $code, $errors)?;
         if let Some(id) = $node.id {
             props.insert("widget_name", Property::String(id));
         }
+        let found = schema::validate_props(stringify!($variant), &props);
+        $errors.extend(locate(found, &spans, &$node.span, $code));
         WidgetNode::$variant { props, children }
     }};
 }
 
-pub fn to_widgetnode(nodes: Vec<ResolvedNode>) -> Result<Vec<WidgetNode>> {
+/// Translates the nodes evaluated from `code`, failing with [`PropErrors`]
+/// if any widget has invalid properties.
+pub fn to_widgetnode(nodes: Vec<ResolvedNode>, code: &str) -> Result<Vec<WidgetNode>> {
+    let mut errors = Vec::new();
+    let widgets = translate_nodes(nodes, code, &mut errors)?;
+
+    if !errors.is_empty() {
+        return Err(PropErrors(errors).into());
+    }
+
+    Ok(widgets)
+}
+
+fn translate_nodes(
+    nodes: Vec<ResolvedNode>,
+    code: &str,
+    errors: &mut Vec<PropError>,
+) -> Result<Vec<WidgetNode>> {
     let mut widgets = Vec::new();
 
     for node in nodes {
         let widget = match node.type_name.as_ref() {
             // Primitives
-            "Label" => handle_primitive!(Label, node),
-            "Button" => handle_primitive!(Button, node),
-            "Image" => handle_primitive!(Image, node),
-            "Input" => handle_primitive!(Input, node),
-            "Progress" => handle_primitive!(Progress, node),
-            "ComboBoxText" => handle_primitive!(ComboBoxText, node),
-            "Scale" => handle_primitive!(Scale, node),
-            "Checkbox" => handle_primitive!(Checkbox, node),
-            "Calendar" => handle_primitive!(Calendar, node),
-            "Graph" => handle_primitive!(Graph, node),
-            "Transform" => handle_primitive!(Transform, node),
-            "CircularProgress" => handle_primitive!(CircularProgress, node),
-            "ColorButton" => handle_primitive!(ColorButton, node),
-            "ColorChooser" => handle_primitive!(ColorChooser, node),
+            "Label" => handle_primitive!(Label, node, code, errors),
+            "Button" => handle_primitive!(Button, node, code, errors),
+            "Image" => handle_primitive!(Image, node, code, errors),
+            "Input" => handle_primitive!(Input, node, code, errors),
+            "Progress" => handle_primitive!(Progress, node, code, errors),
+            "ComboBoxText" => handle_primitive!(ComboBoxText, node, code, errors),
+            "Scale" => handle_primitive!(Scale, node, code, errors),
+            "Checkbox" => handle_primitive!(Checkbox, node, code, errors),
+            "Calendar" => handle_primitive!(Calendar, node, code, errors),
+            "Graph" => handle_primitive!(Graph, node, code, errors),
+            "Transform" => handle_primitive!(Transform, node, code, errors),
+            "CircularProgress" => handle_primitive!(CircularProgress, node, code, errors),
+            "ColorButton" => handle_primitive!(ColorButton, node, code, errors),
+            "ColorChooser" => handle_primitive!(ColorChooser, node, code, errors),
 
             // w/ children
-            "Box" => handle_with_children!(Box, node),
-            "FlowBox" => handle_with_children!(FlowBox, node),
-            "Expander" => handle_with_children!(Expander, node),
-            "Revealer" => handle_with_children!(Revealer, node),
-            "Scroll" => handle_with_children!(Scroll, node),
-            "OverLay" => handle_with_children!(OverLay, node),
-            "AspectFrame" => handle_with_children!(AspectFrame, node),
-            "Stack" => handle_with_children!(Stack, node),
-            "EventBox" => handle_with_children!(EventBox, node),
-            "ToolTip" => handle_with_children!(ToolTip, node),
-            "Animation" => handle_with_children!(Animation, node),
+            "Box" => handle_with_children!(Box, node, code, errors),
+            "FlowBox" => handle_with_children!(FlowBox, node, code, errors),
+            "Expander" => handle_with_children!(Expander, node, code, errors),
+            "Revealer" => handle_with_children!(Revealer, node, code, errors),
+            "Scroll" => handle_with_children!(Scroll, node, code, errors),
+            "OverLay" => handle_with_children!(OverLay, node, code, errors),
+            "AspectFrame" => handle_with_children!(AspectFrame, node, code, errors),
+            "Stack" => handle_with_children!(Stack, node, code, errors),
+            "EventBox" => handle_with_children!(EventBox, node, code, errors),
+            "ToolTip" => handle_with_children!(ToolTip, node, code, errors),
+            "Animation" => handle_with_children!(Animation, node, code, errors),
 
             // Special
-            "GtkUI" => handle_primitive!(GtkUI, node),
-            "Custom" => handle_with_children!(Custom, node),
+            "GtkUI" => handle_primitive!(GtkUI, node, code, errors),
+            "Custom" => handle_with_children!(Custom, node, code, errors),
 
             // Main
             "Poll" => {
                 let name = node.id.with_context(|| "Poll has no <id>".to_string())?;
-                let props = PropertyMap::from_nbcl(node.props);
+                let props = split_props(node.props).0;
 
                 WidgetNode::Poll { var: name, props }
             }
 
             "Listen" => {
                 let name = node.id.with_context(|| "Listen has no <id>".to_string())?;
-                let props = PropertyMap::from_nbcl(node.props);
+                let props = split_props(node.props).0;
 
                 WidgetNode::Listen { var: name, props }
             }
 
             "Script" => {
-                let props = PropertyMap::from_nbcl(node.props);
+                let props = split_props(node.props).0;
                 WidgetNode::Script { props }
             }
 
+            "CssVar" => {
+                let name = node.id.with_context(|| "CssVar has no <id>".to_string())?;
+                if !name.starts_with("--") {
+                    anyhow::bail!("CssVar name `{name}` must start with `--`");
+                }
+                let props = split_props(node.props).0;
+
+                WidgetNode::CssVar { name, props }
+            }
+
             "Window" => {
                 let name = node.id.with_context(|| "Window has no <id>".to_string())?;
-                let props = PropertyMap::from_nbcl(node.props);
+                let props = split_props(node.props).0;
                 // we ensured that only 1 child is
                 // provided in builtins.rs file.
-                let child = to_widgetnode(node.children)?.remove(0);
+                let child = translate_nodes(node.children, code, errors)?.remove(0);
 
                 WidgetNode::DefWindow { name, props, node: Box::new(child) }
             }
@@ -100,3 +136,52 @@ pub fn to_widgetnode(nodes: Vec<ResolvedNode>) -> Result<Vec<WidgetNode>> {
 
     Ok(widgets)
 }
+
+/// Splits evaluated props into a property map and the span of each value.
+fn split_props(props: HashMap<String, (Value, Span)>) -> (PropertyMap, HashMap<String, Span>) {
+    let mut spans = HashMap::new();
+    let values = props
+        .into_iter()
+        .map(|(key, (value, span))| {
+            spans.insert(key.clone(), span);
+            (key, value)
+        })
+        .collect();
+
+    (PropertyMap::from_nbcl(values), spans)
+}
+
+/// Points each error at its value, or at the widget name when the property
+/// is missing.
+fn locate(
+    errors: Vec<PropError>,
+    spans: &HashMap<String, Span>,
+    node_span: &Span,
+    code: &str,
+) -> Vec<PropError> {
+    let node_range = range_in(code, node_span).map(|range| {
+        let name_len = code[range.clone()].find(|c: char| !c.is_alphanumeric() && c != '_');
+        range.start..range.start + name_len.unwrap_or(range.len())
+    });
+
+    errors
+        .into_iter()
+        .map(|mut error| {
+            error.range = if error.missing {
+                node_range.clone()
+            } else {
+                spans.get(&error.prop).and_then(|span| range_in(code, span))
+            };
+            error
+        })
+        .collect()
+}
+
+/// Range of `span` in `code`, without the whitespace after it. Nodes and
+/// props from imported files have spans into those files, which do not
+/// match `code` and are left out.
+fn range_in(code: &str, span: &Span) -> Option<Range<usize>> {
+    let slice = span.slice.trim_end();
+    let matches = code.get(span.start..span.end) == Some(span.slice.as_str());
+    (matches && !slice.is_empty()).then(|| span.start..span.start + slice.len())
+}
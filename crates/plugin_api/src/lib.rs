@@ -255,6 +255,56 @@ pub trait EwwiiAPI: Send + Sync {
     /// ```
     fn register_library(&self, name: &str, items: Vec<LibraryItem>);
 
+    /// Expose a function that templates can call.
+    ///
+    /// Template functions are called synchronously every time a template using
+    /// them is re-evaluated, so handlers should be cheap. Arguments that look like
+    /// numbers or booleans are passed as [`PluginValue::Int`], [`PluginValue::Float`]
+    /// or [`PluginValue::Bool`], everything else as [`PluginValue::String`].
+    /// Returning an error makes the template evaluation fail with that message.
+    ///
+    /// Builtin template functions such as `round` cannot be overridden. Hosts
+    /// that do not implement this only warn that the function was skipped.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use ewwii_plugin_api::{
+    ///     auto_plugin, PluginInfo,
+    ///     NativeFn, NativeFnExt, PluginValue
+    /// };
+    ///
+    /// auto_plugin!(
+    ///     DummyStructure,
+    ///     PluginInfo::new("test.example.template", "1.0.0"),
+    ///     host,
+    ///     {
+    ///         host.register_template_function(
+    ///             "euro",    // function name
+    ///             1,         // number of arguments
+    ///             NativeFn::new(|args| {
+    ///                 let amount = match args[0] {
+    ///                     PluginValue::Int(i) => i as f64,
+    ///                     PluginValue::Float(f) => f,
+    ///                     _ => return Err("euro() expects a number".to_string()),
+    ///                 };
+    ///
+    ///                 Ok(PluginValue::String(format!("{:.2} €", amount)))
+    ///         }));
+    ///     }
+    /// );
+    /// ```
+    ///
+    /// ## Example use in a template
+    ///
+    /// ```js
+    /// text = global("balance").template("Balance: {euro(balance)}")
+    /// ```
+    fn register_template_function(&self, name: &str, arity: usize, handler: NativeFn) {
+        let _ = (arity, handler);
+        self.warn(&format!("Template function `{}` is not supported by this host", name));
+    }
+
     /// Replace nbcl with a custom configuration engine.
     ///
     /// # Example
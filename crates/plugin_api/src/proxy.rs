@@ -32,6 +32,7 @@ impl<T> ManualHandleExt<T> for ManualHandle<T> {
 /// Represents the different types of callbacks that can be registered by a plugin.
 pub enum CallbackHandler {
     NativeFn(NativeFn),
+    TemplateFn(NativeFn),
     ParseFn(ParseFn),
     ListenHandleFn(ListenHandleFn),
     SignalUpdateFn(SignalUpdateFn),
@@ -106,6 +107,14 @@ pub enum PluginRequest {
 
     // Handlers
     ConfigCallbackHandle(u64),
+
+    // Added after the rest, so the requests of older plugins keep their discriminants
+    RegisterTemplateFn {
+        id: String,
+        name: String,
+        arity: usize,
+        callback_id: u64,
+    },
 }
 
 // This is provided on the host side
@@ -132,6 +141,15 @@ pub unsafe extern "C" fn plugin_callback_handler(
             let result = f(args).unwrap_or(PluginValue::Null);
             bincode::serialize(&CallbackResponse::PluginValue(result)).unwrap_or_default()
         }
+        Some(CallbackHandler::TemplateFn(f)) => {
+            // Errors are passed on so the template can report them
+            let args: Vec<PluginValue> = bincode::deserialize(bytes).unwrap_or_default();
+            let response = match f(args) {
+                Ok(value) => CallbackResponse::PluginValue(value),
+                Err(e) => CallbackResponse::Error(PluginError::Internal(e)),
+            };
+            bincode::serialize(&response).unwrap_or_default()
+        }
         Some(CallbackHandler::ParseFn(f)) => {
             let (source, path): (String, String) = bincode::deserialize(bytes).unwrap_or_default();
             match f(&source, &path) {
@@ -319,6 +337,22 @@ impl EwwiiAPI for HostProxy {
         self.call_host(req)
     }
 
+    fn register_template_function(&self, name: &str, arity: usize, handler: NativeFn) {
+        // Register id
+        let id = rand::random::<u64>();
+        get_callbacks().lock().unwrap().insert(id, CallbackHandler::TemplateFn(handler));
+
+        // Send request
+        let req = PluginRequest::RegisterTemplateFn {
+            id: self.get_id().to_string(),
+            name: name.to_string(),
+            arity,
+            callback_id: id,
+        };
+
+        self.call_host(req)
+    }
+
     fn register_config_engine(&self, info: ConfigInfo, parser: ParseFn) {
         // Register id
         let id = rand::random::<u64>();
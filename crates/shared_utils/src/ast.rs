@@ -52,6 +52,9 @@ pub enum WidgetNode {
     Poll { var: String, props: PropertyMap },
     Listen { var: String, props: PropertyMap },
     Tree(Vec<WidgetNode>),
+
+    // Added after `Tree`, so nodes sent by older config engines keep their discriminants
+    CssVar { name: String, props: PropertyMap },
 }
 
 pub fn hash_props_and_type(props: &PropertyMap, widget_type_str: &str) -> u64 {
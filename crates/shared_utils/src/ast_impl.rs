@@ -42,7 +42,8 @@ impl WidgetNode {
             | WidgetNode::DefWindow { props, .. }
             | WidgetNode::Poll { props, .. }
             | WidgetNode::Script { props, .. }
-            | WidgetNode::Listen { props, .. } => Some(props),
+            | WidgetNode::Listen { props, .. }
+            | WidgetNode::CssVar { props, .. } => Some(props),
 
             // Variants with no props field
             WidgetNode::Tree(_) => None,
@@ -136,7 +137,7 @@ impl WidgetNode {
                 WidgetNode::Tree(process_children(children, parent_path, "tree"))
             }
 
-            // == Script/Poll/Listen nodes ==
+            // == Script/Poll/Listen/CssVar nodes ==
             WidgetNode::Script { props } => WidgetNode::Script {
                 props: with_dyn_id(props.clone(), &format!("{}_script", parent_path)),
             },
@@ -148,6 +149,10 @@ impl WidgetNode {
                 var: var.clone(),
                 props: with_dyn_id(props.clone(), &format!("{}_listen_{}", parent_path, var)),
             },
+            WidgetNode::CssVar { name, props } => WidgetNode::CssVar {
+                name: name.clone(),
+                props: with_dyn_id(props.clone(), &format!("{}_cssvar_{}", parent_path, name)),
+            },
 
             // == Leaf nodes ==
             node @ WidgetNode::Label { props }
@@ -0,0 +1,140 @@
+//! Easing curves shared by `Animation` sequences and the `transition` of
+//! numeric properties, so both accept the same names.
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// `cubic-bezier(x1, y1, x2, y2)`, like its CSS counterpart.
+    CubicBezier(f32, f32, f32, f32),
+    /// `spring(damping)`, overshooting and settling by the end. Lower damping bounces more.
+    Spring(f32),
+}
+
+impl Easing {
+    /// The easing called `name` in animation sequences and transitions.
+    fn from_name(name: &str) -> Option<Easing> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease-in" => Some(Easing::EaseIn),
+            "ease-out" => Some(Easing::EaseOut),
+            "ease-in-out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+
+    /// A named easing, or one of `cubic-bezier(..)` and `spring(..)`.
+    pub fn parse(raw: &str) -> Option<Easing> {
+        if let Some(easing) = Easing::from_name(raw) {
+            return Some(easing);
+        }
+        if raw == "spring" {
+            return Some(Easing::Spring(0.5));
+        }
+
+        let (name, args) = parse_call(raw)?;
+        match (name, args.as_slice()) {
+            ("cubic-bezier", &[x1, y1, x2, y2]) => {
+                Some(Easing::CubicBezier(x1.clamp(0.0, 1.0), y1, x2.clamp(0.0, 1.0), y2))
+            }
+            ("spring", &[damping]) => Some(Easing::Spring(damping.clamp(0.1, 1.0))),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, t: f32) -> f32 {
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    -1.0 + (4.0 - 2.0 * t) * t
+                }
+            }
+            Easing::CubicBezier(x1, y1, x2, y2) => cubic_bezier(x1, y1, x2, y2, t),
+            Easing::Spring(damping) => spring(damping, t),
+        }
+    }
+}
+
+fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, t: f32) -> f32 {
+    let bezier = |p1: f32, p2: f32, s: f32| {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * s * p1 + 3.0 * inv * s * s * p2 + s * s * s
+    };
+
+    // x only grows with s while x1 and x2 are in [0, 1], so bisecting finds it
+    let (mut lo, mut hi) = (0.0f32, 1.0f32);
+    let mut s = t;
+    for _ in 0..24 {
+        let x = bezier(x1, x2, s);
+        if (x - t).abs() < 1e-5 {
+            break;
+        }
+        if x < t {
+            lo = s;
+        } else {
+            hi = s;
+        }
+        s = (lo + hi) / 2.0;
+    }
+    bezier(y1, y2, s)
+}
+
+/// A damped spring released at 0, stiff enough to be at rest when `t` reaches 1.
+fn spring(damping: f32, t: f32) -> f32 {
+    if t >= 1.0 {
+        return 1.0;
+    }
+
+    // the envelope e^(-damping * omega * t) is down to 0.001 at the end
+    let omega = 1000f32.ln() / damping;
+    if damping >= 1.0 {
+        return 1.0 - (-omega * t).exp() * (1.0 + omega * t);
+    }
+
+    let omega_d = omega * (1.0 - damping * damping).sqrt();
+    let envelope = (-damping * omega * t).exp();
+    1.0 - envelope * ((omega_d * t).cos() + damping * omega / omega_d * (omega_d * t).sin())
+}
+
+/// Split `name(a, b)` into its name and numeric arguments, separated by
+/// commas or spaces. `None` if it is not a call or an argument is not a number.
+pub fn parse_call(raw: &str) -> Option<(&str, Vec<f32>)> {
+    let (name, rest) = raw.split_once('(')?;
+    let args = rest.strip_suffix(')')?;
+    let args = args
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|a| !a.is_empty())
+        .map(|a| a.parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    Some((name.trim(), args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_easings() {
+        let easings = [
+            Easing::parse("ease-in-out").unwrap(),
+            Easing::parse("cubic-bezier(0.4, 0, 0.2, 1)").unwrap(),
+            Easing::parse("spring(0.3)").unwrap(),
+            Easing::parse("spring").unwrap(),
+        ];
+        for easing in easings {
+            assert!(easing.apply(0.0).abs() < 1e-3, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-3, "{:?}", easing);
+        }
+        // a spring overshoots before it settles
+        assert!((0..100).any(|i| Easing::Spring(0.3).apply(i as f32 / 100.0) > 1.0));
+        assert_eq!(Easing::parse("bouncy"), None);
+    }
+}
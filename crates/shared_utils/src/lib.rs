@@ -1,9 +1,12 @@
 pub mod ast;
 mod ast_impl;
+pub mod easing;
 pub mod prop;
 pub mod prop_utils;
+pub mod schema;
 pub mod span;
 pub mod template;
+mod template_functions;
 pub mod variables;
 
 pub use span::*;
@@ -1,4 +1,5 @@
 use super::variables::GlobalVar;
+use crate::easing::Easing;
 use crate::prop::{Callback, Property, PropertyMap};
 use crate::template::TemplateExpr;
 use anyhow::{anyhow, Result};
@@ -249,7 +250,7 @@ pub fn get_vec_string_prop(prop: &Property, key: &str) -> Result<Vec<PropValue<S
         .collect()
 }
 
-fn parse_duration_str(key_str: &str) -> Option<Duration> {
+pub(crate) fn parse_duration_str(key_str: &str) -> Option<Duration> {
     if let Some(num) = key_str.strip_suffix("ms") {
         num.parse::<u64>().ok().map(Duration::from_millis)
     } else if let Some(num) = key_str.strip_suffix("min") {
@@ -277,6 +278,32 @@ pub fn get_duration_prop(prop: &Property, key: &str) -> Result<Duration> {
         .ok_or_else(|| anyhow!("Unsupported duration format: '{}'", key_str))
 }
 
+/// Get a transition like `300ms ease-out` as its duration and easing. The
+/// easing is `linear` when left out and can be anything `Animation` sequences
+/// accept, such as `cubic-bezier(0.4, 0, 0.2, 1)`. Like durations, it cannot be bound.
+pub fn get_transition_prop(prop: &Property, key: &str) -> Result<(Duration, Easing)> {
+    let raw = prop
+        .as_str()
+        .ok_or_else(|| anyhow!("Expected property `{}` to be a transition string", key))?;
+
+    let raw = raw.trim().to_ascii_lowercase();
+    let (duration, easing) = raw.split_once(char::is_whitespace).unwrap_or((&raw, ""));
+    let duration = parse_duration_str(duration)
+        .ok_or_else(|| anyhow!("Unsupported transition duration in '{}'", raw))?;
+    let easing = match easing.trim() {
+        "" => Easing::default(),
+        easing => Easing::parse(easing).ok_or_else(|| {
+            anyhow!(
+                "Unknown easing '{}', expected linear, ease-in, ease-out, ease-in-out, \
+                 cubic-bezier(x1, y1, x2, y2) or spring(damping)",
+                easing
+            )
+        })?,
+    };
+
+    Ok((duration, easing))
+}
+
 // help unwrap the propvalue in cases where a static is expected
 pub fn unwrap_static<T: Default>(key: &str, prop: PropValue<T>) -> T {
     match prop {
@@ -0,0 +1,863 @@
+//! Typed property schemas of the builtin widgets.
+//!
+//! Configs are checked against these right after evaluation so typos and
+//! invalid values are reported with their location instead of being
+//! silently ignored. Runtime updates coming in over IPC go through the
+//! same schema before they reach the widget.
+
+use crate::prop::{Property, PropertyMap};
+use crate::prop_utils::{get_transition_prop, parse_duration_str};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PropType {
+    Bool,
+    Int,
+    Float,
+    String,
+    /// A duration string such as `200ms` or `2s`. Cannot be bound to a variable.
+    Duration,
+    /// A duration and optional easing such as `300ms ease-out`. Cannot be bound to a variable.
+    Transition,
+    StringList,
+    /// A string out of a fixed set of values, matched case insensitively.
+    Enum(&'static [&'static str]),
+    Map,
+    /// A map whose values are bools, such as the `classes` of a widget.
+    BoolMap,
+    Function,
+    /// A variable from `global("name")`, for props that only make sense bound.
+    Global,
+    /// Any value, interpreted by the node itself.
+    Any,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PropSpec {
+    pub name: &'static str,
+    pub ty: PropType,
+    pub required: bool,
+}
+
+#[derive(Debug)]
+pub struct WidgetSchema {
+    pub name: &'static str,
+    pub props: &'static [PropSpec],
+    /// Whether the widget also accepts the props shared by every widget.
+    pub common: bool,
+}
+
+impl WidgetSchema {
+    pub fn get(&self, prop: &str) -> Option<&'static PropSpec> {
+        let common: &'static [PropSpec] = if self.common { COMMON } else { &[] };
+        self.props.iter().chain(common).find(|p| p.name == prop)
+    }
+
+    /// Own props followed by the common ones, if the widget takes them.
+    pub fn all_props(&self) -> impl Iterator<Item = &'static PropSpec> {
+        let common: &'static [PropSpec] = if self.common { COMMON } else { &[] };
+        self.props.iter().chain(common)
+    }
+
+    fn prop_names(&self) -> impl Iterator<Item = &'static str> {
+        self.all_props().map(|p| p.name)
+    }
+}
+
+const fn prop(name: &'static str, ty: PropType) -> PropSpec {
+    PropSpec { name, ty, required: false }
+}
+
+const fn required(name: &'static str, ty: PropType) -> PropSpec {
+    PropSpec { name, ty, required: true }
+}
+
+const BOOL: PropType = PropType::Bool;
+const INT: PropType = PropType::Int;
+const FLOAT: PropType = PropType::Float;
+const STRING: PropType = PropType::String;
+const DURATION: PropType = PropType::Duration;
+const VALUE_TRANSITION: PropType = PropType::Transition;
+const STRING_LIST: PropType = PropType::StringList;
+const MAP: PropType = PropType::Map;
+const BOOL_MAP: PropType = PropType::BoolMap;
+const FUNCTION: PropType = PropType::Function;
+const GLOBAL: PropType = PropType::Global;
+const ANY: PropType = PropType::Any;
+
+const ALIGN: PropType = PropType::Enum(&["fill", "baseline", "center", "start", "end"]);
+const ORIENTATION: PropType = PropType::Enum(&["h", "horizontal", "v", "vertical"]);
+const GRAVITY: PropType = PropType::Enum(&["south", "north", "east", "west", "auto"]);
+const JUSTIFY: PropType = PropType::Enum(&["left", "right", "center", "fill"]);
+const WRAP_MODE: PropType = PropType::Enum(&["word", "char", "wordchar", "word_char", "word-char"]);
+const POSITION: PropType = PropType::Enum(&["left", "right", "top", "bottom"]);
+const SELECTION_MODE: PropType = PropType::Enum(&["none", "single", "browse", "multiple"]);
+const TRANSITION: PropType = PropType::Enum(&[
+    "slideright",
+    "slideleft",
+    "slideup",
+    "slidedown",
+    "fade",
+    "crossfade",
+    "none",
+]);
+const DRAG_TYPE: PropType = PropType::Enum(&["file", "text"]);
+const LINE_STYLE: PropType = PropType::Enum(&["miter", "bevel", "round"]);
+const GRAPH_TYPE: PropType = PropType::Enum(&["line", "step-line", "fill", "step-fill"]);
+const CONTENT_FIT: PropType = PropType::Enum(&["fill", "contain", "cover", "scaledown"]);
+const STACKING: PropType =
+    PropType::Enum(&["foreground", "fg", "background", "bg", "bottom", "bt", "overlay", "ov"]);
+const WINDOW_TYPE: PropType =
+    PropType::Enum(&["dock", "toolbar", "dialog", "normal", "utility", "desktop", "notification"]);
+const FOCUSABLE: PropType = PropType::Enum(&["none", "exclusive", "ondemand"]);
+const DATA_SOURCE: PropType = PropType::Enum(DATA_SOURCES);
+
+/// Builtin data sources a `Listen` can stream from with `source`.
+pub const DATA_SOURCES: &[&str] = &["mpris", "wifi", "bluetooth", "apps"];
+
+const COMMON: &[PropSpec] = &[
+    prop("visible", BOOL),
+    prop("class", STRING),
+    prop("classes", BOOL_MAP),
+    prop("style", STRING),
+    prop("css", STRING),
+    prop("valign", ALIGN),
+    prop("halign", ALIGN),
+    prop("vexpand", BOOL),
+    prop("hexpand", BOOL),
+    prop("width", INT),
+    prop("height", INT),
+    prop("active", BOOL),
+    prop("tooltip", STRING),
+    prop("can_target", BOOL),
+    prop("focusable", BOOL),
+    prop("widget_name", STRING),
+];
+
+const WIDGETS: &[WidgetSchema] = &[
+    WidgetSchema {
+        name: "Box",
+        props: &[
+            prop("orientation", ORIENTATION),
+            prop("spacing", INT),
+            prop("space_evenly", BOOL),
+        ],
+        common: true,
+    },
+    WidgetSchema { name: "OverLay", props: &[], common: true },
+    WidgetSchema { name: "ToolTip", props: &[], common: true },
+    WidgetSchema {
+        name: "Animation",
+        props: &[
+            prop("hover", STRING),
+            prop("hoverlost", STRING),
+            prop("click", STRING),
+            prop("release", STRING),
+            prop("trigger", STRING),
+            prop("sequences", MAP),
+            prop("sequence", STRING),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "EventBox",
+        props: &[
+            prop("timeout", DURATION),
+            prop("onscroll", STRING),
+            prop("onhover", STRING),
+            prop("onhoverlost", STRING),
+            prop("cursor", STRING),
+            prop("ondropped", STRING),
+            prop("drag_type", DRAG_TYPE),
+            prop("dragvalue", STRING),
+            prop("onclick", STRING),
+            prop("onmiddleclick", STRING),
+            prop("onrightclick", STRING),
+            prop("onrelease", STRING),
+            prop("onmiddlerelease", STRING),
+            prop("onrightrelease", STRING),
+            prop("onkeypress", STRING),
+            prop("onkeyrelease", STRING),
+            prop("orientation", ORIENTATION),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "FlowBox",
+        props: &[
+            prop("default_select", INT),
+            prop("orientation", ORIENTATION),
+            prop("space_evenly", BOOL),
+            prop("selection_model", SELECTION_MODE),
+            prop("timeout", DURATION),
+            prop("onaccept", STRING),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Stack",
+        props: &[
+            prop("selected", INT),
+            prop("transition", TRANSITION),
+            prop("transition_duration", INT),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "CircularProgress",
+        props: &[
+            prop("value", FLOAT),
+            prop("start_at", FLOAT),
+            prop("thickness", FLOAT),
+            prop("clockwise", BOOL),
+            prop("fg_color", STRING),
+            prop("bg_color", STRING),
+            prop("transition", VALUE_TRANSITION),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Graph",
+        props: &[
+            prop("value", FLOAT),
+            prop("time_range", DURATION),
+            prop("min", FLOAT),
+            prop("max", FLOAT),
+            prop("dynamic", BOOL),
+            prop("type", GRAPH_TYPE),
+            prop("thickness", FLOAT),
+            prop("line_style", LINE_STYLE),
+            prop("flip_x", BOOL),
+            prop("flip_y", BOOL),
+            prop("vertical", BOOL),
+            prop("animate", BOOL),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Progress",
+        props: &[
+            prop("orientation", ORIENTATION),
+            prop("flipped", BOOL),
+            prop("value", FLOAT),
+            prop("text", STRING),
+            prop("show_text", BOOL),
+            prop("transition", VALUE_TRANSITION),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Image",
+        props: &[
+            prop("path", STRING),
+            prop("image_width", INT),
+            prop("image_height", INT),
+            prop("preserve_aspect_ratio", BOOL),
+            prop("fill_svg", STRING),
+            prop("content_fit", CONTENT_FIT),
+            prop("can_shrink", BOOL),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Button",
+        props: &[
+            prop("timeout", DURATION),
+            prop("onclick", STRING),
+            prop("onmiddleclick", STRING),
+            prop("onrightclick", STRING),
+            prop("onrelease", STRING),
+            prop("onmiddlerelease", STRING),
+            prop("onrightrelease", STRING),
+            prop("label", STRING),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Label",
+        props: &[
+            prop("text", STRING),
+            prop("markup", STRING),
+            prop("truncate", BOOL),
+            prop("limit_width", INT),
+            prop("truncate_left", BOOL),
+            prop("unescape", BOOL),
+            prop("unindent", BOOL),
+            prop("wrap", BOOL),
+            prop("gravity", GRAVITY),
+            prop("xalign", FLOAT),
+            prop("yalign", FLOAT),
+            prop("justify", JUSTIFY),
+            prop("wrap_mode", WRAP_MODE),
+            prop("lines", INT),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Input",
+        props: &[
+            prop("value", STRING),
+            prop("placeholder", STRING),
+            prop("password", BOOL),
+            prop("timeout", DURATION),
+            prop("onchange", STRING),
+            prop("onaccept", STRING),
+            prop("bind", GLOBAL),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Calendar",
+        props: &[
+            prop("day", FLOAT),
+            prop("month", FLOAT),
+            prop("year", FLOAT),
+            prop("show_heading", BOOL),
+            prop("show_day_names", BOOL),
+            prop("show_week_numbers", BOOL),
+            prop("timeout", DURATION),
+            prop("onclick", STRING),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "ComboBoxText",
+        props: &[
+            prop("items", STRING_LIST),
+            prop("timeout", DURATION),
+            prop("onchange", STRING),
+            prop("bind", GLOBAL),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Expander",
+        props: &[prop("name", STRING), prop("expanded", BOOL)],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Revealer",
+        props: &[prop("transition", TRANSITION), prop("reveal", BOOL), prop("duration", DURATION)],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Checkbox",
+        props: &[
+            prop("checked", BOOL),
+            prop("timeout", DURATION),
+            prop("onchecked", STRING),
+            prop("onunchecked", STRING),
+            prop("bind", GLOBAL),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "ColorButton",
+        props: &[prop("use_alpha", BOOL), prop("timeout", DURATION), prop("onchange", STRING)],
+        common: true,
+    },
+    WidgetSchema {
+        name: "ColorChooser",
+        props: &[
+            prop("use_alpha", BOOL),
+            prop("timeout", DURATION),
+            prop("onchange", STRING),
+            prop("bind", GLOBAL),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Scale",
+        props: &[
+            prop("orientation", ORIENTATION),
+            prop("flipped", BOOL),
+            prop("marks", STRING),
+            prop("draw_value", BOOL),
+            prop("value_pos", POSITION),
+            prop("round_digits", INT),
+            prop("min", FLOAT),
+            prop("max", FLOAT),
+            prop("value", FLOAT),
+            prop("timeout", DURATION),
+            prop("transition", VALUE_TRANSITION),
+            prop("onchange", STRING),
+            prop("bind", GLOBAL),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "Scroll",
+        props: &[
+            prop("hscroll", BOOL),
+            prop("vscroll", BOOL),
+            prop("propagate_natural_height", BOOL),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "AspectFrame",
+        props: &[
+            prop("ratio", FLOAT),
+            prop("xalign", FLOAT),
+            prop("yalign", FLOAT),
+            prop("obey_child", BOOL),
+        ],
+        common: true,
+    },
+    WidgetSchema {
+        name: "GtkUI",
+        props: &[required("file", STRING), required("id", STRING), prop("widget_name", STRING)],
+        common: false,
+    },
+    WidgetSchema {
+        name: "Custom",
+        props: &[required("name", STRING), prop("widget_name", STRING)],
+        common: false,
+    },
+];
+
+/// Top-level nodes. These are read by the daemon itself rather than
+/// validated here, the schemas only describe them for editor tooling.
+const TOP_LEVEL: &[WidgetSchema] = &[
+    WidgetSchema {
+        name: "Window",
+        props: &[
+            prop("monitor", INT),
+            prop("geometry", MAP),
+            prop("reserve", MAP),
+            prop("resizable", BOOL),
+            prop("stacking", STACKING),
+            prop("windowtype", WINDOW_TYPE),
+            prop("sticky", BOOL),
+            prop("wm_ignore", BOOL),
+            prop("exclusive", BOOL),
+            prop("focusable", FOCUSABLE),
+            prop("namespace", STRING),
+            prop("force_normal", BOOL),
+            prop("waited_close", DURATION),
+            prop("open_animation", STRING),
+            prop("close_animation", STRING),
+        ],
+        common: false,
+    },
+    WidgetSchema {
+        name: "Poll",
+        props: &[
+            required("cmd", STRING),
+            prop("initial", STRING),
+            prop("interval", DURATION),
+            prop("skip_unchanged", BOOL),
+            prop("mock", ANY),
+        ],
+        common: false,
+    },
+    WidgetSchema {
+        name: "Listen",
+        props: &[
+            prop("cmd", STRING),
+            prop("initial", STRING),
+            prop("source", DATA_SOURCE),
+            prop("mock", ANY),
+        ],
+        common: false,
+    },
+    WidgetSchema {
+        name: "Script",
+        props: &[prop("every", DURATION), prop("on", STRING), required("run", FUNCTION)],
+        common: false,
+    },
+    WidgetSchema { name: "CssVar", props: &[required("value", ANY)], common: false },
+];
+
+/// Schema of the widget called `name`, if it has one.
+pub fn widget_schema(name: &str) -> Option<&'static WidgetSchema> {
+    WIDGETS.iter().find(|w| w.name == name)
+}
+
+/// Every widget that has a schema.
+pub fn widget_schemas() -> &'static [WidgetSchema] {
+    WIDGETS
+}
+
+/// Schema of the top-level node called `name`, such as `Window` or `Poll`.
+pub fn top_level_schema(name: &str) -> Option<&'static WidgetSchema> {
+    TOP_LEVEL.iter().find(|w| w.name == name)
+}
+
+/// Every top-level node that has a schema.
+pub fn top_level_schemas() -> &'static [WidgetSchema] {
+    TOP_LEVEL
+}
+
+/// An invalid, unknown or missing property.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropError {
+    pub widget: String,
+    pub prop: String,
+    pub message: String,
+    pub hint: Option<String>,
+    /// The property is required but missing, so there is no value to point at.
+    pub missing: bool,
+    /// Byte range in the source of the value, or of the widget if the
+    /// property is missing. Filled in by the config language, if it knows.
+    pub range: Option<std::ops::Range<usize>>,
+}
+
+impl std::fmt::Display for PropError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.widget, self.message)?;
+        if let Some(hint) = &self.hint {
+            write!(f, " ({})", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every property error found in a config.
+#[derive(Debug)]
+pub struct PropErrors(pub Vec<PropError>);
+
+impl std::fmt::Display for PropErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines: Vec<_> = self.0.iter().map(ToString::to_string).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl std::error::Error for PropErrors {}
+
+/// Check `props` of a `widget` against its schema. Widgets without a
+/// schema are not checked.
+pub fn validate_props(widget: &str, props: &PropertyMap) -> Vec<PropError> {
+    let Some(schema) = widget_schema(widget) else {
+        return Vec::new();
+    };
+
+    let error = |prop: &str, message: String, hint: Option<String>| PropError {
+        widget: widget.to_string(),
+        prop: prop.to_string(),
+        message,
+        hint,
+        missing: false,
+        range: None,
+    };
+
+    let mut errors = Vec::new();
+    for (key, value) in props {
+        if matches!(value, Property::None) {
+            continue;
+        }
+
+        match schema.get(key) {
+            Some(spec) => {
+                if let Err((message, hint)) = check_value(spec, value) {
+                    errors.push(error(key, message, hint));
+                }
+            }
+            None => errors.push(error(
+                key,
+                format!("unknown property `{}`", key),
+                suggest(key, schema.prop_names()).map(|s| format!("did you mean `{}`?", s)),
+            )),
+        }
+    }
+
+    for spec in schema.props.iter().filter(|p| p.required && props.get(p.name).is_none()) {
+        errors.push(PropError {
+            missing: true,
+            ..error(spec.name, format!("missing required property `{}`", spec.name), None)
+        });
+    }
+
+    errors
+}
+
+/// Turn a raw string update of `key` into a typed property, e.g. for
+/// updates coming in over IPC.
+#[allow(clippy::result_large_err)]
+pub fn parse_prop_update(widget: &str, key: &str, raw: &str) -> Result<Property, PropError> {
+    let error = |message: String, hint: Option<String>| PropError {
+        widget: widget.to_string(),
+        prop: key.to_string(),
+        message,
+        hint,
+        missing: false,
+        range: None,
+    };
+
+    let Some(schema) = widget_schema(widget) else {
+        return Ok(Property::String(raw.to_string()));
+    };
+    let Some(spec) = schema.get(key) else {
+        let hint = suggest(key, schema.prop_names()).map(|s| format!("did you mean `{}`?", s));
+        return Err(error(format!("unknown property `{}`", key), hint));
+    };
+
+    let value = match spec.ty {
+        PropType::Bool => raw.trim().parse().map(Property::Bool).ok(),
+        PropType::Int => raw.trim().parse().map(Property::Int).ok(),
+        PropType::Float => raw.trim().parse().map(Property::Float).ok(),
+        PropType::StringList => serde_json::from_str::<Vec<String>>(raw)
+            .ok()
+            .map(|items| Property::Array(items.into_iter().map(Property::String).collect())),
+        PropType::String
+        | PropType::Duration
+        | PropType::Transition
+        | PropType::Enum(_)
+        | PropType::Any => Some(Property::String(raw.to_string())),
+        // None of these can be written as a plain string
+        PropType::Map | PropType::BoolMap | PropType::Function | PropType::Global => None,
+    };
+
+    let value = value.ok_or_else(|| {
+        error(format!("expected {} for `{}`, got \"{}\"", describe_type(spec.ty), key, raw), None)
+    })?;
+    check_value(spec, &value).map_err(|(message, hint)| error(message, hint))?;
+
+    Ok(value)
+}
+
+fn check_value(spec: &PropSpec, value: &Property) -> Result<(), (String, Option<String>)> {
+    // Bound values are only known once the variable updates, the widget
+    // parses them then.
+    let static_only = matches!(spec.ty, PropType::Duration | PropType::Transition);
+    if value.as_global_var().is_some() && !static_only {
+        return Ok(());
+    }
+
+    let valid = match (spec.ty, value) {
+        (PropType::Bool, Property::Bool(_)) => true,
+        (PropType::Bool, Property::String(s)) => s.parse::<bool>().is_ok(),
+        (PropType::Int, Property::Int(_)) => true,
+        (PropType::Int, Property::String(s)) => s.parse::<i64>().is_ok(),
+        (PropType::Float, Property::Float(_) | Property::Int(_)) => true,
+        (PropType::Float, Property::String(s)) => s.parse::<f64>().is_ok(),
+        (PropType::String, Property::String(_)) => true,
+        (PropType::Duration, Property::String(s)) => {
+            parse_duration_str(&s.trim().to_ascii_lowercase()).is_some()
+        }
+        (PropType::Transition, Property::String(_)) => {
+            get_transition_prop(value, spec.name).map_err(|e| (e.to_string(), None))?;
+            true
+        }
+        (PropType::StringList, Property::Array(items)) => {
+            items.iter().all(|i| matches!(i, Property::String(_)) || i.as_global_var().is_some())
+        }
+        (PropType::Map, Property::Map(_)) => true,
+        (PropType::BoolMap, Property::Map(entries)) => {
+            let invalid = entries.0.iter().find(|(_, v)| match v {
+                Property::Bool(_) | Property::GlobalVar(_) => false,
+                Property::String(s) => s.parse::<bool>().is_err(),
+                _ => true,
+            });
+            if let Some((key, v)) = invalid {
+                // A comparison such as `global("x") == "1"` is evaluated once
+                // at load, the global has to do it in its template instead.
+                return Err((
+                    format!("expected a bool for `{}.{}`, got {}", spec.name, key, describe(v)),
+                    Some(
+                        "to compare a global, use a template like \
+                         global(\"x\").template(\"{x == '1'}\")"
+                            .to_string(),
+                    ),
+                ));
+            }
+            true
+        }
+        (PropType::Function, Property::Callback(_)) => true,
+        (PropType::Any, _) => true,
+        (PropType::Enum(values), Property::String(s)) => {
+            let lower = s.to_ascii_lowercase();
+            if !values.contains(&lower.as_str()) {
+                let hint = match suggest(&lower, values.iter().copied()) {
+                    Some(s) => format!("did you mean \"{}\"?", s),
+                    None => format!("expected one of: {}", values.join(", ")),
+                };
+                return Err((format!("invalid value \"{}\" for `{}`", s, spec.name), Some(hint)));
+            }
+            true
+        }
+        _ => false,
+    };
+
+    if valid {
+        return Ok(());
+    }
+
+    let hint = match spec.ty {
+        PropType::Duration => "durations cannot be bound to a variable",
+        _ => "transitions cannot be bound to a variable",
+    };
+    let hint = (static_only && value.as_global_var().is_some()).then(|| hint.to_string());
+    Err((
+        format!("expected {} for `{}`, got {}", describe_type(spec.ty), spec.name, describe(value)),
+        hint,
+    ))
+}
+
+/// Human readable name of a property type, e.g. "a bool".
+pub fn describe_type(ty: PropType) -> &'static str {
+    match ty {
+        PropType::Bool => "a bool",
+        PropType::Int => "an integer",
+        PropType::Float => "a number",
+        PropType::String => "a string",
+        PropType::Duration => "a duration like \"200ms\"",
+        PropType::Transition => "a transition like \"300ms ease-out\"",
+        PropType::StringList => "a list of strings",
+        PropType::Enum(_) => "a string",
+        PropType::Map => "a map",
+        PropType::BoolMap => "a map of bools",
+        PropType::Function => "a function",
+        PropType::Global => "a global like global(\"name\")",
+        PropType::Any => "any value",
+    }
+}
+
+fn describe(value: &Property) -> String {
+    match value {
+        Property::None => "null".to_string(),
+        Property::Bool(b) => format!("bool {}", b),
+        Property::Int(i) => format!("integer {}", i),
+        Property::Float(f) => format!("number {}", f),
+        Property::String(s) => format!("string \"{}\"", s),
+        Property::Array(_) => "a list".to_string(),
+        Property::Map(_) => "a map".to_string(),
+        Property::Callback(_) => "a function".to_string(),
+        Property::GlobalVar(v) => format!("global `{}`", v.name),
+    }
+}
+
+/// The closest candidate to `input`, if any is close enough to be a typo.
+fn suggest<'a>(input: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (input.chars().count() / 3).max(1);
+    candidates
+        .map(|c| (edit_distance(input, c), c))
+        .filter(|(d, _)| *d <= max_distance)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance that counts swapping two adjacent chars as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for j in 0..=b.len() {
+        d[0][j] = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn props(entries: &[(&str, Property)]) -> PropertyMap {
+        let mut map = PropertyMap::new();
+        for (k, v) in entries {
+            map.insert(*k, v.clone());
+        }
+        map
+    }
+
+    #[test]
+    fn test_valid_props() {
+        let map = props(&[
+            ("orientation", "V".into()),
+            ("spacing", Property::Int(4)),
+            ("space_evenly", "false".into()),
+            ("halign", "center".into()),
+        ]);
+        assert!(validate_props("Box", &map).is_empty());
+        assert!(validate_props("Poll", &props(&[("anything", "x".into())])).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_prop_suggestion() {
+        let errors = validate_props("Box", &props(&[("halgin", "start".into())]));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "unknown property `halgin`");
+        assert_eq!(errors[0].hint.as_deref(), Some("did you mean `halign`?"));
+
+        let errors = validate_props("Box", &props(&[("zzz", "start".into())]));
+        assert_eq!(errors[0].hint, None);
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let errors = validate_props("Box", &props(&[("space_evenly", "yes".into())]));
+        assert_eq!(errors[0].message, "expected a bool for `space_evenly`, got string \"yes\"");
+
+        let errors = validate_props("Revealer", &props(&[("transition", "slidup".into())]));
+        assert_eq!(errors[0].message, "invalid value \"slidup\" for `transition`");
+        assert_eq!(errors[0].hint.as_deref(), Some("did you mean \"slideup\"?"));
+
+        let errors = validate_props("Button", &props(&[("timeout", "soon".into())]));
+        assert_eq!(errors.len(), 1);
+
+        for transition in ["300ms ease-out", "1s cubic-bezier(0.4, 0, 0.2, 1)", "200ms spring(0.3)"]
+        {
+            assert!(
+                validate_props("Scale", &props(&[("transition", transition.into())])).is_empty()
+            );
+        }
+        let errors = validate_props("Progress", &props(&[("transition", "1s bouncy".into())]));
+        assert!(errors[0].message.starts_with("Unknown easing 'bouncy', expected linear,"));
+
+        let errors = validate_props("Input", &props(&[("bind", "query".into())]));
+        assert_eq!(
+            errors[0].message,
+            "expected a global like global(\"name\") for `bind`, got string \"query\""
+        );
+
+        let classes = props(&[("active", Property::Bool(true)), ("urgent", "maybe".into())]);
+        let errors = validate_props("Label", &props(&[("classes", Property::Map(classes))]));
+        assert_eq!(errors[0].message, "expected a bool for `classes.urgent`, got string \"maybe\"");
+        assert!(errors[0].hint.as_deref().unwrap().contains(".template("));
+    }
+
+    #[test]
+    fn test_required_props() {
+        let errors = validate_props("GtkUI", &props(&[("file", "a.ui".into())]));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].missing);
+        assert_eq!(errors[0].prop, "id");
+    }
+
+    #[test]
+    fn test_parse_prop_update() {
+        assert!(matches!(
+            parse_prop_update("Box", "space_evenly", "true"),
+            Ok(Property::Bool(true))
+        ));
+        assert!(matches!(parse_prop_update("Box", "spacing", "8"), Ok(Property::Int(8))));
+        assert!(matches!(
+            parse_prop_update("ComboBoxText", "items", r#"["a", "b"]"#),
+            Ok(Property::Array(items)) if items.len() == 2
+        ));
+        assert!(parse_prop_update("Box", "spacing", "wide").is_err());
+        assert!(parse_prop_update("Box", "orientation", "diagonal").is_err());
+        assert!(parse_prop_update("Box", "spcing", "8").is_err());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("halgin", "halign"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -1,8 +1,11 @@
+use crate::template_functions;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::hash::Hash;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+pub use crate::template_functions::{register_template_function, TemplateFn};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Hash)]
 pub enum TemplateExpr {
     Literal(String),
     Var(String),
@@ -10,9 +13,11 @@ pub enum TemplateExpr {
     IfElse { condition: Box<TemplateExpr>, if_true: Box<TemplateExpr>, if_false: Box<TemplateExpr> },
     BinOp { op: TemplateOp, left: Box<TemplateExpr>, right: Box<TemplateExpr> },
     Index { expr: Box<TemplateExpr>, key: Box<TemplateExpr> },
+    Unary { op: TemplateUnaryOp, expr: Box<TemplateExpr> },
+    Call { name: String, args: Vec<TemplateExpr> },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Hash)]
 pub enum TemplateOp {
     Add,
     Sub,
@@ -31,6 +36,12 @@ pub enum TemplateOp {
     RegexMatch,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Hash)]
+pub enum TemplateUnaryOp {
+    Neg,
+    Not,
+}
+
 impl TemplateExpr {
     /// Collect all variable names this expression depends on
     pub fn collect_vars(&self) -> Vec<String> {
@@ -54,6 +65,8 @@ impl TemplateExpr {
                 vars.extend(key.collect_vars());
                 vars
             }
+            TemplateExpr::Unary { expr, .. } => expr.collect_vars(),
+            TemplateExpr::Call { args, .. } => args.iter().flat_map(|a| a.collect_vars()).collect(),
         }
     }
 
@@ -154,6 +167,19 @@ impl TemplateExpr {
                     None => Err(format!("Index '{}' not found", key_str)),
                 }
             }
+            TemplateExpr::Unary { op, expr } => match op {
+                TemplateUnaryOp::Neg => {
+                    let value = expr.eval(vars)?;
+                    let n =
+                        value.parse::<f64>().map_err(|_| format!("'{}' is not a number", value))?;
+                    Ok(format_number(-n))
+                }
+                TemplateUnaryOp::Not => Ok((!expr.eval_as_bool(vars)?).to_string()),
+            },
+            TemplateExpr::Call { name, args } => {
+                let args = args.iter().map(|a| a.eval(vars)).collect::<Result<Vec<_>, _>>()?;
+                template_functions::call(name, &args)
+            }
         }
     }
 
@@ -169,31 +195,48 @@ impl TemplateExpr {
         }
     }
 
+    /// Parse a template string, where expressions are wrapped in `{}`.
+    ///
+    /// Errors point at the column in `input` where parsing failed.
     pub fn parse(input: &str) -> Result<Self, String> {
         let mut exprs = Vec::new();
-        let mut remaining = input;
-
-        while !remaining.is_empty() {
-            if let Some(start_idx) = remaining.find('{') {
-                if start_idx > 0 {
-                    exprs.push(TemplateExpr::Literal(remaining[..start_idx].to_string()));
-                }
+        let mut pos = 0;
 
-                remaining = &remaining[start_idx + 1..];
+        while let Some(offset) = input[pos..].find('{') {
+            let start = pos + offset;
+            if start > pos {
+                exprs.push(TemplateExpr::Literal(input[pos..start].to_string()));
+            }
 
-                if let Some(end_idx) = remaining.find('}') {
-                    let inner_expr_str = &remaining[..end_idx];
-                    remaining = &remaining[end_idx + 1..];
+            let mut parser = Parser::new(input, start + 1);
+            if parser.peek()?.token == Token::RBrace {
+                return Err(format!(
+                    "Empty expression inside brackets at column {}",
+                    column_at(input, start)
+                ));
+            }
 
-                    let inner_expr = Self::parse_inner_expression(inner_expr_str.trim())?;
-                    exprs.push(inner_expr);
-                } else {
-                    return Err("Mismatched curly braces: missing closing '}'".to_string());
+            let expr = parser.parse_expr()?;
+            let end = parser.next()?;
+            match end.token {
+                Token::RBrace => {}
+                Token::Eof => {
+                    return Err(format!(
+                        "Mismatched curly braces: missing closing '}}' for '{{' at column {}",
+                        column_at(input, start)
+                    ))
+                }
+                other => {
+                    return Err(format!("Unexpected {} at column {}", other.describe(), end.column))
                 }
-            } else {
-                exprs.push(TemplateExpr::Literal(remaining.to_string()));
-                break;
             }
+
+            exprs.push(expr);
+            pos = parser.pos;
+        }
+
+        if pos < input.len() {
+            exprs.push(TemplateExpr::Literal(input[pos..].to_string()));
         }
 
         match exprs.len() {
@@ -202,76 +245,383 @@ impl TemplateExpr {
             _ => Ok(TemplateExpr::Concat(exprs)),
         }
     }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(String),
+    Str(String),
+    Ident(String),
+    Op(TemplateOp),
+    Not,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Dot,
+    Question,
+    Colon,
+    /// The `}` closing the expression
+    RBrace,
+    Eof,
+}
 
-    fn parse_inner_expression(input: &str) -> Result<Self, String> {
-        let input = input.trim();
-        if input.is_empty() {
-            return Err("Empty expression inside brackets".to_string());
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(n) => format!("number '{}'", n),
+            Token::Str(s) => format!("string \"{}\"", s),
+            Token::Ident(i) => format!("identifier '{}'", i),
+            Token::Op(op) => format!("operator '{}'", op.symbol()),
+            Token::Not => "'!'".to_string(),
+            Token::LParen => "'('".to_string(),
+            Token::RParen => "')'".to_string(),
+            Token::LBracket => "'['".to_string(),
+            Token::RBracket => "']'".to_string(),
+            Token::Comma => "','".to_string(),
+            Token::Dot => "'.'".to_string(),
+            Token::Question => "'?'".to_string(),
+            Token::Colon => "':'".to_string(),
+            Token::RBrace => "'}'".to_string(),
+            Token::Eof => "end of template".to_string(),
         }
+    }
+}
 
-        if let Some(q_idx) = input.find('?') {
-            if let Some(c_idx) = input[q_idx..].find(':') {
-                let actual_c_idx = q_idx + c_idx;
-
-                let condition_str = &input[..q_idx];
-                let true_str = &input[q_idx + 1..actual_c_idx];
-                let false_str = &input[actual_c_idx + 1..];
-
-                return Ok(TemplateExpr::IfElse {
-                    condition: Box::new(Self::parse_inner_expression(condition_str)?),
-                    if_true: Box::new(Self::parse_inner_expression(true_str)?),
-                    if_false: Box::new(Self::parse_inner_expression(false_str)?),
-                });
-            } else {
-                return Err("Ternary operator missing ':' closing branch".to_string());
-            }
+#[derive(Debug, Clone)]
+struct Spanned {
+    token: Token,
+    /// 1-based column in the whole template string
+    column: usize,
+}
+
+fn column_at(input: &str, byte_pos: usize) -> usize {
+    input[..byte_pos].chars().count() + 1
+}
+
+/// Namespaces of builtin globals, whose names read like `ewwii:theme`.
+const VAR_NAMESPACES: &[&str] = &["ewwii"];
+
+/// Length of the identifier at the start of `input`. A `:` after a name in
+/// [`VAR_NAMESPACES`] is part of it, anywhere else it ends the name so that
+/// ternaries like `{a ? b:c}` keep working.
+fn ident_len(input: &str) -> usize {
+    let is_start = |c: char| c.is_alphabetic() || c == '_';
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let joins_names = c == ':'
+            && VAR_NAMESPACES.contains(&&input[..i])
+            && chars.peek().is_some_and(|&(_, next)| is_start(next));
+        if !(c.is_alphanumeric() || c == '_' || joins_names) {
+            return i;
         }
+    }
+    input.len()
+}
 
-        let op_groups = [
-            (vec![("||", TemplateOp::Or), ("&&", TemplateOp::And)], false),
-            (vec![("==", TemplateOp::Eq), ("!=", TemplateOp::NotEq)], false),
-            (
-                vec![
-                    (">=", TemplateOp::Gte),
-                    ("<=", TemplateOp::Lte),
-                    (">", TemplateOp::Gt),
-                    ("<", TemplateOp::Lt),
-                ],
-                false,
-            ),
-            (vec![("+", TemplateOp::Add), ("-", TemplateOp::Sub)], false),
-            (vec![("*", TemplateOp::Mul), ("/", TemplateOp::Div), ("%", TemplateOp::Mod)], false),
-            (vec![("?:", TemplateOp::Elvis), ("~=", TemplateOp::RegexMatch)], false),
-        ];
-
-        for (ops, _) in op_groups.iter() {
-            for (op_str, op_enum) in ops {
-                if let Some(idx) = input.rfind(op_str) {
-                    let left_str = &input[..idx];
-                    let right_str = &input[idx + op_str.len()..];
-
-                    return Ok(TemplateExpr::BinOp {
-                        op: op_enum.clone(),
-                        left: Box::new(Self::parse_inner_expression(left_str)?),
-                        right: Box::new(Self::parse_inner_expression(right_str)?),
-                    });
+impl TemplateOp {
+    fn symbol(&self) -> &'static str {
+        match self {
+            TemplateOp::Add => "+",
+            TemplateOp::Sub => "-",
+            TemplateOp::Mul => "*",
+            TemplateOp::Div => "/",
+            TemplateOp::Eq => "==",
+            TemplateOp::NotEq => "!=",
+            TemplateOp::Mod => "%",
+            TemplateOp::Gt => ">",
+            TemplateOp::Lt => "<",
+            TemplateOp::Gte => ">=",
+            TemplateOp::Lte => "<=",
+            TemplateOp::And => "&&",
+            TemplateOp::Or => "||",
+            TemplateOp::Elvis => "?:",
+            TemplateOp::RegexMatch => "~=",
+        }
+    }
+
+    /// Binding power of the operator, higher binds tighter.
+    fn precedence(&self) -> u8 {
+        match self {
+            TemplateOp::Elvis => 1,
+            TemplateOp::Or => 2,
+            TemplateOp::And => 3,
+            TemplateOp::Eq | TemplateOp::NotEq | TemplateOp::RegexMatch => 4,
+            TemplateOp::Gt | TemplateOp::Lt | TemplateOp::Gte | TemplateOp::Lte => 5,
+            TemplateOp::Add | TemplateOp::Sub => 6,
+            TemplateOp::Mul | TemplateOp::Div | TemplateOp::Mod => 7,
+        }
+    }
+}
+
+/// Tokenizer and precedence climbing parser for the expressions inside `{}`.
+///
+/// Tokens are read lazily so that parsing stops right after the closing
+/// `}` and the rest of the template can be handled by [`TemplateExpr::parse`].
+struct Parser<'a> {
+    input: &'a str,
+    /// Byte position of the next unread char
+    pos: usize,
+    peeked: Option<Spanned>,
+    /// Whether the last token was a `.`, so that `list.0.1` indexes twice
+    /// instead of reading `0.1` as a number.
+    after_dot: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str, pos: usize) -> Self {
+        Self { input, pos, peeked: None, after_dot: false }
+    }
+
+    fn error<T>(&self, message: impl std::fmt::Display, column: usize) -> Result<T, String> {
+        Err(format!("{} at column {}", message, column))
+    }
+
+    fn peek(&mut self) -> Result<&Spanned, String> {
+        if self.peeked.is_none() {
+            let token = self.lex()?;
+            self.peeked = Some(token);
+        }
+        Ok(self.peeked.as_ref().unwrap())
+    }
+
+    fn next(&mut self) -> Result<Spanned, String> {
+        match self.peeked.take() {
+            Some(token) => Ok(token),
+            None => self.lex(),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        let next = self.next()?;
+        if next.token == expected {
+            Ok(())
+        } else {
+            self.error(
+                format_args!("Expected {}, found {}", expected.describe(), next.token.describe()),
+                next.column,
+            )
+        }
+    }
+
+    fn lex(&mut self) -> Result<Spanned, String> {
+        let rest = &self.input[self.pos..];
+        let trimmed = rest.trim_start();
+        self.pos += rest.len() - trimmed.len();
+
+        let column = column_at(self.input, self.pos);
+        let after_dot = std::mem::take(&mut self.after_dot);
+        let mut chars = self.input[self.pos..].chars();
+        let Some(c) = chars.next() else {
+            return Ok(Spanned { token: Token::Eof, column });
+        };
+
+        let two_char = |second: char| chars.clone().next() == Some(second);
+        let (token, len) = match c {
+            '}' => (Token::RBrace, 1),
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            '[' => (Token::LBracket, 1),
+            ']' => (Token::RBracket, 1),
+            ',' => (Token::Comma, 1),
+            '.' => {
+                self.after_dot = true;
+                (Token::Dot, 1)
+            }
+            '?' if two_char(':') => (Token::Op(TemplateOp::Elvis), 2),
+            '?' => (Token::Question, 1),
+            ':' => (Token::Colon, 1),
+            '=' if two_char('=') => (Token::Op(TemplateOp::Eq), 2),
+            '!' if two_char('=') => (Token::Op(TemplateOp::NotEq), 2),
+            '!' => (Token::Not, 1),
+            '>' if two_char('=') => (Token::Op(TemplateOp::Gte), 2),
+            '>' => (Token::Op(TemplateOp::Gt), 1),
+            '<' if two_char('=') => (Token::Op(TemplateOp::Lte), 2),
+            '<' => (Token::Op(TemplateOp::Lt), 1),
+            '&' if two_char('&') => (Token::Op(TemplateOp::And), 2),
+            '|' if two_char('|') => (Token::Op(TemplateOp::Or), 2),
+            '~' if two_char('=') => (Token::Op(TemplateOp::RegexMatch), 2),
+            '+' => (Token::Op(TemplateOp::Add), 1),
+            '-' => (Token::Op(TemplateOp::Sub), 1),
+            '*' => (Token::Op(TemplateOp::Mul), 1),
+            '/' => (Token::Op(TemplateOp::Div), 1),
+            '%' => (Token::Op(TemplateOp::Mod), 1),
+            '"' | '\'' | '`' => return self.lex_string(c, column),
+            c if c.is_ascii_digit() => {
+                let rest = &self.input[self.pos..];
+                let mut len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                // `1.5` is a number, but the `0` in `list.0.1` is an index
+                if !after_dot && rest[len..].starts_with('.') {
+                    let fraction = &rest[len + 1..];
+                    let digits =
+                        fraction.find(|c: char| !c.is_ascii_digit()).unwrap_or(fraction.len());
+                    if digits > 0 {
+                        len += 1 + digits;
+                    }
+                }
+                (Token::Number(rest[..len].to_string()), len)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let rest = &self.input[self.pos..];
+                let len = ident_len(rest);
+                (Token::Ident(rest[..len].to_string()), len)
+            }
+            other => return self.error(format_args!("Unexpected character '{}'", other), column),
+        };
+
+        self.pos += len;
+        Ok(Spanned { token, column })
+    }
+
+    fn lex_string(&mut self, quote: char, column: usize) -> Result<Spanned, String> {
+        let mut value = String::new();
+        let mut chars = self.input[self.pos..].char_indices().skip(1);
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                c if c == quote => {
+                    self.pos += i + c.len_utf8();
+                    return Ok(Spanned { token: Token::Str(value), column });
                 }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    Some((_, 'r')) => value.push('\r'),
+                    Some((_, escaped)) => value.push(escaped),
+                    None => break,
+                },
+                c => value.push(c),
             }
         }
 
-        if input.chars().all(|c| c.is_ascii_digit() || c == '.') {
-            return Ok(TemplateExpr::Literal(input.to_string()));
+        self.error("Unterminated string literal", column)
+    }
+
+    fn parse_expr(&mut self) -> Result<TemplateExpr, String> {
+        let condition = self.parse_binary(0)?;
+        if self.peek()?.token != Token::Question {
+            return Ok(condition);
+        }
+
+        self.next()?;
+        let if_true = self.parse_expr()?;
+        self.expect(Token::Colon)?;
+        let if_false = self.parse_expr()?;
+
+        Ok(TemplateExpr::IfElse {
+            condition: Box::new(condition),
+            if_true: Box::new(if_true),
+            if_false: Box::new(if_false),
+        })
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<TemplateExpr, String> {
+        let mut left = self.parse_unary()?;
+
+        loop {
+            let Token::Op(op) = &self.peek()?.token else {
+                break;
+            };
+            let precedence = op.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+
+            let op = op.clone();
+            self.next()?;
+            // `?:` is right associative, everything else is left associative
+            let next_min = if op == TemplateOp::Elvis { precedence } else { precedence + 1 };
+            let right = self.parse_binary(next_min)?;
+            left = TemplateExpr::BinOp { op, left: Box::new(left), right: Box::new(right) };
         }
 
-        if ((input.starts_with('"') && input.ends_with('"'))
-            || (input.starts_with('\'') && input.ends_with('\''))
-            || (input.starts_with('`') && input.ends_with('`')))
-            && input.len() >= 2
-        {
-            return Ok(TemplateExpr::Literal(input[1..input.len() - 1].to_string()));
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<TemplateExpr, String> {
+        let op = match self.peek()?.token {
+            Token::Op(TemplateOp::Sub) => TemplateUnaryOp::Neg,
+            Token::Not => TemplateUnaryOp::Not,
+            _ => return self.parse_postfix(),
+        };
+
+        self.next()?;
+        Ok(TemplateExpr::Unary { op, expr: Box::new(self.parse_unary()?) })
+    }
+
+    fn parse_postfix(&mut self) -> Result<TemplateExpr, String> {
+        let mut expr = self.parse_primary()?;
+
+        loop {
+            let key = match self.peek()?.token {
+                // `var.key.0` indexes into a JSON global
+                Token::Dot => {
+                    self.next()?;
+                    let next = self.next()?;
+                    match next.token {
+                        Token::Ident(key) | Token::Number(key) => TemplateExpr::Literal(key),
+                        other => {
+                            return self.error(
+                                format_args!(
+                                    "Expected a key after '.', found {}",
+                                    other.describe()
+                                ),
+                                next.column,
+                            )
+                        }
+                    }
+                }
+                Token::LBracket => {
+                    self.next()?;
+                    let key = self.parse_expr()?;
+                    self.expect(Token::RBracket)?;
+                    key
+                }
+                _ => break,
+            };
+
+            expr = TemplateExpr::Index { expr: Box::new(expr), key: Box::new(key) };
         }
 
-        Ok(TemplateExpr::Var(input.to_string()))
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<TemplateExpr, String> {
+        let next = self.next()?;
+
+        match next.token {
+            Token::Number(n) => Ok(TemplateExpr::Literal(n)),
+            Token::Str(s) => Ok(TemplateExpr::Literal(s)),
+            Token::Ident(name) if self.peek()?.token == Token::LParen => {
+                self.next()?;
+                let mut args = Vec::new();
+                if self.peek()?.token != Token::RParen {
+                    loop {
+                        args.push(self.parse_expr()?);
+                        if self.peek()?.token != Token::Comma {
+                            break;
+                        }
+                        self.next()?;
+                    }
+                }
+                self.expect(Token::RParen)?;
+                Ok(TemplateExpr::Call { name, args })
+            }
+            Token::Ident(name) if name == "true" || name == "false" => {
+                Ok(TemplateExpr::Literal(name))
+            }
+            Token::Ident(name) => Ok(TemplateExpr::Var(name)),
+            Token::LParen => {
+                let expr = self.parse_expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            other => self.error(
+                format_args!("Expected an expression, found {}", other.describe()),
+                next.column,
+            ),
+        }
     }
 }
 
@@ -295,10 +645,127 @@ fn eval_cmp(l: String, r: String, op: impl Fn(f64, f64) -> bool) -> Result<Strin
     }
 }
 
-fn format_number(n: f64) -> String {
+pub(crate) fn format_number(n: f64) -> String {
     if n.fract() == 0.0 {
         format!("{}", n as i64)
     } else {
         format!("{}", n)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(template: &str, vars: &[(&str, &str)]) -> String {
+        let vars = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        TemplateExpr::parse(template).unwrap().eval(&vars).unwrap()
+    }
+
+    #[test]
+    fn test_precedence_and_grouping() {
+        assert_eq!(eval("{1 + 2 * 3}", &[]), "7");
+        assert_eq!(eval("{(1 + 2) * 3}", &[]), "9");
+        assert_eq!(eval("{10 - 4 - 3}", &[]), "3");
+        assert_eq!(eval("{-x + 5}", &[("x", "2")]), "3");
+        assert_eq!(eval("{!(x > 1) || x == 2}", &[("x", "2")]), "true");
+        assert_eq!(eval("{x ?: 'none'}", &[("x", "")]), "none");
+    }
+
+    #[test]
+    fn test_nested_ternary() {
+        let template = "{x > 80 ? 'high' : x > 40 ? 'mid' : 'low'}";
+        assert_eq!(eval(template, &[("x", "90")]), "high");
+        assert_eq!(eval(template, &[("x", "50")]), "mid");
+        assert_eq!(eval(template, &[("x", "10")]), "low");
+        assert_eq!(eval("{a ? b ? 1 : 2 : 3}", &[("a", "true"), ("b", "false")]), "2");
+    }
+
+    #[test]
+    fn test_namespaced_vars() {
+        let vars = [("ewwii:theme", "dark"), ("a", "true"), ("b", "x")];
+        assert_eq!(eval("{ewwii:theme == 'dark' ? 1 : 2}", &vars), "1");
+        assert_eq!(eval("{a ? b : ewwii:theme}", &vars), "x");
+        assert_eq!(eval("{a ? 'y':b}", &vars), "y");
+        assert_eq!(eval("{a ? b:ewwii:theme}", &vars), "x");
+        assert_eq!(eval("{b == 'y' ? b:ewwii:theme}", &vars), "dark");
+        assert_eq!(
+            TemplateExpr::parse("{ewwii:theme}").unwrap().collect_vars(),
+            vec!["ewwii:theme".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(eval("{'a - b'}", &[]), "a - b");
+        assert_eq!(eval(r#"{"say \"hi\"\n"}"#, &[]), "say \"hi\"\n");
+        assert_eq!(eval("{'}'} and {`{`}", &[]), "} and {");
+        assert_eq!(eval("Volume: {v}% ", &[("v", "40")]), "Volume: 40% ");
+    }
+
+    #[test]
+    fn test_index() {
+        let json = r#"{"a": {"b": [10, 20]}, "key": "b"}"#;
+        assert_eq!(eval("{j.a.b.1}", &[("j", json)]), "20");
+        assert_eq!(eval("{j.a[j.key][0] + 1.5}", &[("j", json)]), "11.5");
+        assert_eq!(TemplateExpr::parse("{j.a.b}").unwrap().collect_vars(), vec!["j".to_string()]);
+    }
+
+    #[test]
+    fn test_function_call() {
+        let expr = TemplateExpr::parse("{f(a, 'b', 1 + 2)}").unwrap();
+        assert_eq!(
+            expr,
+            TemplateExpr::Call {
+                name: "f".to_string(),
+                args: vec![
+                    TemplateExpr::Var("a".to_string()),
+                    TemplateExpr::Literal("b".to_string()),
+                    TemplateExpr::BinOp {
+                        op: TemplateOp::Add,
+                        left: Box::new(TemplateExpr::Literal("1".to_string())),
+                        right: Box::new(TemplateExpr::Literal("2".to_string())),
+                    },
+                ],
+            }
+        );
+        assert_eq!(expr.collect_vars(), vec!["a".to_string()]);
+        assert_eq!(eval("{upper(substring(name, 0, 3))}", &[("name", "ewwii")]), "EWW");
+        assert_eq!(eval("{round(cpu * 100, 1)}%", &[("cpu", "0.4567")]), "45.7%");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |template: &str| TemplateExpr::parse(template).unwrap_err();
+
+        assert_eq!(error("abc {}"), "Empty expression inside brackets at column 5");
+        assert_eq!(error("{1 +}"), "Expected an expression, found '}' at column 5");
+        assert_eq!(error("{(1 + 2}"), "Expected ')', found '}' at column 8");
+        assert_eq!(error("{x ? 1}"), "Expected ':', found '}' at column 7");
+        assert_eq!(error("{'abc}"), "Unterminated string literal at column 2");
+        assert_eq!(error("{1 # 2}"), "Unexpected character '#' at column 4");
+        assert_eq!(error("{a b}"), "Unexpected identifier 'b' at column 4");
+        assert_eq!(
+            error("ok {x"),
+            "Mismatched curly braces: missing closing '}' for '{' at column 4"
+        );
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let expr = TemplateExpr::parse("{-f(x.y) ?: !z}").unwrap();
+        let json = serde_json::to_string(&expr).unwrap();
+        assert_eq!(serde_json::from_str::<TemplateExpr>(&json).unwrap(), expr);
+
+        // Trees serialized before unary operators and calls existed still load
+        let old = r#"{"BinOp":{"op":"Add","left":{"Var":"a"},"right":{"Literal":"1"}}}"#;
+        assert_eq!(
+            serde_json::from_str::<TemplateExpr>(old).unwrap(),
+            TemplateExpr::BinOp {
+                op: TemplateOp::Add,
+                left: Box::new(TemplateExpr::Var("a".to_string())),
+                right: Box::new(TemplateExpr::Literal("1".to_string())),
+            }
+        );
+    }
+}
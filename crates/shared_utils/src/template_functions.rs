@@ -0,0 +1,335 @@
+//! Builtin functions callable from template expressions, e.g. `{round(cpu, 1)}`.
+
+use crate::template::format_number;
+use bytesize::ByteSize;
+use nix::libc;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Handler of a template function registered at runtime, e.g. by a plugin.
+pub type TemplateFn = Arc<dyn Fn(&[String]) -> Result<String, String> + Send + Sync>;
+
+static REGISTERED: OnceLock<RwLock<HashMap<String, (usize, TemplateFn)>>> = OnceLock::new();
+
+fn registered() -> &'static RwLock<HashMap<String, (usize, TemplateFn)>> {
+    REGISTERED.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Accepted argument count of a builtin function as `(min, max)`.
+fn builtin_arity(name: &str) -> Option<(usize, usize)> {
+    match name {
+        "upper" | "lower" | "trim" | "length" | "keys" | "floor" | "ceil" => Some((1, 1)),
+        "round" | "formatbytes" | "formatduration" => Some((1, 2)),
+        "strftime" => Some((2, 2)),
+        "substring" | "pad" => Some((2, 3)),
+        "clamp" | "replace" => Some((3, 3)),
+        _ => None,
+    }
+}
+
+/// Make `name` callable from templates, taking exactly `arity` arguments.
+///
+/// Builtins cannot be overridden, registering an existing name again
+/// replaces the previous handler.
+pub fn register_template_function(
+    name: &str,
+    arity: usize,
+    handler: TemplateFn,
+) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("Invalid template function name: '{}'", name));
+    }
+
+    if builtin_arity(name).is_some() || matches!(name, "true" | "false") {
+        return Err(format!("Cannot override builtin template function: {}", name));
+    }
+
+    registered().write().unwrap().insert(name.to_string(), (arity, handler));
+    Ok(())
+}
+
+fn arity_error(name: &str, expected: String, got: usize) -> String {
+    format!("{}() expects {} arguments, got {}", name, expected, got)
+}
+
+/// Evaluate a call to the function `name` with already evaluated arguments.
+pub(crate) fn call(name: &str, args: &[String]) -> Result<String, String> {
+    let Some((min, max)) = builtin_arity(name) else {
+        return call_registered(name, args);
+    };
+
+    if args.len() < min || args.len() > max {
+        let expected = if min == max { min.to_string() } else { format!("{} to {}", min, max) };
+        return Err(arity_error(name, expected, args.len()));
+    }
+
+    let arg = |i: usize| args.get(i).map(String::as_str);
+    match name {
+        "round" => round(number(args[0].as_str())?, arg(1).map(integer).transpose()?),
+        "floor" => Ok(format_number(number(&args[0])?.floor())),
+        "ceil" => Ok(format_number(number(&args[0])?.ceil())),
+        "clamp" => {
+            let (value, min, max) = (number(&args[0])?, number(&args[1])?, number(&args[2])?);
+            if min > max {
+                return Err(format!("clamp() min {} is greater than max {}", args[1], args[2]));
+            }
+            Ok(format_number(value.clamp(min, max)))
+        }
+        "formatbytes" => format_bytes(&args[0], arg(1).unwrap_or("iec")),
+        "formatduration" => format_duration(number(&args[0])?, arg(1).unwrap_or("short")),
+        "strftime" => strftime(integer(&args[0])?, &args[1]),
+        "upper" => Ok(args[0].to_uppercase()),
+        "lower" => Ok(args[0].to_lowercase()),
+        "trim" => Ok(args[0].trim().to_string()),
+        "substring" => {
+            let len = arg(2).map(integer).transpose()?;
+            Ok(substring(&args[0], integer(&args[1])?, len))
+        }
+        "replace" => Ok(args[0].replace(&args[1], &args[2])),
+        "pad" => pad(&args[0], integer(&args[1])?, arg(2).unwrap_or(" ")),
+        "length" => Ok(length(&args[0]).to_string()),
+        "keys" => keys(&args[0]),
+        _ => unreachable!(),
+    }
+}
+
+fn call_registered(name: &str, args: &[String]) -> Result<String, String> {
+    // Cloned out so the handler runs without holding the lock
+    let entry = registered().read().unwrap().get(name).cloned();
+    let Some((arity, handler)) = entry else {
+        return Err(format!("Unknown function: {}", name));
+    };
+
+    if args.len() != arity {
+        return Err(arity_error(name, arity.to_string(), args.len()));
+    }
+    handler(args)
+}
+
+fn number(value: &str) -> Result<f64, String> {
+    value.trim().parse::<f64>().map_err(|_| format!("'{}' is not a number", value))
+}
+
+fn integer(value: &str) -> Result<i64, String> {
+    let n = number(value)?;
+    if n.fract() != 0.0 {
+        return Err(format!("'{}' is not an integer", value));
+    }
+    Ok(n as i64)
+}
+
+/// `round(2.346)` gives `2`, `round(2.346, 2)` gives `2.35`.
+fn round(value: f64, digits: Option<i64>) -> Result<String, String> {
+    match digits {
+        None | Some(0) => Ok(format_number(value.round())),
+        Some(d) if (1..=15).contains(&d) => {
+            let factor = 10f64.powi(d as i32);
+            Ok(format!("{:.*}", d as usize, (value * factor).round() / factor))
+        }
+        Some(d) => Err(format!("round() digits must be between 0 and 15, got {}", d)),
+    }
+}
+
+/// Human readable byte count. The style is `iec` (`1.5 KiB`),
+/// `si` (`1.5 kB`) or `short` (`1.5K`).
+fn format_bytes(bytes: &str, style: &str) -> Result<String, String> {
+    let bytes = number(bytes)?;
+    if bytes < 0.0 {
+        return Err(format!("formatbytes() expects a positive number, got {}", bytes));
+    }
+
+    let display = ByteSize(bytes as u64).display();
+    match style {
+        "iec" => Ok(display.iec().to_string()),
+        "si" => Ok(display.si().to_string()),
+        "short" => Ok(display.iec_short().to_string()),
+        other => Err(format!("Unknown formatbytes() style '{}', expected iec, si or short", other)),
+    }
+}
+
+/// Formats seconds either as `1h 2m 3s` (`short`) or `1:02:03` (`clock`).
+fn format_duration(seconds: f64, style: &str) -> Result<String, String> {
+    let sign = if seconds < 0.0 { "-" } else { "" };
+    let total = seconds.abs().floor() as u64;
+    let (days, hours, minutes, secs) =
+        (total / 86400, total % 86400 / 3600, total % 3600 / 60, total % 60);
+
+    match style {
+        "short" => {
+            let parts: Vec<String> = [(days, "d"), (hours, "h"), (minutes, "m"), (secs, "s")]
+                .iter()
+                .filter(|(n, unit)| *n != 0 || *unit == "s")
+                .map(|(n, unit)| format!("{}{}", n, unit))
+                .collect();
+            // `1h 0m 5s` reads as `1h 5s`, but a lone `0s` is kept
+            let parts = if parts.len() > 1 && parts.last().is_some_and(|p| p == "0s") {
+                &parts[..parts.len() - 1]
+            } else {
+                &parts[..]
+            };
+            Ok(format!("{}{}", sign, parts.join(" ")))
+        }
+        "clock" => {
+            let hours = days * 24 + hours;
+            if hours > 0 {
+                Ok(format!("{}{}:{:02}:{:02}", sign, hours, minutes, secs))
+            } else {
+                Ok(format!("{}{}:{:02}", sign, minutes, secs))
+            }
+        }
+        other => {
+            Err(format!("Unknown formatduration() style '{}', expected short or clock", other))
+        }
+    }
+}
+
+/// Formats unix epoch seconds in local time, see `strftime(3)`.
+fn strftime(epoch: i64, format: &str) -> Result<String, String> {
+    let format_c =
+        CString::new(format).map_err(|_| "strftime() format contains a nul byte".to_string())?;
+    let time = epoch as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return Err(format!("Failed to convert {} to local time", epoch));
+    }
+
+    let mut buf = vec![0u8; 256];
+    let len = unsafe {
+        libc::strftime(buf.as_mut_ptr() as *mut libc::c_char, buf.len(), format_c.as_ptr(), &tm)
+    };
+    if len == 0 && !format.is_empty() {
+        return Err("strftime() output is too long".to_string());
+    }
+
+    Ok(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+/// Char based substring, a negative start counts from the end.
+fn substring(value: &str, start: i64, len: Option<i64>) -> String {
+    let count = value.chars().count() as i64;
+    let start = if start < 0 { (count + start).max(0) } else { start.min(count) };
+    let len = len.unwrap_or(count).max(0);
+
+    value.chars().skip(start as usize).take(len as usize).collect()
+}
+
+/// Pads `value` to `width` chars, on the left for a positive width and
+/// on the right for a negative one.
+fn pad(value: &str, width: i64, fill: &str) -> Result<String, String> {
+    let mut fill_chars = fill.chars();
+    let (Some(fill), None) = (fill_chars.next(), fill_chars.next()) else {
+        return Err(format!("pad() fill must be a single character, got '{}'", fill));
+    };
+
+    let missing = (width.unsigned_abs() as usize).saturating_sub(value.chars().count());
+    let padding: String = std::iter::repeat_n(fill, missing).collect();
+
+    if width < 0 {
+        Ok(format!("{}{}", value, padding))
+    } else {
+        Ok(format!("{}{}", padding, value))
+    }
+}
+
+/// Element count of a JSON array or object, char count otherwise.
+fn length(value: &str) -> usize {
+    match serde_json::from_str::<serde_json::Value>(value) {
+        Ok(serde_json::Value::Array(a)) => a.len(),
+        Ok(serde_json::Value::Object(o)) => o.len(),
+        _ => value.chars().count(),
+    }
+}
+
+/// Keys of a JSON object as a JSON array.
+fn keys(value: &str) -> Result<String, String> {
+    match serde_json::from_str::<serde_json::Value>(value) {
+        Ok(serde_json::Value::Object(o)) => {
+            Ok(serde_json::Value::from(o.keys().cloned().collect::<Vec<_>>()).to_string())
+        }
+        _ => Err(format!("keys() expects a JSON object, got '{}'", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call_with(name: &str, args: &[&str]) -> Result<String, String> {
+        call(name, &args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(call_with("round", &["2.5"]).unwrap(), "3");
+        assert_eq!(call_with("round", &["2.346", "2"]).unwrap(), "2.35");
+        assert_eq!(call_with("round", &["7", "1"]).unwrap(), "7.0");
+        assert_eq!(call_with("floor", &["-1.5"]).unwrap(), "-2");
+        assert_eq!(call_with("ceil", &["1.1"]).unwrap(), "2");
+        assert_eq!(call_with("clamp", &["120", "0", "100"]).unwrap(), "100");
+        assert!(call_with("clamp", &["1", "10", "0"]).is_err());
+        assert!(call_with("round", &["abc"]).is_err());
+    }
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(call_with("formatbytes", &["512"]).unwrap(), "512 B");
+        assert_eq!(call_with("formatbytes", &["1536"]).unwrap(), "1.5 KiB");
+        assert_eq!(call_with("formatbytes", &["1500", "si"]).unwrap(), "1.5 kB");
+        assert_eq!(call_with("formatduration", &["3725"]).unwrap(), "1h 2m 5s");
+        assert_eq!(call_with("formatduration", &["3605"]).unwrap(), "1h 5s");
+        assert_eq!(call_with("formatduration", &["3600"]).unwrap(), "1h");
+        assert_eq!(call_with("formatduration", &["0"]).unwrap(), "0s");
+        assert_eq!(call_with("formatduration", &["3725", "clock"]).unwrap(), "1:02:05");
+        assert_eq!(call_with("formatduration", &["65", "clock"]).unwrap(), "1:05");
+        // mid-month, so the result is the same in every timezone
+        assert_eq!(call_with("strftime", &["1700000000", "%Y-%m"]).unwrap(), "2023-11");
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(call_with("upper", &["ewwii"]).unwrap(), "EWWII");
+        assert_eq!(call_with("trim", &["  x "]).unwrap(), "x");
+        assert_eq!(call_with("substring", &["héllo", "1", "3"]).unwrap(), "éll");
+        assert_eq!(call_with("substring", &["hello", "-2"]).unwrap(), "lo");
+        assert_eq!(call_with("replace", &["a-b-c", "-", "+"]).unwrap(), "a+b+c");
+        assert_eq!(call_with("pad", &["7", "3", "0"]).unwrap(), "007");
+        assert_eq!(call_with("pad", &["ab", "-4"]).unwrap(), "ab  ");
+        assert_eq!(call_with("length", &["héllo"]).unwrap(), "5");
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(call_with("length", &["[1, 2, 3]"]).unwrap(), "3");
+        assert_eq!(call_with("keys", &[r#"{"a": 1, "b": 2}"#]).unwrap(), r#"["a","b"]"#);
+        assert!(call_with("keys", &["[1]"]).is_err());
+    }
+
+    #[test]
+    fn test_arity() {
+        assert_eq!(call_with("upper", &[]).unwrap_err(), "upper() expects 1 arguments, got 0");
+        assert_eq!(
+            call_with("round", &["1", "2", "3"]).unwrap_err(),
+            "round() expects 1 to 2 arguments, got 3"
+        );
+        assert_eq!(call_with("nope", &[]).unwrap_err(), "Unknown function: nope");
+    }
+
+    #[test]
+    fn test_registered() {
+        let handler: TemplateFn = Arc::new(|args| Ok(format!("{} EUR", args[0])));
+        register_template_function("test_euro", 1, handler.clone()).unwrap();
+
+        assert_eq!(call_with("test_euro", &["5"]).unwrap(), "5 EUR");
+        assert_eq!(
+            call_with("test_euro", &[]).unwrap_err(),
+            "test_euro() expects 1 arguments, got 0"
+        );
+        assert!(register_template_function("round", 1, handler.clone()).is_err());
+        assert!(register_template_function("my-fn", 1, handler).is_err());
+    }
+}